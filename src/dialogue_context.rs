@@ -0,0 +1,181 @@
+use std::collections::{HashMap, HashSet};
+
+use itertools::Itertools;
+use snips_nlu_ontology::{IntentClassifierResult, IntentParserResult, Slot};
+
+use crate::utils::{IntentName, SlotName};
+
+/// State of a conversation which is used to interpret a new utterance
+#[derive(Debug, Clone, Default)]
+pub struct DialogueContext {
+    /// Result of the previous turn, whose slots can be carried over
+    pub previous_result: Option<IntentParserResult>,
+    /// Multiplicative factors applied to the probabilities of the expected intents
+    pub intent_boosts: HashMap<IntentName, f32>,
+    /// Slot of the previous intent which the user has been asked to provide
+    pub elicited_slot: Option<SlotName>,
+}
+
+/// Turn in which a slot was provided
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotOrigin {
+    /// The slot was extracted from the current utterance
+    CurrentTurn,
+    /// The slot was extracted from the current utterance as the answer to an elicitation
+    Elicited,
+    /// The slot comes from the previous result
+    CarriedOver,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextualSlot {
+    pub slot: Slot,
+    pub origin: SlotOrigin,
+}
+
+/// Parsing result of an utterance interpreted in a dialogue context
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextualParserResult {
+    pub input: String,
+    pub intent: IntentClassifierResult,
+    pub slots: Vec<ContextualSlot>,
+}
+
+/// Applies the boosts to the intents probabilities, normalizes them and sorts the intents by
+/// decreasing probability
+pub fn apply_intent_boosts(
+    intents: Vec<IntentClassifierResult>,
+    intent_boosts: &HashMap<IntentName, f32>,
+) -> Vec<IntentClassifierResult> {
+    let boosted_intents = intents
+        .into_iter()
+        .map(|mut res| {
+            let boost = res
+                .intent_name
+                .as_ref()
+                .and_then(|intent| intent_boosts.get(intent))
+                .cloned()
+                .unwrap_or(1.0);
+            res.confidence_score *= boost;
+            res
+        })
+        .collect_vec();
+    let total_score: f32 = boosted_intents.iter().map(|res| res.confidence_score).sum();
+    boosted_intents
+        .into_iter()
+        .map(|mut res| {
+            if total_score > 0.0 {
+                res.confidence_score /= total_score;
+            }
+            res
+        })
+        .sorted_by(|a, b| b.confidence_score.partial_cmp(&a.confidence_score).unwrap())
+        .collect()
+}
+
+/// Merges the new slots with the previous ones, the previous slots being only kept when no new
+/// slot has the same slot name
+pub fn merge_slots(
+    new_slots: Vec<Slot>,
+    new_slots_origin: SlotOrigin,
+    previous_slots: &[Slot],
+) -> Vec<ContextualSlot> {
+    let new_slot_names: HashSet<&SlotName> = new_slots.iter().map(|slot| &slot.slot_name).collect();
+    let carried_over_slots = previous_slots
+        .iter()
+        .filter(|slot| !new_slot_names.contains(&slot.slot_name))
+        .map(|slot| ContextualSlot {
+            slot: slot.clone(),
+            origin: SlotOrigin::CarriedOver,
+        })
+        .collect_vec();
+    carried_over_slots
+        .into_iter()
+        .chain(new_slots.into_iter().map(|slot| ContextualSlot {
+            slot,
+            origin: new_slots_origin,
+        }))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maplit::hashmap;
+    use snips_nlu_ontology::SlotValue;
+
+    fn get_slot(raw_value: &str, slot_name: &str) -> Slot {
+        Slot {
+            raw_value: raw_value.to_string(),
+            value: SlotValue::Custom(raw_value.into()),
+            alternatives: vec![],
+            range: 0..raw_value.chars().count(),
+            entity: slot_name.to_string(),
+            slot_name: slot_name.to_string(),
+            confidence_score: None,
+        }
+    }
+
+    #[test]
+    fn test_apply_intent_boosts() {
+        // Given
+        let intents = vec![
+            IntentClassifierResult {
+                intent_name: Some("GetWeather".to_string()),
+                confidence_score: 0.5,
+            },
+            IntentClassifierResult {
+                intent_name: Some("BookFlight".to_string()),
+                confidence_score: 0.25,
+            },
+            IntentClassifierResult {
+                intent_name: None,
+                confidence_score: 0.25,
+            },
+        ];
+        let intent_boosts = hashmap! { "BookFlight".to_string() => 5.0 };
+
+        // When
+        let boosted_intents = apply_intent_boosts(intents, &intent_boosts);
+
+        // Then
+        let expected_intents = vec![
+            IntentClassifierResult {
+                intent_name: Some("BookFlight".to_string()),
+                confidence_score: 0.625,
+            },
+            IntentClassifierResult {
+                intent_name: Some("GetWeather".to_string()),
+                confidence_score: 0.25,
+            },
+            IntentClassifierResult {
+                intent_name: None,
+                confidence_score: 0.125,
+            },
+        ];
+        assert_eq!(expected_intents, boosted_intents);
+    }
+
+    #[test]
+    fn test_merge_slots() {
+        // Given
+        let previous_slots = vec![get_slot("paris", "destination"), get_slot("today", "date")];
+        let new_slots = vec![get_slot("tomorrow", "date")];
+
+        // When
+        let merged_slots = merge_slots(new_slots, SlotOrigin::Elicited, &previous_slots);
+
+        // Then
+        let expected_slots = vec![
+            ContextualSlot {
+                slot: get_slot("paris", "destination"),
+                origin: SlotOrigin::CarriedOver,
+            },
+            ContextualSlot {
+                slot: get_slot("tomorrow", "date"),
+                origin: SlotOrigin::Elicited,
+            },
+        ];
+        assert_eq!(expected_slots, merged_slots);
+    }
+}
@@ -1,12 +1,18 @@
+use std::fs::File;
 use std::path::Path;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex, RwLock};
 
+use itertools::Itertools;
 use log::info;
-use snips_nlu_ontology::{BuiltinEntity, BuiltinEntityKind};
-use snips_nlu_parsers::BuiltinEntityParser as _BuiltinEntityParser;
+use snips_nlu_ontology::{BuiltinEntity, BuiltinEntityKind, GrammarEntityKind};
+use snips_nlu_parsers::{BuiltinEntityParser as _BuiltinEntityParser, BuiltinParserMetadata};
 
-use super::utils::Cache;
-use anyhow::{anyhow, Result};
+use super::gazetteer_values::normalize_gazetteer_values;
+use super::utils::{Cache, ParserSwap};
+use crate::resources::normalizer::{
+    normalize_with_alignment, NormalizedText, Normalizer, TextNormalizer,
+};
+use anyhow::{anyhow, Context, Result};
 
 pub trait BuiltinEntityParser: Send + Sync {
     fn extract_entities(
@@ -16,10 +22,23 @@ pub trait BuiltinEntityParser: Send + Sync {
         use_cache: bool,
         max_alternative_resolved_values: usize,
     ) -> Result<Vec<BuiltinEntity>>;
+
+    /// Loads the parser stored at the provided path, and returns the function which replaces
+    /// the current parser with it and clears the cache
+    fn prepare_reload(&self, _path: &Path) -> Result<ParserSwap<'_>> {
+        Err(anyhow!("This builtin entity parser cannot be reloaded"))
+    }
+
+    /// Replaces the parser with the one stored at the provided path and clears the cache
+    fn reload(&self, path: &Path) -> Result<()> {
+        self.prepare_reload(path)?();
+        Ok(())
+    }
 }
 
 pub struct CachingBuiltinEntityParser {
-    parser: _BuiltinEntityParser,
+    parser: RwLock<_BuiltinEntityParser>,
+    normalizer: Arc<dyn Normalizer>,
     cache: Mutex<Cache<CacheKey, Vec<BuiltinEntity>>>,
 }
 
@@ -31,6 +50,11 @@ struct CacheKey {
 }
 
 impl BuiltinEntityParser for CachingBuiltinEntityParser {
+    /// Extracts the entities of the input, the gazetteer entities being matched in the input
+    /// normalized with the normalizer of the parser
+    ///
+    /// The grammars rely on the accents and punctuation of the input, hence grammar entities
+    /// are matched in the lowercased input.
     fn extract_entities(
         &self,
         sentence: &str,
@@ -38,44 +62,184 @@ impl BuiltinEntityParser for CachingBuiltinEntityParser {
         use_cache: bool,
         max_alternative_resolved_values: usize,
     ) -> Result<Vec<BuiltinEntity>> {
-        let lowercased_sentence = sentence.to_lowercase();
-        if !use_cache {
-            return self
-                .parser
+        let grammar_sentence = normalize_with_alignment(&TextNormalizer::default(), sentence);
+        let gazetteer_sentence = normalize_with_alignment(&*self.normalizer, sentence);
+        if gazetteer_sentence.text == grammar_sentence.text {
+            return self.extract_normalized_entities(
+                &grammar_sentence,
+                filter_entity_kinds,
+                use_cache,
+                max_alternative_resolved_values,
+            );
+        }
+        let grammar_entities = self
+            .extract_normalized_entities(
+                &grammar_sentence,
+                filter_entity_kinds,
+                use_cache,
+                max_alternative_resolved_values,
+            )?
+            .into_iter()
+            .filter(|entity| is_grammar_entity(entity.entity_kind));
+        let gazetteer_entities = self
+            .extract_normalized_entities(
+                &gazetteer_sentence,
+                filter_entity_kinds,
+                use_cache,
+                max_alternative_resolved_values,
+            )?
+            .into_iter()
+            .filter(|entity| !is_grammar_entity(entity.entity_kind));
+        Ok(grammar_entities
+            .chain(gazetteer_entities)
+            .sorted_by_key(|entity| entity.range.start)
+            .collect())
+    }
+
+    fn prepare_reload(&self, path: &Path) -> Result<ParserSwap<'_>> {
+        let parser = load_parser(path, &*self.normalizer)?;
+        Ok(Box::new(move || {
+            // The cache lock is held while swapping the parser so that no stale entity gets
+            // cached
+            let mut cache = self.cache.lock().unwrap();
+            *self.parser.write().unwrap() = parser;
+            cache.clear();
+            info!("Builtin entity parser reloaded");
+        }))
+    }
+}
+
+impl CachingBuiltinEntityParser {
+    fn extract_normalized_entities(
+        &self,
+        normalized_sentence: &NormalizedText,
+        filter_entity_kinds: Option<&[BuiltinEntityKind]>,
+        use_cache: bool,
+        max_alternative_resolved_values: usize,
+    ) -> Result<Vec<BuiltinEntity>> {
+        let entities = if !use_cache {
+            self.parser
+                .read()
+                .unwrap()
                 .extract_entities(
-                    &lowercased_sentence,
+                    &normalized_sentence.text,
                     filter_entity_kinds,
                     max_alternative_resolved_values,
                 )
-                .map_err(|e| anyhow!(e));
-        }
-        let cache_key = CacheKey {
-            input: lowercased_sentence,
-            kinds: filter_entity_kinds.map(|entity_kinds| entity_kinds.to_vec()),
-            max_alternative_resolved_values,
+                .map_err(|e| anyhow!(e))?
+        } else {
+            let cache_key = CacheKey {
+                input: normalized_sentence.text.clone(),
+                kinds: filter_entity_kinds.map(|entity_kinds| entity_kinds.to_vec()),
+                max_alternative_resolved_values,
+            };
+            self.cache
+                .lock()
+                .unwrap()
+                .try_cache(&cache_key, |cache_key| {
+                    self.parser
+                        .read()
+                        .unwrap()
+                        .extract_entities(
+                            &cache_key.input,
+                            filter_entity_kinds,
+                            max_alternative_resolved_values,
+                        )
+                        .map_err(|e| anyhow!(e))
+                })?
         };
-
-        self.cache
-            .lock()
-            .unwrap()
-            .try_cache(&cache_key, |cache_key| {
-                self.parser
-                    .extract_entities(
-                        &cache_key.input,
-                        filter_entity_kinds,
-                        max_alternative_resolved_values,
-                    )
-                    .map_err(|e| anyhow!(e))
+        Ok(entities
+            .into_iter()
+            .map(|mut entity| {
+                entity.range = normalized_sentence.original_range(&entity.range);
+                entity
             })
+            .collect())
     }
-}
 
-impl CachingBuiltinEntityParser {
-    pub fn from_path<P: AsRef<Path>>(path: P, cache_capacity: usize) -> Result<Self> {
+    /// Loads the parser which matches gazetteer entities in inputs normalized with the
+    /// provided normalizer, the trained gazetteer values being normalized the same way
+    pub fn from_path<P: AsRef<Path>>(
+        path: P,
+        cache_capacity: usize,
+        normalizer: Arc<dyn Normalizer>,
+    ) -> Result<Self> {
         info!("Loading builtin entity parser ({:?}) ...", path.as_ref());
-        let parser = _BuiltinEntityParser::from_path(path).map_err(|e| anyhow!(e))?;
+        let parser = load_parser(path.as_ref(), &*normalizer)?;
         let cache = Mutex::new(Cache::new(cache_capacity));
         info!("Builtin entity parser loaded");
-        Ok(Self { parser, cache })
+        Ok(Self {
+            parser: RwLock::new(parser),
+            normalizer,
+            cache,
+        })
+    }
+}
+
+fn load_parser(path: &Path, normalizer: &dyn Normalizer) -> Result<_BuiltinEntityParser> {
+    let metadata_path = path.join("metadata.json");
+    let metadata_file = File::open(&metadata_path).with_context(|| {
+        format!(
+            "Cannot open metadata file for builtin entity parser at path: {:?}",
+            metadata_path
+        )
+    })?;
+    let metadata: BuiltinParserMetadata = serde_json::from_reader(metadata_file)
+        .with_context(|| "Cannot deserialize builtin entity parser metadata")?;
+    let normalized_parser_dir = metadata
+        .gazetteer_parser
+        .map(|gazetteer_parser_dir| {
+            normalize_gazetteer_values(path, &gazetteer_parser_dir, normalizer)
+        })
+        .transpose()?
+        .flatten();
+    if let Some(normalized_parser_dir) = normalized_parser_dir {
+        _BuiltinEntityParser::from_path(normalized_parser_dir.path())
+    } else {
+        _BuiltinEntityParser::from_path(path)
+    }
+    .map_err(|e| anyhow!(e))
+}
+
+fn is_grammar_entity(entity_kind: BuiltinEntityKind) -> bool {
+    GrammarEntityKind::from_identifier(entity_kind.identifier()).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::models::NormalizationConfig;
+
+    #[test]
+    fn test_grammar_entities_should_be_matched_without_folding() {
+        // Given
+        let parser_path = Path::new("data")
+            .join("tests")
+            .join("models")
+            .join("nlu_engine_music")
+            .join("builtin_entity_parser");
+        let normalizer = TextNormalizer::new(NormalizationConfig {
+            unicode_nfkc: true,
+            fold_accents: true,
+            fold_punctuation: true,
+        });
+        let parser =
+            CachingBuiltinEntityParser::from_path(parser_path, 1000, Arc::new(normalizer)).unwrap();
+
+        // When
+        let entities = parser
+            .extract_entities(
+                "rappelle moi le 3 août",
+                Some(&[BuiltinEntityKind::Datetime]),
+                true,
+                0,
+            )
+            .unwrap();
+
+        // Then
+        assert!(entities
+            .iter()
+            .any(|entity| entity.value.ends_with("3 août") && entity.range.end == 22));
     }
 }
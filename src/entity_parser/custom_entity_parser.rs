@@ -2,7 +2,7 @@ use std::fs::File;
 use std::path::Path;
 use std::result::Result as StdResult;
 use std::str::FromStr;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex, RwLock};
 
 use itertools::Itertools;
 use log::info;
@@ -13,8 +13,13 @@ use snips_nlu_parsers::{GazetteerEntityMatch, GazetteerParser};
 use snips_nlu_utils::language::Language as NluUtilsLanguage;
 use snips_nlu_utils::token::*;
 
-use crate::entity_parser::utils::Cache;
+use crate::entity_parser::ephemeral_entities::add_ephemeral_entities;
+use crate::entity_parser::fuzzy_matching::{FuzzyEntityIndex, FuzzyMatchingConfig};
+use crate::entity_parser::gazetteer_values::normalize_gazetteer_values;
+use crate::entity_parser::regex_entities::RegexEntities;
+use crate::entity_parser::utils::{merge_overlapping_entities, Cache, ParserSwap};
 use crate::language::FromLanguage;
+use crate::resources::normalizer::{normalize, normalize_with_alignment, Normalizer};
 use crate::utils::EntityName;
 use anyhow::{anyhow, Context, Result};
 
@@ -27,6 +32,36 @@ pub trait CustomEntityParser: Send + Sync {
         filter_entity_kinds: Option<&[String]>,
         max_alternative_resolved_values: usize,
     ) -> Result<Vec<CustomEntity>>;
+
+    /// Loads the parser stored at the provided path, and returns the function which replaces
+    /// the current parser with it and clears the cache
+    fn prepare_reload(&self, _path: &Path) -> Result<ParserSwap<'_>> {
+        Err(anyhow!("This custom entity parser cannot be reloaded"))
+    }
+
+    /// Replaces the parser with the one stored at the provided path and clears the cache
+    fn reload(&self, path: &Path) -> Result<()> {
+        self.prepare_reload(path)?();
+        Ok(())
+    }
+
+    /// Returns the score of an extracted entity, which is lower than 1.0 when the entity was
+    /// matched despite typos
+    fn match_score(&self, _entity: &CustomEntity) -> Result<f32> {
+        Ok(1.0)
+    }
+
+    /// Adds a pattern matching values of the entity, in addition to the trained ones
+    fn add_regex_entity(&self, _entity: &str, _pattern: &str) -> Result<()> {
+        Err(anyhow!(
+            "This custom entity parser does not support regex entities"
+        ))
+    }
+
+    /// Tells whether the parser matches the raw entity values, their stems, or both
+    fn parser_usage(&self) -> CustomEntityParserUsage {
+        CustomEntityParserUsage::WithoutStems
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -53,7 +88,11 @@ impl<'de> Deserialize<'de> for CustomEntityParserUsage {
 
 pub struct CachingCustomEntityParser {
     language: NluUtilsLanguage,
-    parser: GazetteerParser<String>,
+    parser_usage: RwLock<CustomEntityParserUsage>,
+    parser: RwLock<GazetteerParser<String>>,
+    fuzzy_index: RwLock<Option<FuzzyEntityIndex>>,
+    regex_entities: RwLock<RegexEntities>,
+    normalizer: Arc<dyn Normalizer>,
     cache: Mutex<Cache<CacheKey, Vec<CustomEntity>>>,
 }
 
@@ -71,14 +110,15 @@ impl CustomEntityParser for CachingCustomEntityParser {
         filter_entity_kinds: Option<&[String]>,
         max_alternative_resolved_values: usize,
     ) -> Result<Vec<CustomEntity>> {
-        let lowercased_sentence = sentence.to_lowercase();
+        let normalized_sentence = normalize_with_alignment(&*self.normalizer, sentence);
         let cache_key = CacheKey {
-            input: lowercased_sentence,
+            input: normalized_sentence.text.clone(),
             kinds: filter_entity_kinds.map(|entity_kinds| entity_kinds.to_vec()),
             max_alternative_resolved_values,
         };
 
-        self.cache
+        let entities = self
+            .cache
             .lock()
             .unwrap()
             .try_cache(&cache_key, |cache_key| {
@@ -87,7 +127,82 @@ impl CustomEntityParser for CachingCustomEntityParser {
                     filter_entity_kinds,
                     max_alternative_resolved_values,
                 )
+            })?;
+        // Ephemeral and regex entities are matched outside of the cache which only holds trained
+        // entities
+        let entities = add_ephemeral_entities(
+            entities,
+            &cache_key.input,
+            self.language,
+            &*self.normalizer,
+            filter_entity_kinds,
+            max_alternative_resolved_values,
+        )
+        .into_iter()
+        .map(|mut entity| {
+            entity.range = normalized_sentence.original_range(&entity.range);
+            entity
+        })
+        .collect();
+        let regex_entities = self
+            .regex_entities
+            .read()
+            .unwrap()
+            .extract_entities(&cache_key.input, self.language, filter_entity_kinds)
+            .into_iter()
+            .map(|mut entity| {
+                // Values are resolved to the text of the input rather than its normalized form
+                entity.range = normalized_sentence.original_range(&entity.range);
+                entity.value = sentence
+                    .chars()
+                    .skip(entity.range.start)
+                    .take(entity.range.len())
+                    .collect();
+                entity.resolved_value = entity.value.clone();
+                entity
             })
+            .collect();
+        Ok(merge_overlapping_entities(entities, regex_entities))
+    }
+
+    fn prepare_reload(&self, path: &Path) -> Result<ParserSwap<'_>> {
+        let (_, parser_usage, parser, fuzzy_index) = Self::load_parser(path, &self.normalizer)?;
+        let regex_entities = RegexEntities::from_path(path)?;
+        Ok(Box::new(move || {
+            // The cache lock is held while swapping the parser so that no stale entity gets
+            // cached
+            let mut cache = self.cache.lock().unwrap();
+            *self.parser_usage.write().unwrap() = parser_usage;
+            *self.parser.write().unwrap() = parser;
+            *self.fuzzy_index.write().unwrap() = fuzzy_index;
+            self.regex_entities
+                .write()
+                .unwrap()
+                .replace_declared_patterns(regex_entities);
+            cache.clear();
+            info!("Custom entity parser reloaded");
+        }))
+    }
+
+    fn match_score(&self, entity: &CustomEntity) -> Result<f32> {
+        let fuzzy_index = self.fuzzy_index.read().unwrap();
+        if let Some(fuzzy_index) = fuzzy_index.as_ref() {
+            let tokens = tokenize(&normalize(&*self.normalizer, &entity.value), self.language);
+            fuzzy_index.match_score(entity, &tokens, &self.parser.read().unwrap())
+        } else {
+            Ok(1.0)
+        }
+    }
+
+    fn add_regex_entity(&self, entity: &str, pattern: &str) -> Result<()> {
+        self.regex_entities
+            .write()
+            .unwrap()
+            .add_pattern(entity, pattern)
+    }
+
+    fn parser_usage(&self) -> CustomEntityParserUsage {
+        self.parser_usage.read().unwrap().clone()
     }
 }
 
@@ -100,9 +215,9 @@ impl CachingCustomEntityParser {
     ) -> Result<Vec<CustomEntity>> {
         let tokens = tokenize(sentence, self.language);
         let shifts = compute_char_shifts(&tokens);
-        let cleaned_input = tokens.into_iter().map(|token| token.value).join(" ");
-        Ok(self
-            .parser
+        let cleaned_input = tokens.iter().map(|token| &*token.value).join(" ");
+        let parser = self.parser.read().unwrap();
+        let entities = parser
             .extract_entities(
                 &cleaned_input,
                 filter_entity_kinds,
@@ -118,7 +233,19 @@ impl CachingCustomEntityParser {
                 entity_match.range = remapped_range_start..remapped_range_end;
                 entity_match
             })
-            .collect())
+            .collect();
+        if let Some(fuzzy_index) = self.fuzzy_index.read().unwrap().as_ref() {
+            fuzzy_index.add_fuzzy_entities(
+                entities,
+                sentence,
+                &tokens,
+                &parser,
+                filter_entity_kinds,
+                max_alternative_resolved_values,
+            )
+        } else {
+            Ok(entities)
+        }
     }
 }
 
@@ -157,11 +284,44 @@ pub struct CustomEntityParserMetadata {
     pub language: String,
     pub parser_directory: String,
     pub parser_usage: CustomEntityParserUsage,
+    #[serde(default)]
+    pub fuzzy_matching: Option<FuzzyMatchingConfig>,
 }
 
 impl CachingCustomEntityParser {
-    pub fn from_path<P: AsRef<Path>>(path: P, cache_capacity: usize) -> Result<Self> {
+    /// Loads the parser which matches entities in inputs normalized with the provided
+    /// normalizer, the trained values being normalized the same way
+    pub fn from_path<P: AsRef<Path>>(
+        path: P,
+        cache_capacity: usize,
+        normalizer: Arc<dyn Normalizer>,
+    ) -> Result<Self> {
         info!("Loading custom entity parser ({:?}) ...", path.as_ref());
+        let (language, parser_usage, parser, fuzzy_index) = Self::load_parser(&path, &normalizer)?;
+        let regex_entities = RegexEntities::from_path(&path)?;
+        let cache = Mutex::new(Cache::new(cache_capacity));
+        info!("Custom entity parser loaded");
+        Ok(Self {
+            language,
+            parser_usage: RwLock::new(parser_usage),
+            parser: RwLock::new(parser),
+            fuzzy_index: RwLock::new(fuzzy_index),
+            regex_entities: RwLock::new(regex_entities),
+            normalizer,
+            cache,
+        })
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn load_parser<P: AsRef<Path>>(
+        path: P,
+        normalizer: &Arc<dyn Normalizer>,
+    ) -> Result<(
+        NluUtilsLanguage,
+        CustomEntityParserUsage,
+        GazetteerParser<String>,
+        Option<FuzzyEntityIndex>,
+    )> {
         let metadata_path = path.as_ref().join("metadata.json");
         let metadata_file = File::open(&metadata_path).with_context(|| {
             format!(
@@ -175,20 +335,42 @@ impl CachingCustomEntityParser {
             Language::from_str(&metadata.language).map_err(|e| anyhow!(e))?,
         );
         let gazetteer_parser_path = path.as_ref().join(&metadata.parser_directory);
-        let parser = GazetteerParser::from_path(gazetteer_parser_path).map_err(|e| anyhow!(e))?;
-        let cache = Mutex::new(Cache::new(cache_capacity));
-        info!("Custom entity parser loaded");
-        Ok(Self {
-            language,
-            parser,
-            cache,
-        })
+        let normalized_parser_dir =
+            normalize_gazetteer_values(path.as_ref(), &metadata.parser_directory, &**normalizer)?;
+        let parser = if let Some(normalized_parser_dir) = normalized_parser_dir {
+            GazetteerParser::from_path(
+                normalized_parser_dir
+                    .path()
+                    .join(&metadata.parser_directory),
+            )
+        } else {
+            GazetteerParser::from_path(&gazetteer_parser_path)
+        }
+        .map_err(|e| anyhow!(e))?;
+        let fuzzy_index = metadata
+            .fuzzy_matching
+            .map(|config| {
+                FuzzyEntityIndex::from_path(
+                    &gazetteer_parser_path,
+                    &config,
+                    language,
+                    normalizer.clone(),
+                )
+            })
+            .transpose()?;
+        Ok((language, metadata.parser_usage, parser, fuzzy_index))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::entity_parser::ephemeral_entities::{
+        with_ephemeral_entity_values, EphemeralEntityValue,
+    };
+    use crate::models::NormalizationConfig;
+    use crate::resources::normalizer::TextNormalizer;
+    use crate::testutils::write_gazetteer_parser;
 
     #[test]
     fn test_compute_char_shifts() {
@@ -215,7 +397,12 @@ mod tests {
             .join("nlu_engine_beverage")
             .join("custom_entity_parser");
 
-        let custom_entity_parser = CachingCustomEntityParser::from_path(parser_path, 1000).unwrap();
+        let custom_entity_parser = CachingCustomEntityParser::from_path(
+            parser_path,
+            1000,
+            Arc::new(TextNormalizer::default()),
+        )
+        .unwrap();
         let input = "Make me a  ?hot tea";
 
         // When
@@ -235,6 +422,54 @@ mod tests {
         assert_eq!(expected_entities, entities);
     }
 
+    #[test]
+    fn test_custom_entity_parser_normalizes_trained_values() {
+        // Given
+        let parser_dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            parser_dir.path().join("metadata.json"),
+            r#"{"language": "fr", "parser_directory": "parser", "parser_usage": 1}"#,
+        )
+        .unwrap();
+        write_gazetteer_parser(
+            &parser_dir.path().join("parser"),
+            "drink",
+            &[("café", "Café")],
+        );
+        let normalizer = TextNormalizer::new(NormalizationConfig {
+            unicode_nfkc: false,
+            fold_accents: true,
+            fold_punctuation: false,
+        });
+        let custom_entity_parser =
+            CachingCustomEntityParser::from_path(parser_dir.path(), 1000, Arc::new(normalizer))
+                .unwrap();
+
+        // When
+        let entities = custom_entity_parser
+            .extract_entities("un café et un cafe", None, 0)
+            .unwrap();
+
+        // Then
+        let expected_entities = vec![
+            CustomEntity {
+                value: "cafe".to_string(),
+                resolved_value: "Café".to_string(),
+                alternative_resolved_values: vec![],
+                range: 3..7,
+                entity_identifier: "drink".to_string(),
+            },
+            CustomEntity {
+                value: "cafe".to_string(),
+                resolved_value: "Café".to_string(),
+                alternative_resolved_values: vec![],
+                range: 14..18,
+                entity_identifier: "drink".to_string(),
+            },
+        ];
+        assert_eq!(expected_entities, entities);
+    }
+
     #[test]
     fn test_custom_entity_parser_caches_properly() {
         // Given
@@ -244,7 +479,12 @@ mod tests {
             .join("nlu_engine_game")
             .join("custom_entity_parser");
 
-        let custom_entity_parser = CachingCustomEntityParser::from_path(parser_path, 1000).unwrap();
+        let custom_entity_parser = CachingCustomEntityParser::from_path(
+            parser_path,
+            1000,
+            Arc::new(TextNormalizer::default()),
+        )
+        .unwrap();
         let input = "I want to play invader";
 
         // When
@@ -280,4 +520,87 @@ mod tests {
             entities_with_alternatives
         );
     }
+
+    #[test]
+    fn test_custom_entity_parser_matches_ephemeral_values_without_caching_them() {
+        // Given
+        let parser_path = Path::new("data")
+            .join("tests")
+            .join("models")
+            .join("nlu_engine_game")
+            .join("custom_entity_parser");
+
+        let custom_entity_parser = CachingCustomEntityParser::from_path(
+            parser_path,
+            1000,
+            Arc::new(TextNormalizer::default()),
+        )
+        .unwrap();
+        let input = "I want to play space quest";
+        let ephemeral_values = vec![EphemeralEntityValue {
+            entity: "game".to_string(),
+            value: "Space Quest VI".to_string(),
+            synonyms: vec!["space quest".to_string()],
+        }];
+
+        // When
+        let ephemeral_entities = with_ephemeral_entity_values(ephemeral_values, || {
+            custom_entity_parser.extract_entities(input, None, 0)
+        })
+        .unwrap();
+        let entities = custom_entity_parser
+            .extract_entities(input, None, 0)
+            .unwrap();
+
+        // Then
+        let expected_ephemeral_entities = vec![CustomEntity {
+            value: "space quest".to_string(),
+            resolved_value: "Space Quest VI".to_string(),
+            alternative_resolved_values: vec![],
+            range: 15..26,
+            entity_identifier: "game".to_string(),
+        }];
+        assert_eq!(expected_ephemeral_entities, ephemeral_entities);
+        assert_eq!(Vec::<CustomEntity>::new(), entities);
+    }
+
+    #[test]
+    fn test_custom_entity_parser_matches_regex_entities() {
+        // Given
+        let parser_path = Path::new("data")
+            .join("tests")
+            .join("models")
+            .join("nlu_engine_game")
+            .join("custom_entity_parser");
+
+        let custom_entity_parser = CachingCustomEntityParser::from_path(
+            parser_path,
+            1000,
+            Arc::new(TextNormalizer::default()),
+        )
+        .unwrap();
+        custom_entity_parser
+            .add_regex_entity("game", r"space quest [ivx]+")
+            .unwrap();
+        let input = "I want to play Space Quest VI";
+
+        // When
+        let entities = custom_entity_parser
+            .extract_entities(input, None, 0)
+            .unwrap();
+        let entities_out_of_scope = custom_entity_parser
+            .extract_entities(input, Some(&["other_entity".to_string()]), 0)
+            .unwrap();
+
+        // Then
+        let expected_entities = vec![CustomEntity {
+            value: "Space Quest VI".to_string(),
+            resolved_value: "Space Quest VI".to_string(),
+            alternative_resolved_values: vec![],
+            range: 15..29,
+            entity_identifier: "game".to_string(),
+        }];
+        assert_eq!(expected_entities, entities);
+        assert_eq!(Vec::<CustomEntity>::new(), entities_out_of_scope);
+    }
 }
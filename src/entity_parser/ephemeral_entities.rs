@@ -0,0 +1,274 @@
+use std::cell::RefCell;
+
+use itertools::Itertools;
+use snips_nlu_utils::language::Language as NluUtilsLanguage;
+use snips_nlu_utils::token::{tokenize, tokenize_light};
+
+use super::custom_entity_parser::CustomEntity;
+use super::utils::merge_overlapping_entities;
+use crate::resources::normalizer::{normalize, Normalizer};
+use crate::utils::EntityName;
+
+/// Custom entity value which is only matched during a single parse
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EphemeralEntityValue {
+    pub entity: EntityName,
+    pub value: String,
+    pub synonyms: Vec<String>,
+}
+
+/// Ephemeral value along with the forms which are matched in the input
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EphemeralEntityForms {
+    pub entity: EntityName,
+    pub resolved_value: String,
+    pub raw_values: Vec<String>,
+}
+
+impl From<EphemeralEntityValue> for EphemeralEntityForms {
+    fn from(entity_value: EphemeralEntityValue) -> Self {
+        let raw_values = std::iter::once(entity_value.value.clone())
+            .chain(entity_value.synonyms)
+            .collect();
+        Self {
+            entity: entity_value.entity,
+            resolved_value: entity_value.value,
+            raw_values,
+        }
+    }
+}
+
+thread_local! {
+    static EPHEMERAL_ENTITY_VALUES: RefCell<Vec<EphemeralEntityForms>> =
+        const { RefCell::new(Vec::new()) };
+}
+
+/// Restores the previous ephemeral values of the thread, even when the parse panics
+struct EphemeralValuesGuard {
+    previous_values: Option<Vec<EphemeralEntityForms>>,
+}
+
+impl Drop for EphemeralValuesGuard {
+    fn drop(&mut self) {
+        if let Some(previous_values) = self.previous_values.take() {
+            EPHEMERAL_ENTITY_VALUES.with(|values| *values.borrow_mut() = previous_values);
+        }
+    }
+}
+
+/// Runs `f` with the ephemeral values being matched by the custom entity parsers of the
+/// current thread, in addition to the values they were trained on
+pub fn with_ephemeral_entity_values<T, V, F>(entity_values: Vec<V>, f: F) -> T
+where
+    V: Into<EphemeralEntityForms>,
+    F: FnOnce() -> T,
+{
+    let entity_values = entity_values.into_iter().map(Into::into).collect();
+    let previous_values = EPHEMERAL_ENTITY_VALUES.with(|values| values.replace(entity_values));
+    let _guard = EphemeralValuesGuard {
+        previous_values: Some(previous_values),
+    };
+    f()
+}
+
+/// Adds the matches of the ephemeral values of the current thread to the trained entities
+///
+/// As in the gazetteer parser, overlapping matches are resolved by keeping the longest ones.
+/// Trained entities are preferred over ephemeral ones having the same range.
+pub fn add_ephemeral_entities(
+    entities: Vec<CustomEntity>,
+    sentence: &str,
+    language: NluUtilsLanguage,
+    normalizer: &dyn Normalizer,
+    filter_entity_kinds: Option<&[String]>,
+    max_alternative_resolved_values: usize,
+) -> Vec<CustomEntity> {
+    let ephemeral_entities = EPHEMERAL_ENTITY_VALUES.with(|values| {
+        let values = values.borrow();
+        let scoped_values = values
+            .iter()
+            .filter(|value| {
+                filter_entity_kinds
+                    .map(|kinds| kinds.contains(&value.entity))
+                    .unwrap_or(true)
+            })
+            .collect_vec();
+        if scoped_values.is_empty() {
+            return vec![];
+        }
+        match_ephemeral_values(
+            sentence,
+            &scoped_values,
+            language,
+            normalizer,
+            max_alternative_resolved_values,
+        )
+    });
+    merge_overlapping_entities(entities, ephemeral_entities)
+}
+
+fn match_ephemeral_values(
+    sentence: &str,
+    entity_values: &[&EphemeralEntityForms],
+    language: NluUtilsLanguage,
+    normalizer: &dyn Normalizer,
+    max_alternative_resolved_values: usize,
+) -> Vec<CustomEntity> {
+    let tokens = tokenize(sentence, language);
+    let token_values = tokens
+        .iter()
+        .map(|token| token.value.to_lowercase())
+        .collect_vec();
+    let mut entities: Vec<CustomEntity> = vec![];
+    for entity_value in entity_values {
+        let raw_values = entity_value
+            .raw_values
+            .iter()
+            .map(|raw_value| tokenize_light(&normalize(normalizer, raw_value), language))
+            .filter(|raw_tokens| !raw_tokens.is_empty())
+            .unique();
+        for raw_tokens in raw_values {
+            for start in 0..=token_values.len().saturating_sub(raw_tokens.len()) {
+                let end = start + raw_tokens.len();
+                if end > token_values.len() || token_values[start..end] != raw_tokens[..] {
+                    continue;
+                }
+                let range = tokens[start].char_range.start..tokens[end - 1].char_range.end;
+                let existing_match = entities.iter_mut().find(|entity| {
+                    entity.range == range && entity.entity_identifier == entity_value.entity
+                });
+                if let Some(entity) = existing_match {
+                    // Values sharing a synonym are returned as alternatives
+                    if entity.resolved_value != entity_value.resolved_value
+                        && !entity
+                            .alternative_resolved_values
+                            .contains(&entity_value.resolved_value)
+                        && entity.alternative_resolved_values.len()
+                            < max_alternative_resolved_values
+                    {
+                        entity
+                            .alternative_resolved_values
+                            .push(entity_value.resolved_value.clone());
+                    }
+                    continue;
+                }
+                entities.push(CustomEntity {
+                    value: sentence
+                        .chars()
+                        .skip(range.start)
+                        .take(range.len())
+                        .collect(),
+                    resolved_value: entity_value.resolved_value.clone(),
+                    alternative_resolved_values: vec![],
+                    range,
+                    entity_identifier: entity_value.entity.clone(),
+                });
+            }
+        }
+    }
+    entities
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::normalizer::TextNormalizer;
+
+    fn get_entity(value: &str, resolved_value: &str, start: usize, entity: &str) -> CustomEntity {
+        CustomEntity {
+            value: value.to_string(),
+            resolved_value: resolved_value.to_string(),
+            alternative_resolved_values: vec![],
+            range: start..start + value.chars().count(),
+            entity_identifier: entity.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_add_ephemeral_entities() {
+        // Given
+        let ephemeral_values = vec![
+            EphemeralEntityValue {
+                entity: "device".to_string(),
+                value: "Kitchen Lamp".to_string(),
+                synonyms: vec!["the lamp".to_string()],
+            },
+            EphemeralEntityValue {
+                entity: "device".to_string(),
+                value: "Radio".to_string(),
+                synonyms: vec![],
+            },
+        ];
+        let trained_entities = vec![get_entity("on", "on", 5, "state")];
+        let sentence = "turn on the lamp and the radio";
+
+        // When
+        let entities = with_ephemeral_entity_values(ephemeral_values, || {
+            add_ephemeral_entities(
+                trained_entities.clone(),
+                sentence,
+                NluUtilsLanguage::EN,
+                &TextNormalizer::default(),
+                None,
+                0,
+            )
+        });
+        let entities_out_of_scope = add_ephemeral_entities(
+            trained_entities.clone(),
+            sentence,
+            NluUtilsLanguage::EN,
+            &TextNormalizer::default(),
+            None,
+            0,
+        );
+
+        // Then
+        let expected_entities = vec![
+            get_entity("on", "on", 5, "state"),
+            get_entity("the lamp", "Kitchen Lamp", 8, "device"),
+            get_entity("radio", "Radio", 25, "device"),
+        ];
+        assert_eq!(expected_entities, entities);
+        assert_eq!(trained_entities, entities_out_of_scope);
+    }
+
+    #[test]
+    fn test_add_ephemeral_entities_should_keep_longest_matches() {
+        // Given
+        let ephemeral_values = vec![
+            EphemeralEntityValue {
+                entity: "device".to_string(),
+                value: "Living Room Lamp".to_string(),
+                synonyms: vec!["living room lamp".to_string()],
+            },
+            EphemeralEntityValue {
+                entity: "room".to_string(),
+                value: "Living Room".to_string(),
+                synonyms: vec![],
+            },
+        ];
+        let trained_entities = vec![get_entity("lamp", "lamp", 23, "device")];
+        let sentence = "switch off living room lamp";
+
+        // When
+        let entities = with_ephemeral_entity_values(ephemeral_values, || {
+            add_ephemeral_entities(
+                trained_entities,
+                sentence,
+                NluUtilsLanguage::EN,
+                &TextNormalizer::default(),
+                Some(&["device".to_string()]),
+                0,
+            )
+        });
+
+        // Then
+        let expected_entities = vec![get_entity(
+            "living room lamp",
+            "Living Room Lamp",
+            11,
+            "device",
+        )];
+        assert_eq!(expected_entities, entities);
+    }
+}
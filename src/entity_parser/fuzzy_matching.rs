@@ -0,0 +1,532 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context, Result};
+use itertools::Itertools;
+use serde::Deserialize;
+use snips_nlu_parsers::{GazetteerParser, GazetteerParserMetadata};
+use snips_nlu_utils::language::Language as NluUtilsLanguage;
+use snips_nlu_utils::range::ranges_overlap;
+use snips_nlu_utils::token::{tokenize_light, Token};
+
+use super::custom_entity_parser::CustomEntity;
+use super::gazetteer_values::read_gazetteer_values;
+use crate::resources::normalizer::{normalize, Normalizer};
+use crate::utils::EntityName;
+
+/// Configuration of the typo tolerant matching of custom entities
+#[derive(Debug, Clone, Deserialize)]
+pub struct FuzzyMatchingConfig {
+    /// Maximum ratio between the number of edits and the length of a token, applied to all the
+    /// entities when defined
+    #[serde(default)]
+    pub max_edit_ratio: Option<f32>,
+    /// Maximum edit ratios of specific entities
+    #[serde(default)]
+    pub entities_max_edit_ratio: HashMap<EntityName, f32>,
+}
+
+impl FuzzyMatchingConfig {
+    fn entity_max_edit_ratio(&self, entity: &str) -> Option<f32> {
+        self.entities_max_edit_ratio
+            .get(entity)
+            .cloned()
+            .or(self.max_edit_ratio)
+    }
+}
+
+/// Index of the tokens of the custom entities values, used to correct the typos of the input
+/// tokens before running the gazetteer parser
+pub struct FuzzyEntityIndex {
+    vocabularies: HashMap<EntityName, EntityVocabulary>,
+    normalizer: Arc<dyn Normalizer>,
+}
+
+struct EntityVocabulary {
+    max_edit_ratio: f32,
+    tokens: HashSet<String>,
+    tokens_tree: BkTree,
+}
+
+/// BK-tree of tokens, in which the children of a token are indexed by their edit distance to
+/// it, so that close tokens are found without computing the distance to all the tokens
+#[derive(Default)]
+struct BkTree {
+    nodes: Vec<BkNode>,
+}
+
+struct BkNode {
+    token: String,
+    children: HashMap<usize, usize>,
+}
+
+/// Token of the input once corrected
+struct CorrectedToken {
+    value: String,
+    /// Range of the original tokens in the input
+    char_range: std::ops::Range<usize>,
+    edits: usize,
+}
+
+impl FuzzyEntityIndex {
+    /// Builds the vocabularies from the trained and injected values of the parser of each
+    /// entity, normalized in the same way as the inputs
+    pub fn from_path<P: AsRef<Path>>(
+        gazetteer_parser_dir: P,
+        config: &FuzzyMatchingConfig,
+        language: NluUtilsLanguage,
+        normalizer: Arc<dyn Normalizer>,
+    ) -> Result<Self> {
+        let metadata_path = gazetteer_parser_dir.as_ref().join("metadata.json");
+        let metadata_file = fs::File::open(&metadata_path).with_context(|| {
+            format!(
+                "Cannot open gazetteer parser metadata at {:?}",
+                metadata_path
+            )
+        })?;
+        let metadata: GazetteerParserMetadata = serde_json::from_reader(metadata_file)
+            .with_context(|| "Cannot deserialize gazetteer parser metadata")?;
+        let mut vocabularies = HashMap::new();
+        for parser_metadata in metadata.parsers_metadata {
+            let max_edit_ratio =
+                match config.entity_max_edit_ratio(&parser_metadata.entity_identifier) {
+                    Some(max_edit_ratio) => max_edit_ratio,
+                    None => continue,
+                };
+            let parser_dir = gazetteer_parser_dir
+                .as_ref()
+                .join(&parser_metadata.entity_parser);
+            let tokens = read_gazetteer_values(&parser_dir)?
+                .into_iter()
+                .flat_map(|value| tokenize_light(&value.raw_value, language))
+                .collect();
+            vocabularies.insert(
+                parser_metadata.entity_identifier,
+                EntityVocabulary::new(tokens, max_edit_ratio, &*normalizer),
+            );
+        }
+        Ok(Self {
+            vocabularies,
+            normalizer,
+        })
+    }
+
+    /// Adds the entities matched once the typos of the input tokens are corrected, which do not
+    /// overlap with the exact matches
+    pub fn add_fuzzy_entities(
+        &self,
+        entities: Vec<CustomEntity>,
+        sentence: &str,
+        tokens: &[Token],
+        parser: &GazetteerParser<String>,
+        filter_entity_kinds: Option<&[String]>,
+        max_alternative_resolved_values: usize,
+    ) -> Result<Vec<CustomEntity>> {
+        let mut fuzzy_entities = vec![];
+        for (entity, vocabulary) in self.vocabularies.iter().sorted_by_key(|(e, _)| *e) {
+            if let Some(entity_kinds) = filter_entity_kinds {
+                if !entity_kinds.contains(entity) {
+                    continue;
+                }
+            }
+            let corrected_tokens = match vocabulary.correct(tokens, &*self.normalizer) {
+                Some(corrected_tokens) => corrected_tokens,
+                None => continue,
+            };
+            let corrected_input = corrected_tokens.iter().map(|token| &*token.value).join(" ");
+            let entity_matches = parser
+                .extract_entities(
+                    &corrected_input,
+                    Some(std::slice::from_ref(entity)),
+                    max_alternative_resolved_values,
+                )
+                .map_err(|e| anyhow!(e))?;
+            for mut entity_match in entity_matches {
+                let matched_tokens = get_matched_tokens(&corrected_tokens, &entity_match.range);
+                if matched_tokens.iter().all(|token| token.edits == 0) {
+                    continue;
+                }
+                let range = match (matched_tokens.first(), matched_tokens.last()) {
+                    (Some(first), Some(last)) => first.char_range.start..last.char_range.end,
+                    _ => continue,
+                };
+                entity_match.value = sentence
+                    .chars()
+                    .skip(range.start)
+                    .take(range.len())
+                    .collect();
+                entity_match.range = range;
+                fuzzy_entities.push(entity_match);
+            }
+        }
+        let mut merged_entities = entities;
+        for fuzzy_entity in fuzzy_entities
+            .into_iter()
+            .sorted_by_key(|entity| -(entity.range.len() as i64))
+        {
+            if merged_entities
+                .iter()
+                .all(|entity| !ranges_overlap(&entity.range, &fuzzy_entity.range))
+            {
+                merged_entities.push(fuzzy_entity);
+            }
+        }
+        merged_entities.sort_by_key(|entity| entity.range.start);
+        Ok(merged_entities)
+    }
+
+    /// Computes the score of an entity match, which is 1.0 for exact matches and decreases with
+    /// the number of edits needed to correct fuzzy matches
+    pub fn match_score(
+        &self,
+        entity: &CustomEntity,
+        tokens: &[Token],
+        parser: &GazetteerParser<String>,
+    ) -> Result<f32> {
+        let vocabulary = match self.vocabularies.get(&entity.entity_identifier) {
+            Some(vocabulary) => vocabulary,
+            None => return Ok(1.0),
+        };
+        let cleaned_value = tokens.iter().map(|token| &*token.value).join(" ");
+        let value_length = cleaned_value.chars().count();
+        let is_exact_match = parser
+            .extract_entities(
+                &cleaned_value,
+                Some(std::slice::from_ref(&entity.entity_identifier)),
+                0,
+            )
+            .map_err(|e| anyhow!(e))?
+            .into_iter()
+            .any(|entity_match| {
+                entity_match.range == (0..value_length)
+                    && entity_match.resolved_value == entity.resolved_value
+            });
+        if is_exact_match {
+            return Ok(1.0);
+        }
+        Ok(vocabulary
+            .correct(tokens, &*self.normalizer)
+            .map(|corrected_tokens| {
+                let edits: usize = corrected_tokens.iter().map(|token| token.edits).sum();
+                let length: usize = corrected_tokens
+                    .iter()
+                    .map(|token| token.value.chars().count())
+                    .sum();
+                (1.0 - edits as f32 / length.max(1) as f32).max(0.0)
+            })
+            .unwrap_or(1.0))
+    }
+}
+
+impl EntityVocabulary {
+    fn new(tokens: Vec<String>, max_edit_ratio: f32, normalizer: &dyn Normalizer) -> Self {
+        let tokens: HashSet<String> = tokens
+            .into_iter()
+            .map(|token| normalize(normalizer, &token))
+            .collect();
+        let mut tokens_tree = BkTree::default();
+        for token in tokens.iter().sorted() {
+            tokens_tree.insert(token.clone());
+        }
+        Self {
+            max_edit_ratio,
+            tokens,
+            tokens_tree,
+        }
+    }
+
+    fn contains(&self, value: &str) -> bool {
+        self.tokens.contains(value)
+    }
+
+    /// Finds the closest token within the maximum number of edits
+    fn closest_token(&self, value: &str) -> Option<(String, usize)> {
+        if self.contains(value) {
+            return Some((value.to_string(), 0));
+        }
+        let max_edits = (self.max_edit_ratio * value.chars().count() as f32) as usize;
+        if max_edits == 0 {
+            return None;
+        }
+        self.tokens_tree
+            .find(value, max_edits)
+            .into_iter()
+            .min_by(|(token_a, edits_a), (token_b, edits_b)| {
+                edits_a.cmp(edits_b).then(token_a.cmp(token_b))
+            })
+            .map(|(token, edits)| (token.to_string(), edits))
+    }
+
+    /// Corrects the tokens, merging consecutive tokens when they form a known token, and
+    /// returns None when no token is modified
+    fn correct(
+        &self,
+        tokens: &[Token],
+        normalizer: &dyn Normalizer,
+    ) -> Option<Vec<CorrectedToken>> {
+        let values = tokens
+            .iter()
+            .map(|token| normalize(normalizer, &token.value))
+            .collect_vec();
+        let mut corrected_tokens = vec![];
+        let mut index = 0;
+        while index < tokens.len() {
+            let single_correction = self.closest_token(&values[index]);
+            if index + 1 < tokens.len()
+                && !(self.contains(&values[index]) && self.contains(&values[index + 1]))
+            {
+                let merged_value = format!("{}{}", values[index], values[index + 1]);
+                if let Some((value, edits)) = self.closest_token(&merged_value) {
+                    corrected_tokens.push(CorrectedToken {
+                        value,
+                        char_range: tokens[index].char_range.start
+                            ..tokens[index + 1].char_range.end,
+                        // Removing the space between the tokens counts as an edit
+                        edits: edits + 1,
+                    });
+                    index += 2;
+                    continue;
+                }
+            }
+            let (value, edits) = single_correction.unwrap_or_else(|| (values[index].clone(), 0));
+            corrected_tokens.push(CorrectedToken {
+                value,
+                char_range: tokens[index].char_range.clone(),
+                edits,
+            });
+            index += 1;
+        }
+        if corrected_tokens.iter().all(|token| token.edits == 0) {
+            None
+        } else {
+            Some(corrected_tokens)
+        }
+    }
+}
+
+/// Returns the corrected tokens which are contained in a range of the corrected input
+fn get_matched_tokens<'a>(
+    corrected_tokens: &'a [CorrectedToken],
+    range: &std::ops::Range<usize>,
+) -> Vec<&'a CorrectedToken> {
+    let mut token_start = 0;
+    let mut matched_tokens = vec![];
+    for token in corrected_tokens {
+        let token_end = token_start + token.value.chars().count();
+        if token_start >= range.start && token_end <= range.end {
+            matched_tokens.push(token);
+        }
+        token_start = token_end + 1;
+    }
+    matched_tokens
+}
+
+/// Levenshtein distance between two strings, computed on characters
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b_chars = b.chars().collect_vec();
+    let mut previous_row = (0..=b_chars.len()).collect_vec();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current_row = vec![i + 1];
+        for (j, b_char) in b_chars.iter().enumerate() {
+            let substitution_cost = if a_char == *b_char { 0 } else { 1 };
+            let distance = (previous_row[j] + substitution_cost)
+                .min(previous_row[j + 1] + 1)
+                .min(current_row[j] + 1);
+            current_row.push(distance);
+        }
+        previous_row = current_row;
+    }
+    previous_row[b_chars.len()]
+}
+
+impl BkTree {
+    fn insert(&mut self, token: String) {
+        let mut node_index = 0;
+        while node_index < self.nodes.len() {
+            let edits = edit_distance(&token, &self.nodes[node_index].token);
+            if edits == 0 {
+                return;
+            }
+            let next_index = self.nodes.len();
+            let child_index = *self.nodes[node_index]
+                .children
+                .entry(edits)
+                .or_insert(next_index);
+            if child_index == next_index {
+                break;
+            }
+            node_index = child_index;
+        }
+        self.nodes.push(BkNode {
+            token,
+            children: HashMap::new(),
+        });
+    }
+
+    /// Returns the tokens which are within `max_edits` of the value, along with their number of
+    /// edits
+    fn find(&self, value: &str, max_edits: usize) -> Vec<(&str, usize)> {
+        let mut matches = vec![];
+        if self.nodes.is_empty() {
+            return matches;
+        }
+        let mut candidates = vec![0];
+        while let Some(node_index) = candidates.pop() {
+            let node = &self.nodes[node_index];
+            let edits = edit_distance(value, &node.token);
+            if edits <= max_edits {
+                matches.push((&*node.token, edits));
+            }
+            // By the triangle inequality, matches can only be found in the children whose
+            // distance to the node is within `max_edits` of the distance of the value
+            candidates.extend(
+                node.children
+                    .iter()
+                    .filter(|(child_edits, _)| {
+                        **child_edits + max_edits >= edits && **child_edits <= edits + max_edits
+                    })
+                    .map(|(_, child_index)| *child_index),
+            );
+        }
+        matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::models::NormalizationConfig;
+    use crate::resources::normalizer::TextNormalizer;
+    use crate::testutils::write_gazetteer_parser;
+
+    fn get_tokens(values: &[&str]) -> Vec<Token> {
+        let mut start = 0;
+        values
+            .iter()
+            .map(|value| {
+                let length = value.chars().count();
+                let token = Token::new(
+                    value.to_string(),
+                    start..start + length,
+                    start..start + length,
+                );
+                start += length + 1;
+                token
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(0, edit_distance("beyonce", "beyonce"));
+        assert_eq!(1, edit_distance("beyonse", "beyonce"));
+        assert_eq!(2, edit_distance("beyonc", "beyonse"));
+        assert_eq!(3, edit_distance("kitten", "sitting"));
+        assert_eq!(4, edit_distance("", "café"));
+    }
+
+    #[test]
+    fn test_bk_tree_finds_close_tokens() {
+        // Given
+        let mut tokens_tree = BkTree::default();
+        for token in &["beyonce", "bey", "radiohead", "radio", "rodeo", "beyonce"] {
+            tokens_tree.insert(token.to_string());
+        }
+
+        // When
+        let matches = tokens_tree
+            .find("radeo", 1)
+            .into_iter()
+            .sorted()
+            .collect_vec();
+
+        // Then
+        assert_eq!(5, tokens_tree.nodes.len());
+        assert_eq!(vec![("radio", 1), ("rodeo", 1)], matches);
+        assert!(tokens_tree.find("metallica", 2).is_empty());
+    }
+
+    #[test]
+    fn test_fuzzy_index_reads_parser_values() {
+        // Given
+        let parser_dir = tempfile::tempdir().unwrap();
+        write_gazetteer_parser(
+            parser_dir.path(),
+            "artist",
+            &[("Beyoncé", "Beyoncé"), ("Radiohead", "Radiohead")],
+        );
+        let config = FuzzyMatchingConfig {
+            max_edit_ratio: Some(0.2),
+            entities_max_edit_ratio: HashMap::new(),
+        };
+        let normalizer = TextNormalizer::new(NormalizationConfig {
+            unicode_nfkc: false,
+            fold_accents: true,
+            fold_punctuation: false,
+        });
+
+        // When
+        let index = FuzzyEntityIndex::from_path(
+            parser_dir.path(),
+            &config,
+            NluUtilsLanguage::EN,
+            Arc::new(normalizer),
+        )
+        .unwrap();
+
+        // Then
+        let vocabulary = &index.vocabularies["artist"];
+        let expected_tokens = vec!["beyonce", "radiohead"];
+        assert_eq!(
+            expected_tokens,
+            vocabulary.tokens.iter().sorted().collect_vec()
+        );
+        assert_eq!(
+            Some(("radiohead".to_string(), 1)),
+            vocabulary.closest_token("radiohed")
+        );
+    }
+
+    #[test]
+    fn test_correct_tokens() {
+        // Given
+        let vocabulary = EntityVocabulary::new(
+            vec![
+                "Beyonce".to_string(),
+                "beyonce".to_string(),
+                "radiohead".to_string(),
+                "radio".to_string(),
+            ],
+            0.2,
+            &TextNormalizer::default(),
+        );
+        let tokens = get_tokens(&["play", "beyonse", "and", "radio", "head"]);
+
+        // When
+        let corrected_tokens = vocabulary
+            .correct(&tokens, &TextNormalizer::default())
+            .unwrap();
+
+        // Then
+        let corrections = corrected_tokens
+            .iter()
+            .map(|token| (&*token.value, token.char_range.clone(), token.edits))
+            .collect_vec();
+        let expected_corrections = vec![
+            ("play", 0..4, 0),
+            ("beyonce", 5..12, 1),
+            ("and", 13..16, 0),
+            ("radiohead", 17..27, 1),
+        ];
+        assert_eq!(expected_corrections, corrections);
+        assert!(vocabulary
+            .correct(
+                &get_tokens(&["play", "beyonce"]),
+                &TextNormalizer::default()
+            )
+            .is_none());
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+
+use itertools::Itertools;
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use snips_nlu_parsers::gazetteer_entity_parser::{
+    EntityValue as GazetteerEntityValue, Parser as GazetteerEntityParser,
+};
+use snips_nlu_parsers::GazetteerParserMetadata;
+
+use crate::resources::normalizer::{normalize, Normalizer};
+use crate::utils::copy_directory;
+use anyhow::{anyhow, Context, Result};
+
+/// Value matched by the gazetteer parser of an entity
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GazetteerValue {
+    /// Tokens of the matched value, separated by spaces
+    pub raw_value: String,
+    pub resolved_value: String,
+    /// Whether the value was injected rather than trained
+    pub injected: bool,
+}
+
+/// Configuration written along with the parser by `GazetteerEntityParser::dump`
+#[derive(Deserialize)]
+struct ParserConfig {
+    parser_filename: String,
+}
+
+/// Layout of the file written by `GazetteerEntityParser::dump`, in which the fields of the
+/// parser are serialized in order
+#[derive(Deserialize)]
+struct SerializedParser {
+    tokens_symbol_table: (HashMap<String, u32>, u32),
+    resolved_symbol_table: (HashMap<u32, String>, u32),
+    _token_to_resolved_values: IgnoredAny,
+    resolved_value_to_tokens: HashMap<u32, (u32, Vec<u32>)>,
+    _n_stop_words: IgnoredAny,
+    _additional_stop_words: IgnoredAny,
+    _stop_words: IgnoredAny,
+    _edge_cases: IgnoredAny,
+    injected_values: HashSet<u32>,
+    _threshold: IgnoredAny,
+    _license_info: IgnoredAny,
+}
+
+/// Reads the values of the gazetteer parser of an entity, sorted by decreasing priority
+pub fn read_gazetteer_values(entity_parser_dir: &Path) -> Result<Vec<GazetteerValue>> {
+    let config_path = entity_parser_dir.join("metadata.json");
+    let config_file = File::open(&config_path)
+        .with_context(|| format!("Cannot open gazetteer parser config {:?}", config_path))?;
+    let config: ParserConfig = serde_json::from_reader(config_file).with_context(|| {
+        format!(
+            "Cannot deserialize gazetteer parser config {:?}",
+            config_path
+        )
+    })?;
+    let parser_path = entity_parser_dir.join(config.parser_filename);
+    let parser_file = File::open(&parser_path)
+        .with_context(|| format!("Cannot open gazetteer parser {:?}", parser_path))?;
+    let parser: SerializedParser = rmp_serde::from_read(BufReader::new(parser_file))
+        .with_context(|| format!("Cannot deserialize gazetteer parser {:?}", parser_path))?;
+
+    let tokens: HashMap<u32, &str> = parser
+        .tokens_symbol_table
+        .0
+        .iter()
+        .map(|(token, token_index)| (*token_index, &**token))
+        .collect();
+    parser
+        .resolved_value_to_tokens
+        .iter()
+        .sorted_by_key(|(_, (rank, _))| *rank)
+        .map(|(value_index, (_, token_indexes))| {
+            let raw_value = token_indexes
+                .iter()
+                .map(|token_index| {
+                    tokens.get(token_index).ok_or_else(|| {
+                        anyhow!("Unknown token {} in parser {:?}", token_index, parser_path)
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?
+                .into_iter()
+                .join(" ");
+            let resolved_value = parser
+                .resolved_symbol_table
+                .0
+                .get(value_index)
+                .ok_or_else(|| {
+                    anyhow!("Unknown value {} in parser {:?}", value_index, parser_path)
+                })?
+                .clone();
+            Ok(GazetteerValue {
+                raw_value,
+                resolved_value,
+                injected: parser.injected_values.contains(value_index),
+            })
+        })
+        .collect()
+}
+
+/// Copies an entity parser directory, whose gazetteer parser is stored in the
+/// `gazetteer_parser_dir` subdirectory, and injects in the copy the normalized form of the
+/// values which are changed by the normalizer
+///
+/// The gazetteer parser is matched against normalized inputs, hence it must contain normalized
+/// values. `None` is returned when the normalizer only lowercases the values, in which case the
+/// parser can be loaded as is.
+pub fn normalize_gazetteer_values(
+    parser_dir: &Path,
+    gazetteer_parser_dir: &str,
+    normalizer: &dyn Normalizer,
+) -> Result<Option<tempfile::TempDir>> {
+    let metadata_path = parser_dir.join(gazetteer_parser_dir).join("metadata.json");
+    let metadata_file = File::open(&metadata_path)
+        .with_context(|| format!("Cannot open gazetteer parser metadata {:?}", metadata_path))?;
+    let metadata: GazetteerParserMetadata =
+        serde_json::from_reader(metadata_file).with_context(|| {
+            format!(
+                "Cannot deserialize gazetteer parser metadata {:?}",
+                metadata_path
+            )
+        })?;
+
+    let mut normalized_values = vec![];
+    for entity_parser_metadata in metadata.parsers_metadata {
+        let entity_parser_dir = parser_dir
+            .join(gazetteer_parser_dir)
+            .join(&entity_parser_metadata.entity_parser);
+        let values = read_gazetteer_values(&entity_parser_dir)?
+            .into_iter()
+            .filter_map(|value| {
+                let normalized_value = normalize(normalizer, &value.raw_value);
+                if normalized_value != value.raw_value.to_lowercase() {
+                    Some(GazetteerEntityValue {
+                        raw_value: normalized_value,
+                        resolved_value: value.resolved_value,
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect_vec();
+        if !values.is_empty() {
+            normalized_values.push((entity_parser_metadata.entity_parser, values));
+        }
+    }
+    if normalized_values.is_empty() {
+        return Ok(None);
+    }
+
+    let normalized_parser_dir = tempfile::Builder::new()
+        .prefix("normalized_entity_parser_")
+        .tempdir()?;
+    copy_directory(parser_dir, normalized_parser_dir.path())?;
+    for (entity_parser, values) in normalized_values {
+        let entity_parser_dir = normalized_parser_dir
+            .path()
+            .join(gazetteer_parser_dir)
+            .join(entity_parser);
+        let gazetteer_parser = GazetteerEntityParser::from_folder(&entity_parser_dir)?
+            .inject_new_values(values, false, false);
+        fs::remove_dir_all(&entity_parser_dir)?;
+        gazetteer_parser.dump(&entity_parser_dir)?;
+    }
+    Ok(Some(normalized_parser_dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testutils::write_gazetteer_parser;
+
+    #[test]
+    fn test_read_gazetteer_values() {
+        // Given
+        let gazetteer_parser_dir = tempfile::tempdir().unwrap();
+        write_gazetteer_parser(
+            gazetteer_parser_dir.path(),
+            "drink",
+            &[("café", "Café"), ("thé", "Thé")],
+        );
+
+        // When
+        let values = read_gazetteer_values(&gazetteer_parser_dir.path().join("parser_1")).unwrap();
+
+        // Then
+        let expected_values = vec![
+            GazetteerValue {
+                raw_value: "café".to_string(),
+                resolved_value: "Café".to_string(),
+                injected: false,
+            },
+            GazetteerValue {
+                raw_value: "thé".to_string(),
+                resolved_value: "Thé".to_string(),
+                injected: false,
+            },
+        ];
+        assert_eq!(expected_values, values);
+    }
+
+    #[test]
+    fn test_read_values_of_trained_parser() {
+        // Given
+        let entity_parser_dir = Path::new("data")
+            .join("tests")
+            .join("models")
+            .join("nlu_engine_game")
+            .join("custom_entity_parser")
+            .join("parser")
+            .join("parser_1");
+
+        // When
+        let values = read_gazetteer_values(&entity_parser_dir).unwrap();
+
+        // Then
+        assert_eq!(20, values.len());
+        assert_eq!(
+            GazetteerValue {
+                raw_value: "Invader Attack".to_string(),
+                resolved_value: "Invader Attack 3".to_string(),
+                injected: false,
+            },
+            values[0]
+        );
+    }
+}
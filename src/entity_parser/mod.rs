@@ -1,6 +1,13 @@
 pub mod builtin_entity_parser;
 pub mod custom_entity_parser;
-mod utils;
+pub mod ephemeral_entities;
+pub mod fuzzy_matching;
+pub mod gazetteer_values;
+pub mod regex_entities;
+pub mod utils;
 
 pub use self::builtin_entity_parser::*;
 pub use self::custom_entity_parser::*;
+pub use self::ephemeral_entities::{
+    with_ephemeral_entity_values, EphemeralEntityForms, EphemeralEntityValue,
+};
@@ -0,0 +1,268 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::path::Path;
+
+use regex::{Regex, RegexBuilder};
+use snips_nlu_utils::language::Language as NluUtilsLanguage;
+use snips_nlu_utils::token::tokenize;
+
+use super::custom_entity_parser::CustomEntity;
+use crate::utils::EntityName;
+use anyhow::{Context, Result};
+
+/// Optional file of the custom entity parser directory which maps entities to regex patterns
+pub const REGEX_ENTITIES_FILE: &str = "regex_entities.json";
+
+/// Maximum number of tokens of a regex entity, which bounds the number of candidate spans
+const MAX_REGEX_ENTITY_TOKENS: usize = 8;
+
+/// Patterns matching the values of custom entities which cannot be listed, such as order
+/// numbers or flight codes
+///
+/// Patterns are either declared in the custom entity parser directory, or added at runtime.
+/// They are case insensitive and only match whole tokens, up to `MAX_REGEX_ENTITY_TOKENS`.
+#[derive(Debug, Default)]
+pub struct RegexEntities {
+    declared_patterns: Vec<(EntityName, Regex)>,
+    added_patterns: Vec<(EntityName, Regex)>,
+}
+
+impl RegexEntities {
+    pub fn from_path<P: AsRef<Path>>(parser_dir: P) -> Result<Self> {
+        Ok(Self {
+            declared_patterns: read_declared_patterns(parser_dir)?,
+            added_patterns: vec![],
+        })
+    }
+
+    /// Replaces the declared patterns with the ones of `other`, the patterns added at runtime
+    /// being kept
+    pub fn replace_declared_patterns(&mut self, other: RegexEntities) {
+        self.declared_patterns = other.declared_patterns;
+    }
+
+    pub fn add_pattern(&mut self, entity: &str, pattern: &str) -> Result<()> {
+        let regex = compile_pattern(entity, pattern)?;
+        self.added_patterns.push((entity.to_string(), regex));
+        Ok(())
+    }
+
+    /// Extracts the longest match of each pattern starting at each token which is not already
+    /// covered by a match of the same entity
+    ///
+    /// Matches are not deduplicated across entities, overlaps are resolved when merging them
+    /// with the entities of the gazetteer parser.
+    pub fn extract_entities(
+        &self,
+        sentence: &str,
+        language: NluUtilsLanguage,
+        filter_entity_kinds: Option<&[String]>,
+    ) -> Vec<CustomEntity> {
+        let patterns = self
+            .declared_patterns
+            .iter()
+            .chain(self.added_patterns.iter())
+            .filter(|(entity, _)| {
+                filter_entity_kinds
+                    .map(|kinds| kinds.contains(entity))
+                    .unwrap_or(true)
+            })
+            .collect::<Vec<_>>();
+        if patterns.is_empty() {
+            return vec![];
+        }
+        let tokens = tokenize(sentence, language);
+        let mut entities: Vec<CustomEntity> = vec![];
+        // Tokens covered by the matches of each entity
+        let mut covered_tokens: HashMap<&EntityName, Vec<bool>> = HashMap::new();
+        for (entity, regex) in patterns {
+            let covered = covered_tokens
+                .entry(entity)
+                .or_insert_with(|| vec![false; tokens.len()]);
+            for start in 0..tokens.len() {
+                if covered[start] {
+                    continue;
+                }
+                let max_end = tokens.len().min(start + MAX_REGEX_ENTITY_TOKENS);
+                let opt_end = (start..max_end).rev().find(|end| {
+                    regex.is_match(&sentence[tokens[start].range.start..tokens[*end].range.end])
+                });
+                if let Some(end) = opt_end {
+                    let range = tokens[start].char_range.start..tokens[end].char_range.end;
+                    covered[start..=end].iter_mut().for_each(|c| *c = true);
+                    let value =
+                        sentence[tokens[start].range.start..tokens[end].range.end].to_string();
+                    entities.push(CustomEntity {
+                        value: value.clone(),
+                        resolved_value: value,
+                        alternative_resolved_values: vec![],
+                        range,
+                        entity_identifier: entity.clone(),
+                    });
+                }
+            }
+        }
+        entities
+    }
+}
+
+fn read_declared_patterns<P: AsRef<Path>>(parser_dir: P) -> Result<Vec<(EntityName, Regex)>> {
+    let patterns_path = parser_dir.as_ref().join(REGEX_ENTITIES_FILE);
+    if !patterns_path.exists() {
+        return Ok(vec![]);
+    }
+    let patterns_file = File::open(&patterns_path)
+        .with_context(|| format!("Cannot open regex entities file: {:?}", patterns_path))?;
+    let patterns: BTreeMap<EntityName, Vec<String>> = serde_json::from_reader(patterns_file)
+        .with_context(|| "Cannot deserialize regex entities")?;
+    patterns
+        .iter()
+        .flat_map(|(entity, patterns)| patterns.iter().map(move |pattern| (entity, pattern)))
+        .map(|(entity, pattern)| Ok((entity.clone(), compile_pattern(entity, pattern)?)))
+        .collect()
+}
+
+/// Compiles a pattern which must match the whole text of the candidate tokens
+fn compile_pattern(entity: &str, pattern: &str) -> Result<Regex> {
+    RegexBuilder::new(&format!("^(?:{})$", pattern))
+        .case_insensitive(true)
+        .build()
+        .with_context(|| {
+            format!(
+                "Invalid pattern '{}' for regex entity '{}'",
+                pattern, entity
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn get_entity(value: &str, start: usize, entity: &str) -> CustomEntity {
+        CustomEntity {
+            value: value.to_string(),
+            resolved_value: value.to_string(),
+            alternative_resolved_values: vec![],
+            range: start..start + value.chars().count(),
+            entity_identifier: entity.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_extract_regex_entities() {
+        // Given
+        let mut regex_entities = RegexEntities::default();
+        regex_entities
+            .add_pattern("order_number", r"[a-z]{2}\d{6}")
+            .unwrap();
+        regex_entities
+            .add_pattern("flight_code", r"[a-z]{2} ?\d{2,4}")
+            .unwrap();
+        let sentence = "where is order AB123456 for flight af 1234 or zz1234567";
+
+        // When
+        let entities = regex_entities.extract_entities(sentence, NluUtilsLanguage::EN, None);
+        let order_entities = regex_entities.extract_entities(
+            sentence,
+            NluUtilsLanguage::EN,
+            Some(&["order_number".to_string()]),
+        );
+
+        // Then
+        let expected_entities = vec![
+            get_entity("AB123456", 15, "order_number"),
+            get_entity("af 1234", 35, "flight_code"),
+        ];
+        assert_eq!(expected_entities, entities);
+        assert_eq!(expected_entities[..1].to_vec(), order_entities);
+    }
+
+    #[test]
+    fn test_load_declared_regex_entities() {
+        // Given
+        let parser_dir = tempfile::tempdir().unwrap();
+        fs::write(
+            parser_dir.path().join(REGEX_ENTITIES_FILE),
+            r#"{"ticket_id": ["t-?\\d+"]}"#,
+        )
+        .unwrap();
+
+        // When
+        let mut regex_entities = RegexEntities::from_path(parser_dir.path()).unwrap();
+        regex_entities.add_pattern("ticket_id", r"#\d+").unwrap();
+        let entities =
+            regex_entities.extract_entities("close T42 and #43", NluUtilsLanguage::EN, None);
+        regex_entities.replace_declared_patterns(RegexEntities::default());
+        let entities_after_reload =
+            regex_entities.extract_entities("close T42 and #43", NluUtilsLanguage::EN, None);
+
+        // Then
+        assert_eq!(
+            vec![
+                get_entity("T42", 6, "ticket_id"),
+                get_entity("#43", 14, "ticket_id")
+            ],
+            entities
+        );
+        assert_eq!(
+            vec![get_entity("#43", 14, "ticket_id")],
+            entities_after_reload
+        );
+    }
+
+    #[test]
+    fn test_regex_entities_should_be_bounded_in_tokens() {
+        // Given
+        let mut regex_entities = RegexEntities::default();
+        regex_entities.add_pattern("words", r"[a-z ]+").unwrap();
+        let sentence = "a b c d e f g h i j";
+
+        // When
+        let entities = regex_entities.extract_entities(sentence, NluUtilsLanguage::EN, None);
+
+        // Then
+        let expected_entities = vec![
+            get_entity("a b c d e f g h", 0, "words"),
+            get_entity("i j", 16, "words"),
+        ];
+        assert_eq!(expected_entities, entities);
+    }
+
+    #[test]
+    fn test_regex_entities_should_not_match_inside_matches_of_the_same_entity() {
+        // Given
+        let mut regex_entities = RegexEntities::default();
+        regex_entities
+            .add_pattern("phone_number", r"\d{2}( \d{2})*")
+            .unwrap();
+        regex_entities
+            .add_pattern("phone_number", r"\d{2} \d{2}")
+            .unwrap();
+        regex_entities.add_pattern("code", r"\d{2}").unwrap();
+        let sentence = "call 01 23 45";
+
+        // When
+        let entities = regex_entities.extract_entities(sentence, NluUtilsLanguage::EN, None);
+
+        // Then
+        let expected_entities = vec![
+            get_entity("01 23 45", 5, "phone_number"),
+            get_entity("01", 5, "code"),
+            get_entity("23", 8, "code"),
+            get_entity("45", 11, "code"),
+        ];
+        assert_eq!(expected_entities, entities);
+    }
+
+    #[test]
+    fn test_invalid_pattern_should_fail() {
+        // When
+        let result = RegexEntities::default().add_pattern("order_number", "[a-z");
+
+        // Then
+        assert!(result.is_err());
+    }
+}
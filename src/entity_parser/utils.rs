@@ -1,9 +1,15 @@
 use std::hash::Hash;
 
+use itertools::Itertools;
 use lru_cache::LruCache;
+use snips_nlu_utils::range::ranges_overlap;
 
+use super::custom_entity_parser::CustomEntity;
 use anyhow::Result;
 
+/// Replaces a parser with the one loaded by `prepare_reload`, which cannot fail
+pub type ParserSwap<'a> = Box<dyn FnOnce() + 'a>;
+
 pub struct Cache<K, V>(LruCache<K, V>)
 where
     K: Eq + Hash + Clone,
@@ -18,6 +24,10 @@ where
         Cache(LruCache::new(capacity))
     }
 
+    pub fn clear(&mut self) {
+        self.0.clear()
+    }
+
     pub fn try_cache<F: Fn(&K) -> Result<V>>(&mut self, key: &K, producer: F) -> Result<V> {
         let cached_value = self.0.get_mut(key).cloned();
         if let Some(value) = cached_value {
@@ -28,3 +38,82 @@ where
         Ok(value)
     }
 }
+
+/// Adds entities to the ones of a parser, an added entity and an entity of the parser which
+/// overlap being resolved by keeping the longest one
+///
+/// The entities of the parser are preferred over the added ones having the same length.
+/// Overlapping entities coming from the same source, such as gazetteer matches of different
+/// entities, are all kept.
+pub fn merge_overlapping_entities(
+    entities: Vec<CustomEntity>,
+    added_entities: Vec<CustomEntity>,
+) -> Vec<CustomEntity> {
+    if added_entities.is_empty() {
+        return entities;
+    }
+    let overlaps =
+        |entity: &CustomEntity, other: &CustomEntity| ranges_overlap(&entity.range, &other.range);
+    let kept_entities = entities
+        .iter()
+        .filter(|entity| {
+            !added_entities.iter().any(|added_entity| {
+                overlaps(entity, added_entity) && added_entity.range.len() > entity.range.len()
+            })
+        })
+        .cloned()
+        .collect_vec();
+    let kept_added_entities = added_entities
+        .into_iter()
+        .filter(|added_entity| {
+            !entities.iter().any(|entity| {
+                overlaps(entity, added_entity) && entity.range.len() >= added_entity.range.len()
+            })
+        })
+        .collect_vec();
+    kept_entities
+        .into_iter()
+        .chain(kept_added_entities)
+        .sorted_by_key(|entity| entity.range.start)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_entity(value: &str, start: usize, entity: &str) -> CustomEntity {
+        CustomEntity {
+            value: value.to_string(),
+            resolved_value: value.to_string(),
+            alternative_resolved_values: vec![],
+            range: start..start + value.chars().count(),
+            entity_identifier: entity.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_merge_overlapping_entities_only_resolves_added_entities_overlaps() {
+        // Given
+        let entities = vec![
+            get_entity("paris", 8, "city"),
+            get_entity("paris", 8, "destination"),
+            get_entity("af", 17, "airline"),
+        ];
+        let added_entities = vec![
+            get_entity("af 1234", 17, "flight_code"),
+            get_entity("paris", 8, "flight_code"),
+        ];
+
+        // When
+        let merged_entities = merge_overlapping_entities(entities, added_entities);
+
+        // Then
+        let expected_entities = vec![
+            get_entity("paris", 8, "city"),
+            get_entity("paris", 8, "destination"),
+            get_entity("af 1234", 17, "flight_code"),
+        ];
+        assert_eq!(expected_entities, merged_entities);
+    }
+}
@@ -1,6 +1,7 @@
-use anyhow::{anyhow, Context};
+use anyhow::{anyhow, bail, Context};
 use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::io::{Read, Seek, Write};
 use std::iter::FromIterator;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
@@ -8,28 +9,116 @@ use std::sync::Arc;
 
 use itertools::Itertools;
 use log::info;
+use serde::{Deserialize, Serialize};
 use snips_nlu_ontology::{BuiltinGazetteerEntityKind, GrammarEntityKind};
 use snips_nlu_parsers::gazetteer_entity_parser::{
     EntityValue as GazetteerEntityValue, Parser as GazetteerEntityParser,
 };
-use snips_nlu_parsers::{BuiltinParserMetadata, GazetteerParserMetadata};
+use snips_nlu_parsers::{
+    BuiltinParserMetadata, GazetteerEntityMatch, GazetteerParser, GazetteerParserMetadata,
+};
 use snips_nlu_utils::language::Language as NluUtilsLanguage;
 use snips_nlu_utils::token::tokenize_light;
 
 use crate::entity_parser::custom_entity_parser::CustomEntityParserMetadata;
 use crate::entity_parser::custom_entity_parser::CustomEntityParserUsage;
+use crate::entity_parser::gazetteer_values::read_gazetteer_values;
 use crate::models::nlu_engine::NluEngineModel;
 use crate::resources::loading::load_engine_shared_resources;
+use crate::resources::normalizer::{normalize, Normalizer};
 use crate::resources::stemmer::Stemmer;
 use crate::resources::SharedResources;
+use crate::utils::{extract_nlu_engine_zip_archive, write_nlu_engine_zip_archive};
 
 use super::errors::NluInjectionErrorKind;
+use super::journal::{
+    commit_staged_parsers, recover_interrupted_injection, rollback, sync_directory,
+    BACKUP_DIRECTORY, JOURNAL_FILE, STAGING_DIRECTORY,
+};
 
 pub type InjectedEntity = String;
 pub type InjectedValue = String;
 
-fn normalize(s: &str) -> String {
-    s.to_lowercase()
+/// Values injected in a gazetteer parser since it was last reset to its trained values, stored
+/// in the parser directory
+const INJECTED_VALUES_FILE: &str = "injected_values.json";
+
+/// Entity value to inject along with the synonyms which resolve to it
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct InjectedEntityValue {
+    pub value: InjectedValue,
+    #[serde(default)]
+    pub synonyms: Vec<InjectedValue>,
+}
+
+/// Values of the gazetteer parser of an entity
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntityValues {
+    /// Values the parser was trained on, read from the parser itself
+    ///
+    /// The synonyms are the tokenized forms matched by the parser, which include the stems of
+    /// the values when the parser uses stems.
+    pub trained: Vec<InjectedEntityValue>,
+    /// Values injected since the parser was last reset to its trained values
+    pub injected: Vec<InjectedEntityValue>,
+}
+
+/// Entities section of a dataset in the Snips format
+#[derive(Debug, Deserialize)]
+struct DatasetEntities {
+    entities: HashMap<InjectedEntity, DatasetEntity>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DatasetEntity {
+    #[serde(default)]
+    data: Vec<InjectedEntityValue>,
+    #[serde(default)]
+    use_synonyms: Option<bool>,
+}
+
+/// Changes that an injection would make to the parser of an entity
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct EntityInjectionReport {
+    /// Number of added values which are not yet in the parser
+    pub new_values: usize,
+    /// Number of added values which are already trained or injected
+    pub existing_values: usize,
+    /// Number of previously injected values which would be dropped
+    pub dropped_values: usize,
+    /// Stemmed forms of the values which would be injected in addition to the values
+    pub stemmed_values: Vec<InjectedValue>,
+    /// Values which would also be matched by the parser of another entity
+    pub collisions: Vec<ValueCollision>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ValueCollision {
+    pub value: InjectedValue,
+    pub other_entity: InjectedEntity,
+}
+
+/// Report of a dry run injection
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct InjectionReport {
+    pub entities: HashMap<InjectedEntity, EntityInjectionReport>,
+}
+
+/// Injected values of an entity parser before and after an injection
+struct EntityValuesUpdate {
+    previous_values: Vec<InjectedEntityValue>,
+    injected_values: Vec<InjectedEntityValue>,
+    /// Values to inject in the parser
+    new_values: Vec<InjectedEntityValue>,
+    from_vanilla: bool,
+}
+
+/// Update to apply to an entity parser
+struct EntityParserUpdate {
+    new_values: Vec<GazetteerEntityValue>,
+    from_vanilla: bool,
+    /// Values injected in the parser once updated
+    injected_values: Vec<InjectedEntityValue>,
 }
 
 struct NluEngineInfo {
@@ -52,29 +141,130 @@ struct CustomGazetteerParserInfo {
 
 pub struct NluInjector<P: AsRef<Path>> {
     nlu_engine_dir: P,
-    entity_values: HashMap<InjectedEntity, Vec<InjectedValue>>,
+    /// Directory in which the engine archive is extracted, which is kept as long as the injector
+    archive_dir: Option<tempfile::TempDir>,
+    entity_values: HashMap<InjectedEntity, Vec<InjectedEntityValue>>,
+    removed_values: HashMap<InjectedEntity, HashSet<InjectedValue>>,
+    replaced_entities: HashSet<InjectedEntity>,
     from_vanilla: bool,
     shared_resources: Option<Arc<SharedResources>>,
 }
 
+impl NluInjector<PathBuf> {
+    /// Creates an injector for an engine archive, such as the ones read by
+    /// `SnipsNluEngine::from_zip`, which is meant to be used with `inject_to_zip`
+    pub fn from_zip<R: Read + Seek>(zip_reader: R) -> anyhow::Result<Self> {
+        let archive_dir = tempfile::Builder::new()
+            .prefix("temp_dir_nlu_injection_")
+            .tempdir()?;
+        let engine_dir = extract_nlu_engine_zip_archive(zip_reader, archive_dir.path())
+            .with_context(|| NluInjectionErrorKind::InternalInjectionError {
+                msg: "could not extract engine archive".to_string(),
+            })?;
+        let mut injector = Self::new(engine_dir);
+        injector.archive_dir = Some(archive_dir);
+        Ok(injector)
+    }
+}
+
 impl<P: AsRef<Path>> NluInjector<P> {
     pub fn new(nlu_engine_dir: P) -> Self {
         Self {
             nlu_engine_dir,
+            archive_dir: None,
             entity_values: HashMap::new(),
+            removed_values: HashMap::new(),
+            replaced_entities: HashSet::new(),
             from_vanilla: false,
             shared_resources: None,
         }
     }
 
-    pub fn add_value(mut self, entity: InjectedEntity, value: InjectedValue) -> Self {
+    pub fn add_value(self, entity: InjectedEntity, value: InjectedValue) -> Self {
+        self.add_value_with_synonyms(entity, value, vec![])
+    }
+
+    /// Adds a value which is also matched when any of its synonyms is found
+    pub fn add_value_with_synonyms(
+        mut self,
+        entity: InjectedEntity,
+        resolved_value: InjectedValue,
+        synonyms: Vec<InjectedValue>,
+    ) -> Self {
         self.entity_values
             .entry(entity)
             .or_insert_with(|| vec![])
-            .push(value);
+            .push(InjectedEntityValue {
+                value: resolved_value,
+                synonyms,
+            });
         self
     }
 
+    /// Adds the values, and their synonyms, of all the entities of a dataset in the Snips
+    /// format
+    ///
+    /// Synonyms are ignored for entities which do not use them, and entities without values,
+    /// such as builtin entities, are skipped.
+    pub fn add_dataset_values<R: Read>(mut self, dataset_reader: R) -> anyhow::Result<Self> {
+        let dataset: DatasetEntities =
+            serde_json::from_reader(dataset_reader).with_context(|| {
+                NluInjectionErrorKind::InternalInjectionError {
+                    msg: "invalid dataset entities format".to_string(),
+                }
+            })?;
+        for (entity, dataset_entity) in dataset.entities {
+            let use_synonyms = dataset_entity.use_synonyms.unwrap_or(true);
+            for entity_value in dataset_entity.data {
+                let synonyms = if use_synonyms {
+                    entity_value.synonyms
+                } else {
+                    vec![]
+                };
+                self = self.add_value_with_synonyms(entity.clone(), entity_value.value, synonyms);
+            }
+        }
+        Ok(self)
+    }
+
+    /// Removes an injected value along with its synonyms
+    ///
+    /// The entity parser is then rebuilt from its trained values and the remaining injected
+    /// values. Removals are applied after the values added with the same injector.
+    pub fn remove_value(mut self, entity: InjectedEntity, value: InjectedValue) -> Self {
+        self.entity_values.entry(entity.clone()).or_default();
+        self.removed_values.entry(entity).or_default().insert(value);
+        self
+    }
+
+    /// Replaces all the injected values of an entity, so that its parser only contains the
+    /// trained values and the provided ones
+    pub fn replace_values(
+        mut self,
+        entity: InjectedEntity,
+        values: Vec<InjectedEntityValue>,
+    ) -> Self {
+        self.replaced_entities.insert(entity.clone());
+        self.entity_values.insert(entity, values);
+        self
+    }
+
+    /// Lists the trained values of the parser of an entity and the values injected since it was
+    /// last reset to its trained values
+    pub fn list_values(&self, entity: &str) -> anyhow::Result<EntityValues> {
+        let engine_info = get_nlu_engine_info(self.nlu_engine_dir.as_ref())?;
+        let builtin_parser_info = get_builtin_parser_info(&engine_info.builtin_entity_parser_dir)?;
+        let custom_parser_info = get_custom_parser_info(&engine_info.custom_entity_parser_dir)?;
+        let entity_values = vec![(entity.to_string(), vec![])].into_iter().collect();
+        let parsers_dirs = get_entity_parsers_dirs(
+            &engine_info,
+            &builtin_parser_info,
+            &custom_parser_info,
+            &entity_values,
+        )?;
+        read_entity_values(&parsers_dirs[entity])
+    }
+
     #[allow(clippy::wrong_self_convention)]
     pub fn from_vanilla(mut self, from_vanilla: bool) -> Self {
         self.from_vanilla = from_vanilla;
@@ -87,7 +277,31 @@ impl<P: AsRef<Path>> NluInjector<P> {
     }
 
     pub fn inject(self) -> anyhow::Result<()> {
+        self.inject_in_engine_dir()
+    }
+
+    /// Injects the values in the engine and writes the updated engine to a zip archive
+    ///
+    /// The archive has the same layout as the ones read by `SnipsNluEngine::from_zip`, without
+    /// the data used to roll back the injection. The engine directory is also updated, which
+    /// only lasts as long as the injector for injectors created with `from_zip`.
+    pub fn inject_to_zip<W: Write + Seek>(self, zip_writer: W) -> anyhow::Result<()> {
+        self.inject_in_engine_dir()?;
+        write_nlu_engine_zip_archive(
+            self.nlu_engine_dir.as_ref(),
+            zip_writer,
+            &[STAGING_DIRECTORY, BACKUP_DIRECTORY, JOURNAL_FILE],
+        )
+        .with_context(|| NluInjectionErrorKind::InternalInjectionError {
+            msg: "could not write injected engine archive".to_string(),
+        })?;
+        info!("Injected engine archive written with success !");
+        Ok(())
+    }
+
+    fn inject_in_engine_dir(&self) -> anyhow::Result<()> {
         info!("Starting injection...");
+        recover_interrupted_injection(self.nlu_engine_dir.as_ref())?;
 
         info!("Retrieving parsers paths...");
         let engine_info = get_nlu_engine_info(self.nlu_engine_dir.as_ref())?;
@@ -100,70 +314,309 @@ impl<P: AsRef<Path>> NluInjector<P> {
             &self.entity_values,
         )?;
 
-        let shared_resources = if let Some(resources) = self.shared_resources {
-            Ok(resources)
-        } else {
-            load_engine_shared_resources(self.nlu_engine_dir.as_ref()).with_context(|| {
-                NluInjectionErrorKind::InternalInjectionError {
-                    msg: format!(
-                        "Could not load shared resources from {:?}",
-                        self.nlu_engine_dir.as_ref()
-                    ),
-                }
-            })
-        }
-        .map_err(|e| anyhow!(e))?;
-
+        let shared_resources = self.get_shared_resources()?;
         let maybe_stemmer = shared_resources.stemmer.as_ref();
+        let values_updates = self.compute_values_updates(&parsers_dirs)?;
 
         // Normalize and stem all values if needed
         info!("Normalizing injected values...");
-        let normalized_entity_values = self
-            .entity_values
+        let parser_updates = values_updates
             .into_iter()
-            .map(|(entity, values)| {
-                let normalize_entity_values = normalize_entity_value(values);
-                if engine_info.custom_entities.contains(&*entity) {
-                    let stemmed_entity_values = stem_entity_value(
+            .map(|(entity, values_update)| {
+                let normalize_entity_values =
+                    normalize_entity_value(values_update.new_values, &*shared_resources.normalizer);
+                let new_values = if engine_info.custom_entities.contains(&*entity) {
+                    stem_entity_value(
                         normalize_entity_values,
                         &engine_info,
                         &custom_parser_info,
                         maybe_stemmer,
-                    )?;
-                    Ok((entity, stemmed_entity_values))
+                    )?
                 } else {
-                    Ok((entity, normalize_entity_values))
-                }
+                    normalize_entity_values
+                };
+                let parser_update = EntityParserUpdate {
+                    new_values,
+                    from_vanilla: values_update.from_vanilla,
+                    injected_values: values_update.injected_values,
+                };
+                Ok((entity, parser_update))
             })
             .collect::<Result<HashMap<_, _>, NluInjectionErrorKind>>()?;
 
-        for (entity, new_entity_values) in normalized_entity_values {
-            info!("Injecting values for entity '{}'", entity);
+        // Updated parsers are written to a staging directory before replacing the current ones,
+        // so that the engine is left untouched when any of them fails
+        let engine_dir = self.nlu_engine_dir.as_ref();
+        let staging_dir = engine_dir.join(STAGING_DIRECTORY);
+        if staging_dir.exists() {
+            fs::remove_dir_all(&staging_dir)?;
+        }
+        fs::create_dir_all(&staging_dir).with_context(|| {
+            NluInjectionErrorKind::InternalInjectionError {
+                msg: format!("could not create staging directory {:?}", staging_dir),
+            }
+        })?;
+        let injection_result = stage_parsers(parser_updates, &parsers_dirs, &staging_dir)
+            .and_then(|staged_parsers| commit_staged_parsers(engine_dir, staged_parsers));
+        fs::remove_dir_all(&staging_dir).with_context(|| {
+            NluInjectionErrorKind::InternalInjectionError {
+                msg: format!("could not remove staging directory {:?}", staging_dir),
+            }
+        })?;
+        injection_result?;
 
-            let parser_dir = &parsers_dirs[&entity];
-            let mut gazetteer_parser = GazetteerEntityParser::from_folder(parser_dir)?;
+        info!("Injection performed with success !");
+        Ok(())
+    }
 
-            gazetteer_parser = gazetteer_parser.inject_new_values(new_entity_values, true, self.from_vanilla);
+    /// Computes the changes that the injection would make to each entity parser, without
+    /// modifying the engine directory
+    pub fn dry_run(&self) -> anyhow::Result<InjectionReport> {
+        let engine_info = get_nlu_engine_info(self.nlu_engine_dir.as_ref())?;
+        let builtin_parser_info = get_builtin_parser_info(&engine_info.builtin_entity_parser_dir)?;
+        let custom_parser_info = get_custom_parser_info(&engine_info.custom_entity_parser_dir)?;
+        let parsers_dirs = get_entity_parsers_dirs(
+            &engine_info,
+            &builtin_parser_info,
+            &custom_parser_info,
+            &self.entity_values,
+        )?;
+        let shared_resources = self.get_shared_resources()?;
+        let maybe_stemmer = shared_resources.stemmer.as_ref();
+        let values_updates = self.compute_values_updates(&parsers_dirs)?;
 
-            fs::remove_dir_all(parser_dir.clone()).with_context(|| {
-                NluInjectionErrorKind::InternalInjectionError {
-                    msg: format!("could not remove previous parser at {:?}", parser_dir),
+        let custom_gazetteer_parser =
+            GazetteerParser::<String>::from_path(&custom_parser_info.gazetteer_parser_dir)
+                .map_err(|e| anyhow!(e))?;
+        let builtin_gazetteer_parser = builtin_parser_info
+            .as_ref()
+            .map(|parser_info| {
+                GazetteerParser::<String>::from_path(&parser_info.gazetteer_parser_dir)
+                    .map_err(|e| anyhow!(e))
+            })
+            .transpose()?;
+        let gazetteer_entities = builtin_parser_info
+            .iter()
+            .map(|parser_info| &parser_info.gazetteer_parser_metadata)
+            .chain(std::iter::once(
+                &custom_parser_info.gazetteer_parser_metadata,
+            ))
+            .flat_map(|metadata| metadata.parsers_metadata.iter())
+            .map(|metadata| metadata.entity_identifier.clone())
+            .collect::<Vec<_>>();
+        let find_trained_matches = |entity: &str, raw_value: &str| {
+            let gazetteer_parser = if engine_info.custom_entities.contains(entity) {
+                Some(&custom_gazetteer_parser)
+            } else {
+                builtin_gazetteer_parser.as_ref()
+            };
+            gazetteer_parser
+                .map(|parser| find_full_matches(parser, entity, raw_value, engine_info.language))
+                .unwrap_or_else(|| Ok(vec![]))
+        };
+
+        let mut entities_reports = HashMap::with_capacity(values_updates.len());
+        for (entity, values_update) in values_updates.iter() {
+            let mut new_values = 0;
+            let mut existing_values = 0;
+            for added_value in self.entity_values[entity].iter().unique_by(|v| &v.value) {
+                let is_injected = values_update
+                    .previous_values
+                    .iter()
+                    .any(|v| v.value == added_value.value);
+                let is_trained = find_trained_matches(
+                    entity,
+                    &normalize(&*shared_resources.normalizer, &added_value.value),
+                )?
+                .into_iter()
+                .any(|entity_match| entity_match.resolved_value == added_value.value);
+                if is_injected || is_trained {
+                    existing_values += 1;
+                } else {
+                    new_values += 1;
                 }
-            })?;
+            }
+            let dropped_values = values_update
+                .previous_values
+                .iter()
+                .filter(|previous_value| {
+                    !values_update
+                        .injected_values
+                        .iter()
+                        .any(|v| v.value == previous_value.value)
+                })
+                .count();
 
-            gazetteer_parser.dump(&parser_dir)?;
+            let normalized_values = normalize_entity_value(
+                values_update.new_values.clone(),
+                &*shared_resources.normalizer,
+            );
+            let stemmed_values = if engine_info.custom_entities.contains(entity) {
+                stem_entity_value(
+                    normalized_values.clone(),
+                    &engine_info,
+                    &custom_parser_info,
+                    maybe_stemmer,
+                )?
+                .into_iter()
+                .filter(|value| !normalized_values.contains(value))
+                .map(|value| value.raw_value)
+                .unique()
+                .collect()
+            } else {
+                vec![]
+            };
+
+            let mut collisions = vec![];
+            for normalized_value in normalized_values.iter() {
+                let raw_value = &normalized_value.raw_value;
+                let other_entities = gazetteer_entities
+                    .iter()
+                    .filter(|other_entity| *other_entity != entity)
+                    .collect::<HashSet<_>>();
+                let trained_collisions = other_entities
+                    .iter()
+                    .map(|other_entity| find_trained_matches(other_entity, raw_value))
+                    .collect::<anyhow::Result<Vec<_>>>()?
+                    .into_iter()
+                    .flatten()
+                    .map(|entity_match| entity_match.entity_identifier);
+                let injected_collisions = values_updates
+                    .iter()
+                    .filter(|(other_entity, other_update)| {
+                        *other_entity != entity
+                            && normalize_entity_value(
+                                other_update.injected_values.clone(),
+                                &*shared_resources.normalizer,
+                            )
+                            .iter()
+                            .any(|other_value| other_value.raw_value == *raw_value)
+                    })
+                    .map(|(other_entity, _)| other_entity.clone());
+                collisions.extend(
+                    trained_collisions
+                        .chain(injected_collisions)
+                        .unique()
+                        .sorted()
+                        .map(|other_entity| ValueCollision {
+                            value: raw_value.clone(),
+                            other_entity,
+                        }),
+                );
+            }
+
+            let entity_report = EntityInjectionReport {
+                new_values,
+                existing_values,
+                dropped_values,
+                stemmed_values,
+                collisions,
+            };
+            entities_reports.insert(entity.clone(), entity_report);
         }
+        Ok(InjectionReport {
+            entities: entities_reports,
+        })
+    }
 
-        info!("Injection performed with success !");
+    /// Restores the entity parsers as they were before the last injection
+    pub fn rollback(&self) -> anyhow::Result<()> {
+        rollback(self.nlu_engine_dir.as_ref())?;
+        info!("Rollback performed with success !");
         Ok(())
     }
+
+    fn get_shared_resources(&self) -> anyhow::Result<Arc<SharedResources>> {
+        if let Some(resources) = self.shared_resources.as_ref() {
+            return Ok(resources.clone());
+        }
+        load_engine_shared_resources(self.nlu_engine_dir.as_ref()).with_context(|| {
+            NluInjectionErrorKind::InternalInjectionError {
+                msg: format!(
+                    "Could not load shared resources from {:?}",
+                    self.nlu_engine_dir.as_ref()
+                ),
+            }
+        })
+    }
+
+    /// Computes the values to inject in each entity parser
+    ///
+    /// Parsers from which values are removed are rebuilt from their trained values.
+    fn compute_values_updates(
+        &self,
+        parsers_dirs: &HashMap<String, PathBuf>,
+    ) -> anyhow::Result<HashMap<InjectedEntity, EntityValuesUpdate>> {
+        let no_removed_values = HashSet::new();
+        let mut values_updates = HashMap::with_capacity(self.entity_values.len());
+        for (entity, added_values) in self.entity_values.iter() {
+            let removed_values = self
+                .removed_values
+                .get(entity)
+                .unwrap_or(&no_removed_values);
+            let is_reset = self.from_vanilla || self.replaced_entities.contains(entity);
+            let previous_values = read_injected_values(&parsers_dirs[entity])?;
+            let kept_values = if is_reset {
+                vec![]
+            } else {
+                previous_values.clone()
+            };
+            let injected_values =
+                update_injected_values(entity, kept_values, added_values, removed_values)?;
+            let from_vanilla = is_reset || !removed_values.is_empty();
+            let new_values = if from_vanilla {
+                injected_values.clone()
+            } else {
+                added_values.clone()
+            };
+            let values_update = EntityValuesUpdate {
+                previous_values,
+                injected_values,
+                new_values,
+                from_vanilla,
+            };
+            values_updates.insert(entity.clone(), values_update);
+        }
+        Ok(values_updates)
+    }
+}
+
+/// Injects the new values in each parser and dumps the updated parsers in the staging
+/// directory, returning the paths of the current and updated parsers
+fn stage_parsers(
+    parser_updates: HashMap<InjectedEntity, EntityParserUpdate>,
+    parsers_dirs: &HashMap<String, PathBuf>,
+    staging_dir: &Path,
+) -> anyhow::Result<Vec<(PathBuf, PathBuf)>> {
+    parser_updates
+        .into_iter()
+        .enumerate()
+        .map(|(index, (entity, parser_update))| {
+            info!("Injecting values for entity '{}'", entity);
+
+            let parser_dir = &parsers_dirs[&entity];
+            let mut gazetteer_parser = GazetteerEntityParser::from_folder(parser_dir)?;
+
+            gazetteer_parser = gazetteer_parser.inject_new_values(
+                parser_update.new_values,
+                true,
+                parser_update.from_vanilla,
+            );
+
+            let staged_parser_dir = staging_dir.join(index.to_string());
+            gazetteer_parser.dump(&staged_parser_dir)?;
+            write_injected_values(&staged_parser_dir, &parser_update.injected_values)?;
+            sync_directory(&staged_parser_dir)?;
+            Ok((parser_dir.clone(), staged_parser_dir))
+        })
+        .collect()
 }
 
 fn get_entity_parsers_dirs(
     engine_info: &NluEngineInfo,
     maybe_builtin_parser_info: &Option<BuiltinGazetteerParserInfo>,
     custom_parser_info: &CustomGazetteerParserInfo,
-    entity_values: &HashMap<String, Vec<String>>,
+    entity_values: &HashMap<String, Vec<InjectedEntityValue>>,
 ) -> anyhow::Result<HashMap<String, PathBuf>> {
     entity_values
         .keys()
@@ -355,12 +808,155 @@ fn get_nlu_engine_info<P: AsRef<Path>>(engine_dir: P) -> anyhow::Result<NluEngin
     })
 }
 
-fn normalize_entity_value(entity_values: Vec<InjectedValue>) -> Vec<GazetteerEntityValue> {
+/// Finds the matches of an entity which span the whole value
+fn find_full_matches(
+    gazetteer_parser: &GazetteerParser<String>,
+    entity: &str,
+    value: &str,
+    language: NluUtilsLanguage,
+) -> anyhow::Result<Vec<GazetteerEntityMatch<String>>> {
+    let cleaned_value = tokenize_light(value, language).join(" ");
+    let value_length = cleaned_value.chars().count();
+    Ok(gazetteer_parser
+        .extract_entities(&cleaned_value, Some(&[entity.to_string()]), 0)
+        .map_err(|e| anyhow!(e))?
+        .into_iter()
+        .filter(|entity_match| entity_match.range == (0..value_length))
+        .collect())
+}
+
+/// Computes the values injected in an entity parser once the new values are added and the
+/// removed ones are discarded
+fn update_injected_values(
+    entity: &str,
+    previous_values: Vec<InjectedEntityValue>,
+    added_values: &[InjectedEntityValue],
+    removed_values: &HashSet<InjectedValue>,
+) -> anyhow::Result<Vec<InjectedEntityValue>> {
+    let injected_values = previous_values
+        .into_iter()
+        .chain(added_values.iter().cloned())
+        .unique()
+        .collect::<Vec<_>>();
+    if let Some(unknown_value) = removed_values
+        .iter()
+        .find(|value| !injected_values.iter().any(|v| v.value == **value))
+    {
+        bail!(NluInjectionErrorKind::EntityNotInjectable {
+            msg: format!(
+                "value '{}' of entity '{}' was not injected and cannot be removed",
+                unknown_value, entity
+            ),
+        });
+    }
+    Ok(injected_values
+        .into_iter()
+        .filter(|value| !removed_values.contains(&value.value))
+        .collect())
+}
+
+/// Reads the trained values of a gazetteer parser and the injected values stored in its
+/// directory
+fn read_entity_values(parser_dir: &Path) -> anyhow::Result<EntityValues> {
+    Ok(EntityValues {
+        trained: read_trained_values(parser_dir)?,
+        injected: read_injected_values(parser_dir)?,
+    })
+}
+
+/// Reads the trained values of a gazetteer parser, the raw values which resolve to the same
+/// value being gathered as its synonyms
+fn read_trained_values(parser_dir: &Path) -> anyhow::Result<Vec<InjectedEntityValue>> {
+    let parser_values = read_gazetteer_values(parser_dir).with_context(|| {
+        NluInjectionErrorKind::InternalInjectionError {
+            msg: format!(
+                "could not read the values of the parser in {:?}",
+                parser_dir
+            ),
+        }
+    })?;
+    let mut trained_values: Vec<InjectedEntityValue> = vec![];
+    let mut value_indexes: HashMap<String, usize> = HashMap::new();
+    for parser_value in parser_values.into_iter().filter(|value| !value.injected) {
+        let value_index = *value_indexes
+            .entry(parser_value.resolved_value.clone())
+            .or_insert_with(|| {
+                trained_values.push(InjectedEntityValue {
+                    value: parser_value.resolved_value.clone(),
+                    synonyms: vec![],
+                });
+                trained_values.len() - 1
+            });
+        let trained_value = &mut trained_values[value_index];
+        let raw_value = parser_value.raw_value.to_lowercase();
+        let is_known_form = raw_value == trained_value.value.to_lowercase()
+            || trained_value
+                .synonyms
+                .iter()
+                .any(|synonym| synonym.to_lowercase() == raw_value);
+        if !is_known_form {
+            trained_value.synonyms.push(parser_value.raw_value);
+        }
+    }
+    Ok(trained_values)
+}
+
+fn read_injected_values(parser_dir: &Path) -> anyhow::Result<Vec<InjectedEntityValue>> {
+    read_values_file(&parser_dir.join(INJECTED_VALUES_FILE))
+}
+
+fn read_values_file(values_path: &Path) -> anyhow::Result<Vec<InjectedEntityValue>> {
+    if !values_path.exists() {
+        return Ok(vec![]);
+    }
+    let values_file = fs::File::open(values_path).with_context(|| {
+        NluInjectionErrorKind::InternalInjectionError {
+            msg: format!("could not open values file {:?}", values_path),
+        }
+    })?;
+    serde_json::from_reader(values_file).with_context(|| {
+        NluInjectionErrorKind::InternalInjectionError {
+            msg: format!("invalid values file {:?}", values_path),
+        }
+    })
+}
+
+fn write_injected_values(
+    parser_dir: &Path,
+    injected_values: &[InjectedEntityValue],
+) -> anyhow::Result<()> {
+    let injected_values_path = parser_dir.join(INJECTED_VALUES_FILE);
+    let injected_values_file = fs::File::create(&injected_values_path)?;
+    serde_json::to_writer(injected_values_file, injected_values).with_context(|| {
+        NluInjectionErrorKind::InternalInjectionError {
+            msg: format!(
+                "could not write injected values file {:?}",
+                injected_values_path
+            ),
+        }
+    })?;
+    Ok(())
+}
+
+fn normalize_entity_value(
+    entity_values: Vec<InjectedEntityValue>,
+    normalizer: &dyn Normalizer,
+) -> Vec<GazetteerEntityValue> {
     entity_values
         .into_iter()
-        .map(|value| GazetteerEntityValue {
-            raw_value: normalize(&*value),
-            resolved_value: value,
+        .flat_map(|entity_value| {
+            let resolved_value = entity_value.value;
+            let raw_values = std::iter::once(&resolved_value)
+                .chain(entity_value.synonyms.iter())
+                .map(|value| normalize(normalizer, value))
+                .unique()
+                .collect::<Vec<_>>();
+            raw_values
+                .into_iter()
+                .map(move |raw_value| GazetteerEntityValue {
+                    raw_value,
+                    resolved_value: resolved_value.clone(),
+                })
         })
         .collect()
 }
@@ -415,6 +1011,7 @@ mod tests {
     use self::tempfile::tempdir;
     use snips_nlu_ontology::*;
 
+    use crate::resources::normalizer::TextNormalizer;
     use crate::SharedResources;
     use crate::SnipsNluEngine;
 
@@ -431,6 +1028,306 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_normalize_entity_value_with_synonyms() {
+        // Given
+        let entity_values = vec![
+            InjectedEntityValue {
+                value: "New York City".to_string(),
+                synonyms: vec![
+                    "NYC".to_string(),
+                    "the big apple".to_string(),
+                    "new york city".to_string(),
+                ],
+            },
+            InjectedEntityValue {
+                value: "Paris".to_string(),
+                synonyms: vec![],
+            },
+        ];
+
+        // When
+        let normalized_values = normalize_entity_value(entity_values, &TextNormalizer::default());
+
+        // Then
+        let expected_values = vec![
+            GazetteerEntityValue {
+                raw_value: "new york city".to_string(),
+                resolved_value: "New York City".to_string(),
+            },
+            GazetteerEntityValue {
+                raw_value: "nyc".to_string(),
+                resolved_value: "New York City".to_string(),
+            },
+            GazetteerEntityValue {
+                raw_value: "the big apple".to_string(),
+                resolved_value: "New York City".to_string(),
+            },
+            GazetteerEntityValue {
+                raw_value: "paris".to_string(),
+                resolved_value: "Paris".to_string(),
+            },
+        ];
+        assert_eq!(expected_values, normalized_values);
+    }
+
+    #[test]
+    fn test_add_dataset_values() {
+        // Given
+        let dataset: &[u8] = r#"{
+            "entities": {
+                "city": {
+                    "data": [
+                        {"value": "New York City", "synonyms": ["NYC", "the big apple"]},
+                        {"value": "Paris", "synonyms": []}
+                    ],
+                    "use_synonyms": true,
+                    "automatically_extensible": false
+                },
+                "country": {
+                    "data": [{"value": "France", "synonyms": ["la france"]}],
+                    "use_synonyms": false,
+                    "automatically_extensible": false
+                },
+                "snips/musicAlbum": {}
+            }
+        }"#
+        .as_ref();
+
+        // When
+        let injector = NluInjector::new("engine_dir")
+            .add_dataset_values(dataset)
+            .unwrap();
+
+        // Then
+        let expected_entity_values = vec![
+            (
+                "city".to_string(),
+                vec![
+                    InjectedEntityValue {
+                        value: "New York City".to_string(),
+                        synonyms: vec!["NYC".to_string(), "the big apple".to_string()],
+                    },
+                    InjectedEntityValue {
+                        value: "Paris".to_string(),
+                        synonyms: vec![],
+                    },
+                ],
+            ),
+            (
+                "country".to_string(),
+                vec![InjectedEntityValue {
+                    value: "France".to_string(),
+                    synonyms: vec![],
+                }],
+            ),
+        ]
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+        assert_eq!(expected_entity_values, injector.entity_values);
+    }
+
+    #[test]
+    fn test_update_injected_values() {
+        // Given
+        let get_value = |value: &str| InjectedEntityValue {
+            value: value.to_string(),
+            synonyms: vec![],
+        };
+        let previous_values = vec![get_value("funky"), get_value("jazzy")];
+        let added_values = vec![get_value("rock"), get_value("funky")];
+        let removed_values = vec!["jazzy".to_string()].into_iter().collect();
+
+        // When
+        let injected_values =
+            update_injected_values("playlist", previous_values, &added_values, &removed_values)
+                .unwrap();
+
+        // Then
+        assert_eq!(vec![get_value("funky"), get_value("rock")], injected_values);
+    }
+
+    #[test]
+    fn test_update_injected_values_should_fail_when_removing_unknown_value() {
+        // Given
+        let previous_values = vec![InjectedEntityValue {
+            value: "funky".to_string(),
+            synonyms: vec![],
+        }];
+        let removed_values = vec!["classical".to_string()].into_iter().collect();
+
+        // When
+        let injected_values =
+            update_injected_values("playlist", previous_values, &[], &removed_values);
+
+        // Then
+        assert!(injected_values.is_err());
+    }
+
+    #[test]
+    fn test_read_entity_values_gathers_trained_synonyms() {
+        // Given
+        let parser_dir = Path::new("data")
+            .join("tests")
+            .join("models")
+            .join("nlu_engine_game")
+            .join("custom_entity_parser")
+            .join("parser")
+            .join("parser_1");
+
+        // When
+        let entity_values = read_entity_values(&parser_dir).unwrap();
+
+        // Then
+        assert_eq!(4, entity_values.trained.len());
+        assert_eq!(
+            InjectedEntityValue {
+                value: "Invader Attack 3".to_string(),
+                synonyms: vec![
+                    "Invader Attack".to_string(),
+                    "Invader Attack three".to_string(),
+                ],
+            },
+            entity_values.trained[0]
+        );
+        assert!(entity_values.injected.is_empty());
+    }
+
+    #[test]
+    fn test_remove_injected_value() {
+        // Given
+        let path = Path::new("data")
+            .join("tests")
+            .join("models")
+            .join("nlu_engine_music");
+
+        let tdir = tempdir().unwrap();
+        dir::copy(path, tdir.as_ref(), &dir::CopyOptions::new()).unwrap();
+        let engine_dir = tdir.as_ref().join("nlu_engine_music");
+
+        NluInjector::new(&engine_dir)
+            .add_value("playlist".to_string(), "funky".to_string())
+            .add_value("playlist".to_string(), "jazzy".to_string())
+            .inject()
+            .unwrap();
+
+        // When
+        NluInjector::new(&engine_dir)
+            .remove_value("playlist".to_string(), "funky".to_string())
+            .inject()
+            .unwrap();
+
+        // Then
+        let entity_values = NluInjector::new(&engine_dir)
+            .list_values("playlist")
+            .unwrap();
+        let expected_values = EntityValues {
+            trained: vec![InjectedEntityValue {
+                value: "jazz".to_string(),
+                synonyms: vec![],
+            }],
+            injected: vec![InjectedEntityValue {
+                value: "jazzy".to_string(),
+                synonyms: vec![],
+            }],
+        };
+        assert_eq!(expected_values, entity_values);
+
+        let nlu_engine = SnipsNluEngine::from_path(&engine_dir).unwrap();
+        let parsing = nlu_engine
+            .parse("je voudrais ecouter ma playlist funky", None, None)
+            .unwrap();
+        assert_eq!(parsing.slots, vec![]);
+    }
+
+    #[test]
+    fn test_dry_run() {
+        // Given
+        let path = Path::new("data")
+            .join("tests")
+            .join("models")
+            .join("nlu_engine_music");
+        let tdir = tempdir().unwrap();
+        dir::copy(path, tdir.as_ref(), &dir::CopyOptions::new()).unwrap();
+        let engine_dir = tdir.as_ref().join("nlu_engine_music");
+        NluInjector::new(&engine_dir)
+            .add_value("playlist".to_string(), "chill".to_string())
+            .inject()
+            .unwrap();
+        let engine_shared_resources = load_engine_shared_resources(&engine_dir).unwrap();
+        let stemmer = MockedStemmer {
+            values: vec![("funky", "funk")].into_iter().collect(),
+        };
+        let mocked_resources = Arc::new(SharedResources {
+            builtin_entity_parser: engine_shared_resources.builtin_entity_parser.clone(),
+            custom_entity_parser: engine_shared_resources.custom_entity_parser.clone(),
+            gazetteers: engine_shared_resources.gazetteers.clone(),
+            stemmer: Some(Arc::new(stemmer)),
+            word_clusterers: HashMap::new(),
+            word_embeddings: HashMap::new(),
+            stop_words: HashSet::new(),
+            feature_registry: engine_shared_resources.feature_registry.clone(),
+            custom_entities: engine_shared_resources.custom_entities.clone(),
+            normalizer: engine_shared_resources.normalizer.clone(),
+        });
+        let injector = NluInjector::new(&engine_dir)
+            .from_vanilla(true)
+            .shared_resources(mocked_resources)
+            .add_value("playlist".to_string(), "funky".to_string())
+            .add_value("playlist".to_string(), "chill".to_string());
+
+        // When
+        let report = injector.dry_run().unwrap();
+
+        // Then
+        let expected_report = EntityInjectionReport {
+            new_values: 1,
+            existing_values: 1,
+            dropped_values: 0,
+            stemmed_values: vec!["funk".to_string()],
+            collisions: vec![],
+        };
+        assert_eq!(Some(&expected_report), report.entities.get("playlist"));
+        let injected_values = injector.list_values("playlist").unwrap().injected;
+        let expected_injected_values = vec![InjectedEntityValue {
+            value: "chill".to_string(),
+            synonyms: vec![],
+        }];
+        assert_eq!(expected_injected_values, injected_values);
+    }
+
+    #[test]
+    fn test_inject_to_zip() {
+        // Given
+        let path = Path::new("data")
+            .join("tests")
+            .join("models")
+            .join("nlu_engine_beverage.zip");
+        let archive = fs::File::open(path).unwrap();
+        let mut injected_archive = std::io::Cursor::new(vec![]);
+
+        // When
+        NluInjector::from_zip(archive)
+            .unwrap()
+            .add_value("Temperature".to_string(), "lukewarm".to_string())
+            .inject_to_zip(&mut injected_archive)
+            .unwrap();
+
+        // Then
+        let injected_archive = injected_archive.into_inner();
+        let injector = NluInjector::from_zip(std::io::Cursor::new(&injected_archive)).unwrap();
+        let expected_values = vec![InjectedEntityValue {
+            value: "lukewarm".to_string(),
+            synonyms: vec![],
+        }];
+        assert_eq!(
+            expected_values,
+            injector.list_values("Temperature").unwrap().injected
+        );
+        assert!(!injector.nlu_engine_dir.join(JOURNAL_FILE).exists());
+        assert!(SnipsNluEngine::from_zip(std::io::Cursor::new(&injected_archive)).is_ok());
+    }
+
     #[test]
     fn test_injection() {
         let path = Path::new("data")
@@ -457,7 +1354,11 @@ mod tests {
             gazetteers: engine_shared_resources.as_ref().gazetteers.clone(),
             stemmer: Some(Arc::new(stemmer.clone())),
             word_clusterers: HashMap::new(),
+            word_embeddings: HashMap::new(),
             stop_words: HashSet::new(),
+            feature_registry: engine_shared_resources.feature_registry.clone(),
+            custom_entities: engine_shared_resources.custom_entities.clone(),
+            normalizer: engine_shared_resources.normalizer.clone(),
         });
 
         // Behaviour before injection
@@ -512,7 +1413,11 @@ mod tests {
             gazetteers: injected_resources.gazetteers.clone(),
             stemmer: Some(Arc::new(stemmer)),
             word_clusterers: injected_resources.word_clusterers.clone(),
+            word_embeddings: injected_resources.word_embeddings.clone(),
             stop_words: HashSet::new(),
+            feature_registry: injected_resources.feature_registry.clone(),
+            normalizer: injected_resources.normalizer.clone(),
+            custom_entities: injected_resources.custom_entities.clone(),
         };
 
         let nlu_engine = SnipsNluEngine::from_path_with_resources(
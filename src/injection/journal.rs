@@ -0,0 +1,324 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use super::errors::NluInjectionErrorKind;
+
+pub const STAGING_DIRECTORY: &str = ".injection_staging";
+pub const BACKUP_DIRECTORY: &str = ".injection_backup";
+pub const JOURNAL_FILE: &str = ".injection_journal.json";
+
+/// Record of the parsers replaced by the last injection, along with the location of their
+/// previous version
+///
+/// The journal is marked as committed once all the parsers have been replaced, an uncommitted
+/// journal denoting an injection which was interrupted.
+#[derive(Debug, Serialize, Deserialize)]
+struct InjectionJournal {
+    entries: Vec<JournalEntry>,
+    #[serde(default)]
+    committed: bool,
+}
+
+/// Paths are relative to the engine directory so that the engine can be moved
+#[derive(Debug, Serialize, Deserialize)]
+struct JournalEntry {
+    parser_dir: PathBuf,
+    backup_dir: PathBuf,
+}
+
+/// Replaces the parsers with their staged version
+///
+/// The previous parsers are moved to a backup directory and recorded in a journal, so that
+/// the engine is restored if any of the replacements fails and can later be rolled back.
+pub fn commit_staged_parsers(
+    engine_dir: &Path,
+    staged_parsers: Vec<(PathBuf, PathBuf)>,
+) -> anyhow::Result<()> {
+    discard_journal(engine_dir)?;
+    let backup_dir = engine_dir.join(BACKUP_DIRECTORY);
+    fs::create_dir_all(&backup_dir).with_context(|| {
+        NluInjectionErrorKind::InternalInjectionError {
+            msg: format!("could not create backup directory {:?}", backup_dir),
+        }
+    })?;
+
+    let entries = staged_parsers
+        .iter()
+        .enumerate()
+        .map(|(index, (parser_dir, _))| {
+            let parser_dir = parser_dir
+                .strip_prefix(engine_dir)
+                .map_err(|_| anyhow!("parser {:?} is not in the engine directory", parser_dir))?
+                .to_path_buf();
+            let backup_dir = Path::new(BACKUP_DIRECTORY).join(index.to_string());
+            Ok(JournalEntry {
+                parser_dir,
+                backup_dir,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let mut journal = InjectionJournal {
+        entries,
+        committed: false,
+    };
+    write_journal(engine_dir, &journal)?;
+
+    for ((parser_dir, staged_parser_dir), entry) in staged_parsers.iter().zip(&journal.entries) {
+        let swap_result = fs::rename(parser_dir, engine_dir.join(&entry.backup_dir))
+            .and_then(|_| fs::rename(staged_parser_dir, parser_dir))
+            .with_context(|| NluInjectionErrorKind::InternalInjectionError {
+                msg: format!("could not replace parser at {:?}", parser_dir),
+            });
+        if let Err(error) = swap_result {
+            warn!("Injection failed, restoring previous parsers...");
+            restore_parsers(engine_dir, &journal)?;
+            discard_journal(engine_dir)?;
+            return Err(error);
+        }
+    }
+    for (parser_dir, _) in staged_parsers.iter() {
+        if let Some(parent_dir) = parser_dir.parent() {
+            sync_directory(parent_dir)?;
+        }
+    }
+    journal.committed = true;
+    write_journal(engine_dir, &journal)
+}
+
+/// Restores the parsers which were replaced by the last injection
+///
+/// An interrupted injection is rolled back as well, the parsers being restored as they were
+/// before it.
+pub fn rollback(engine_dir: &Path) -> anyhow::Result<()> {
+    let journal = read_journal(engine_dir)?
+        .ok_or_else(|| anyhow!("No injection to roll back in {:?}", engine_dir))?;
+    if journal.committed {
+        info!("Rolling back last injection...");
+    } else {
+        warn!("Rolling back interrupted injection...");
+    }
+    restore_parsers(engine_dir, &journal)?;
+    discard_journal(engine_dir)
+}
+
+/// Rolls back the injection which was interrupted before replacing all its parsers, if any
+///
+/// This must not be called while another process is injecting values in the same engine.
+pub fn recover_interrupted_injection(engine_dir: &Path) -> anyhow::Result<()> {
+    match read_journal(engine_dir)? {
+        Some(ref journal) if !journal.committed => {
+            warn!("Rolling back interrupted injection...");
+            restore_parsers(engine_dir, journal)?;
+            discard_journal(engine_dir)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Flushes to disk all the files of a directory, recursively, as well as the directory itself
+pub fn sync_directory(dir: &Path) -> anyhow::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            sync_directory(&path)?;
+        } else {
+            fs::File::open(&path)?.sync_all()?;
+        }
+    }
+    fs::File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+fn restore_parsers(engine_dir: &Path, journal: &InjectionJournal) -> anyhow::Result<()> {
+    for entry in journal.entries.iter().rev() {
+        let backup_dir = engine_dir.join(&entry.backup_dir);
+        // Parsers without backup have not been replaced
+        if !backup_dir.exists() {
+            continue;
+        }
+        let parser_dir = engine_dir.join(&entry.parser_dir);
+        if parser_dir.exists() {
+            fs::remove_dir_all(&parser_dir)?;
+        }
+        fs::rename(&backup_dir, &parser_dir).with_context(|| {
+            NluInjectionErrorKind::InternalInjectionError {
+                msg: format!("could not restore parser at {:?}", parser_dir),
+            }
+        })?;
+        if let Some(parent_dir) = parser_dir.parent() {
+            sync_directory(parent_dir)?;
+        }
+    }
+    Ok(())
+}
+
+fn read_journal(engine_dir: &Path) -> anyhow::Result<Option<InjectionJournal>> {
+    let journal_path = engine_dir.join(JOURNAL_FILE);
+    if !journal_path.exists() {
+        return Ok(None);
+    }
+    let journal_file = fs::File::open(&journal_path).with_context(|| {
+        NluInjectionErrorKind::InternalInjectionError {
+            msg: format!("could not open injection journal {:?}", journal_path),
+        }
+    })?;
+    let journal = serde_json::from_reader(journal_file).with_context(|| {
+        NluInjectionErrorKind::InternalInjectionError {
+            msg: format!("invalid injection journal {:?}", journal_path),
+        }
+    })?;
+    Ok(Some(journal))
+}
+
+fn write_journal(engine_dir: &Path, journal: &InjectionJournal) -> anyhow::Result<()> {
+    let journal_path = engine_dir.join(JOURNAL_FILE);
+    let temp_journal_path = engine_dir.join(format!("{}.tmp", JOURNAL_FILE));
+    let mut journal_file = fs::File::create(&temp_journal_path)?;
+    journal_file.write_all(&serde_json::to_vec(journal)?)?;
+    journal_file.sync_all()?;
+    fs::rename(&temp_journal_path, &journal_path).with_context(|| {
+        NluInjectionErrorKind::InternalInjectionError {
+            msg: format!("could not write injection journal {:?}", journal_path),
+        }
+    })?;
+    fs::File::open(engine_dir)?.sync_all()?;
+    Ok(())
+}
+
+fn discard_journal(engine_dir: &Path) -> anyhow::Result<()> {
+    let journal_path = engine_dir.join(JOURNAL_FILE);
+    if journal_path.exists() {
+        fs::remove_file(&journal_path)?;
+    }
+    let backup_dir = engine_dir.join(BACKUP_DIRECTORY);
+    if backup_dir.exists() {
+        fs::remove_dir_all(&backup_dir)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use self::tempfile::tempdir;
+
+    use super::*;
+
+    fn create_parser(dir: &Path, content: &str) {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join("parser"), content).unwrap();
+    }
+
+    fn read_parser(dir: &Path) -> String {
+        fs::read_to_string(dir.join("parser")).unwrap()
+    }
+
+    #[test]
+    fn test_commit_staged_parsers_and_rollback() {
+        // Given
+        let engine_dir = tempdir().unwrap();
+        let engine_dir = engine_dir.path();
+        let parser_dir = engine_dir.join("custom_entity_parser").join("parser_1");
+        let staged_parser_dir = engine_dir.join(STAGING_DIRECTORY).join("0");
+        create_parser(&parser_dir, "original");
+        create_parser(&staged_parser_dir, "injected");
+
+        // When
+        commit_staged_parsers(
+            engine_dir,
+            vec![(parser_dir.clone(), staged_parser_dir.clone())],
+        )
+        .unwrap();
+
+        // Then
+        assert_eq!("injected", read_parser(&parser_dir));
+        assert!(!staged_parser_dir.exists());
+        assert!(read_journal(engine_dir).unwrap().unwrap().committed);
+
+        // When
+        recover_interrupted_injection(engine_dir).unwrap();
+
+        // Then
+        assert_eq!("injected", read_parser(&parser_dir));
+
+        // When
+        rollback(engine_dir).unwrap();
+
+        // Then
+        assert_eq!("original", read_parser(&parser_dir));
+        assert!(rollback(engine_dir).is_err());
+    }
+
+    #[test]
+    fn test_commit_staged_parsers_should_restore_parsers_on_failure() {
+        // Given
+        let engine_dir = tempdir().unwrap();
+        let engine_dir = engine_dir.path();
+        let parser_dir_1 = engine_dir.join("custom_entity_parser").join("parser_1");
+        let parser_dir_2 = engine_dir.join("custom_entity_parser").join("parser_2");
+        let staged_parser_dir_1 = engine_dir.join(STAGING_DIRECTORY).join("0");
+        let missing_staged_parser_dir = engine_dir.join(STAGING_DIRECTORY).join("1");
+        create_parser(&parser_dir_1, "original_1");
+        create_parser(&parser_dir_2, "original_2");
+        create_parser(&staged_parser_dir_1, "injected_1");
+
+        // When
+        let result = commit_staged_parsers(
+            engine_dir,
+            vec![
+                (parser_dir_1.clone(), staged_parser_dir_1),
+                (parser_dir_2.clone(), missing_staged_parser_dir),
+            ],
+        );
+
+        // Then
+        assert!(result.is_err());
+        assert_eq!("original_1", read_parser(&parser_dir_1));
+        assert_eq!("original_2", read_parser(&parser_dir_2));
+        assert!(rollback(engine_dir).is_err());
+    }
+
+    #[test]
+    fn test_recover_interrupted_injection() {
+        // Given
+        let engine_dir = tempdir().unwrap();
+        let engine_dir = engine_dir.path();
+        let parser_dir_1 = engine_dir.join("custom_entity_parser").join("parser_1");
+        let parser_dir_2 = engine_dir.join("custom_entity_parser").join("parser_2");
+        let backup_dir_1 = Path::new(BACKUP_DIRECTORY).join("0");
+        let backup_dir_2 = Path::new(BACKUP_DIRECTORY).join("1");
+        // The injection was interrupted after replacing the first parser
+        create_parser(&parser_dir_1, "injected_1");
+        create_parser(&engine_dir.join(&backup_dir_1), "original_1");
+        create_parser(&parser_dir_2, "original_2");
+        let journal = InjectionJournal {
+            entries: vec![
+                JournalEntry {
+                    parser_dir: parser_dir_1.strip_prefix(engine_dir).unwrap().to_path_buf(),
+                    backup_dir: backup_dir_1,
+                },
+                JournalEntry {
+                    parser_dir: parser_dir_2.strip_prefix(engine_dir).unwrap().to_path_buf(),
+                    backup_dir: backup_dir_2,
+                },
+            ],
+            committed: false,
+        };
+        write_journal(engine_dir, &journal).unwrap();
+
+        // When
+        recover_interrupted_injection(engine_dir).unwrap();
+
+        // Then
+        assert_eq!("original_1", read_parser(&parser_dir_1));
+        assert_eq!("original_2", read_parser(&parser_dir_2));
+        assert!(read_journal(engine_dir).unwrap().is_none());
+        assert!(!engine_dir.join(BACKUP_DIRECTORY).exists());
+    }
+}
@@ -1,5 +1,10 @@
 mod errors;
 mod injection;
+mod journal;
 
 pub use self::errors::NluInjectionErrorKind;
-pub use self::injection::{InjectedEntity, InjectedValue, NluInjector};
+pub use self::injection::{
+    EntityInjectionReport, EntityValues, InjectedEntity, InjectedEntityValue, InjectedValue,
+    InjectionReport, NluInjector, ValueCollision,
+};
+pub(crate) use self::journal::recover_interrupted_injection;
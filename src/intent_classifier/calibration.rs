@@ -0,0 +1,305 @@
+use ndarray::prelude::*;
+use snips_nlu_ontology::IntentClassifierResult;
+
+use crate::models::{CalibrationConfiguration, ScoresNormalization};
+use anyhow::{bail, Result};
+
+/// Maps the raw decision values of a logistic regression to probabilities
+///
+/// The decision values are first rescaled, using either a temperature or per-class Platt
+/// parameters, and then normalized. The default calibration applies a sigmoid on each
+/// decision value, which corresponds to the raw one-vs.-rest estimates.
+pub struct Calibration {
+    normalization: ScoresNormalization,
+    scaling: Scaling,
+}
+
+enum Scaling {
+    Identity,
+    Temperature(f32),
+    Platt {
+        slopes: Array1<f32>,
+        intercepts: Array1<f32>,
+    },
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self {
+            normalization: ScoresNormalization::OneVsRest,
+            scaling: Scaling::Identity,
+        }
+    }
+}
+
+impl Calibration {
+    pub fn new(config: CalibrationConfiguration) -> Result<Self> {
+        let scaling = match (config.temperature, config.platt_scaling) {
+            (Some(_), Some(_)) => {
+                bail!("Temperature and Platt scaling cannot be used together for calibration")
+            }
+            (Some(temperature), None) => {
+                if temperature <= 0. {
+                    bail!(
+                        "Calibration temperature must be positive, found {}",
+                        temperature
+                    );
+                }
+                Scaling::Temperature(temperature)
+            }
+            (None, Some(platt)) => {
+                if platt.slopes.len() != platt.intercepts.len() {
+                    bail!(
+                        "Mismatched Platt scaling parameters: found {} slopes and {} intercepts",
+                        platt.slopes.len(),
+                        platt.intercepts.len()
+                    );
+                }
+                Scaling::Platt {
+                    slopes: Array::from_vec(platt.slopes),
+                    intercepts: Array::from_vec(platt.intercepts),
+                }
+            }
+            (None, None) => Scaling::Identity,
+        };
+        Ok(Self {
+            normalization: config.normalization,
+            scaling,
+        })
+    }
+
+    /// Whether the calibrated probabilities of the classes sum to one
+    pub fn is_normalized(&self) -> bool {
+        self.normalization != ScoresNormalization::OneVsRest
+    }
+
+    /// Number of decision values expected by the calibration, if it depends on it
+    pub fn nb_decision_values(&self) -> Option<usize> {
+        match &self.scaling {
+            Scaling::Platt { slopes, .. } => Some(slopes.dim()),
+            _ => None,
+        }
+    }
+
+    /// Converts decision values into probabilities
+    ///
+    /// A single decision value corresponds to a binary classifier, in which case the
+    /// probabilities of both classes are returned.
+    pub fn calibrate(&self, decision_values: Array1<f32>) -> Array1<f32> {
+        let scaled_values = self.scale(decision_values);
+        if scaled_values.dim() == 1 {
+            let proba = logit(scaled_values[0]);
+            return arr1(&[1.0 - proba, proba]);
+        }
+        match self.normalization {
+            ScoresNormalization::OneVsRest => scaled_values.mapv(logit),
+            ScoresNormalization::Renormalization => {
+                let probas = scaled_values.mapv(logit);
+                let total = probas.sum();
+                if total > 0. {
+                    probas / total
+                } else {
+                    probas
+                }
+            }
+            ScoresNormalization::Softmax => softmax(scaled_values),
+        }
+    }
+
+    fn scale(&self, decision_values: Array1<f32>) -> Array1<f32> {
+        match &self.scaling {
+            Scaling::Identity => decision_values,
+            Scaling::Temperature(temperature) => decision_values / *temperature,
+            Scaling::Platt { slopes, intercepts } => decision_values * slopes + intercepts,
+        }
+    }
+}
+
+/// Rescales the probabilities of a subset of the intents, such as the whitelisted ones, so that
+/// they sum to one
+pub fn renormalize_results(results: Vec<IntentClassifierResult>) -> Vec<IntentClassifierResult> {
+    let total: f32 = results.iter().map(|res| res.confidence_score).sum();
+    if total <= 0. {
+        return results;
+    }
+    results
+        .into_iter()
+        .map(|res| IntentClassifierResult {
+            confidence_score: res.confidence_score / total,
+            ..res
+        })
+        .collect()
+}
+
+fn logit(x: f32) -> f32 {
+    1. / (1. + (-x).exp())
+}
+
+fn softmax(values: Array1<f32>) -> Array1<f32> {
+    // Shifting by the max value does not change the result but avoids overflows
+    let max_value = values.fold(f32::NEG_INFINITY, |max, v| max.max(*v));
+    let exp_values = values.mapv(|v| (v - max_value).exp());
+    let total = exp_values.sum();
+    exp_values / total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::PlattScalingConfiguration;
+    use crate::testutils::{assert_epsilon_eq_array1, epsilon_eq};
+    use ndarray::array;
+
+    fn calibration_config(normalization: ScoresNormalization) -> CalibrationConfiguration {
+        CalibrationConfiguration {
+            normalization,
+            temperature: None,
+            platt_scaling: None,
+        }
+    }
+
+    #[test]
+    fn test_default_calibration_is_one_vs_rest() {
+        // Given
+        let calibration = Calibration::default();
+
+        // When
+        let probas = calibration.calibrate(array![0.9, -0.67, 1.91]);
+
+        // Then
+        let expected_probas = array![0.7109495, 0.3384968, 0.8710191];
+        assert_epsilon_eq_array1(&probas, &expected_probas, 1e-06);
+    }
+
+    #[test]
+    fn test_softmax_calibration() {
+        // Given
+        let config = calibration_config(ScoresNormalization::Softmax);
+        let calibration = Calibration::new(config).unwrap();
+
+        // When
+        let probas = calibration.calibrate(array![1.0, 2.0, 3.0]);
+
+        // Then
+        let expected_probas = array![0.09003057, 0.24472847, 0.66524096];
+        assert_epsilon_eq_array1(&probas, &expected_probas, 1e-06);
+        assert!(epsilon_eq(probas.sum(), 1.0, 1e-06));
+    }
+
+    #[test]
+    fn test_renormalization_calibration() {
+        // Given
+        let config = calibration_config(ScoresNormalization::Renormalization);
+        let calibration = Calibration::new(config).unwrap();
+
+        // When
+        let probas = calibration.calibrate(array![0.9, -0.67, 1.91]);
+
+        // Then
+        let expected_probas = array![0.3701964, 0.1762577, 0.4535458];
+        assert_epsilon_eq_array1(&probas, &expected_probas, 1e-06);
+        assert!(epsilon_eq(probas.sum(), 1.0, 1e-06));
+    }
+
+    #[test]
+    fn test_renormalize_results() {
+        // Given
+        let results = vec![
+            IntentClassifierResult {
+                intent_name: Some("MakeTea".to_string()),
+                confidence_score: 0.3,
+            },
+            IntentClassifierResult {
+                intent_name: None,
+                confidence_score: 0.1,
+            },
+        ];
+
+        // When
+        let renormalized_results = renormalize_results(results);
+
+        // Then
+        assert!(epsilon_eq(
+            renormalized_results[0].confidence_score,
+            0.75,
+            1e-06
+        ));
+        assert!(epsilon_eq(
+            renormalized_results[1].confidence_score,
+            0.25,
+            1e-06
+        ));
+    }
+
+    #[test]
+    fn test_softmax_calibration_with_temperature() {
+        // Given
+        let mut config = calibration_config(ScoresNormalization::Softmax);
+        config.temperature = Some(2.0);
+        let calibration = Calibration::new(config).unwrap();
+
+        // When
+        let probas = calibration.calibrate(array![2.0, 4.0, 6.0]);
+
+        // Then
+        let expected_probas = array![0.09003057, 0.24472847, 0.66524096];
+        assert_epsilon_eq_array1(&probas, &expected_probas, 1e-06);
+    }
+
+    #[test]
+    fn test_platt_scaling_calibration() {
+        // Given
+        let mut config = calibration_config(ScoresNormalization::OneVsRest);
+        config.platt_scaling = Some(PlattScalingConfiguration {
+            slopes: vec![2.0, 0.5],
+            intercepts: vec![-1.0, 0.0],
+        });
+        let calibration = Calibration::new(config).unwrap();
+
+        // When
+        let probas = calibration.calibrate(array![0.5, 2.0]);
+
+        // Then
+        let expected_probas = array![0.5, 0.7310586];
+        assert_epsilon_eq_array1(&probas, &expected_probas, 1e-06);
+    }
+
+    #[test]
+    fn test_calibration_when_binary() {
+        // Given
+        let config = calibration_config(ScoresNormalization::Softmax);
+        let calibration = Calibration::new(config).unwrap();
+
+        // When
+        let probas = calibration.calibrate(array![0.9]);
+
+        // Then
+        let expected_probas = array![0.2890504, 0.7109495];
+        assert_epsilon_eq_array1(&probas, &expected_probas, 1e-06);
+    }
+
+    #[test]
+    fn test_invalid_calibration_configurations() {
+        // Given
+        let mut negative_temperature = calibration_config(ScoresNormalization::Softmax);
+        negative_temperature.temperature = Some(-1.0);
+
+        let mut temperature_and_platt = calibration_config(ScoresNormalization::Softmax);
+        temperature_and_platt.temperature = Some(1.0);
+        temperature_and_platt.platt_scaling = Some(PlattScalingConfiguration {
+            slopes: vec![1.0],
+            intercepts: vec![0.0],
+        });
+
+        let mut mismatched_platt = calibration_config(ScoresNormalization::Softmax);
+        mismatched_platt.platt_scaling = Some(PlattScalingConfiguration {
+            slopes: vec![1.0, 1.0],
+            intercepts: vec![0.0],
+        });
+
+        // When / Then
+        assert!(Calibration::new(negative_temperature).is_err());
+        assert!(Calibration::new(temperature_and_platt).is_err());
+        assert!(Calibration::new(mismatched_platt).is_err());
+    }
+}
@@ -0,0 +1,335 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::iter::FromIterator;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use itertools::Itertools;
+use log::{debug, info};
+use ndarray::prelude::*;
+use snips_nlu_ontology::{IntentClassifierResult, Language};
+use snips_nlu_utils::language::Language as NluUtilsLanguage;
+use snips_nlu_utils::string::normalize;
+use snips_nlu_utils::token::tokenize_light;
+
+use crate::intent_classifier::IntentClassifier;
+use crate::language::FromLanguage;
+use crate::models::{
+    CalibrationConfiguration, EmbeddingIntentClassifierModel, ScoresNormalization,
+};
+use crate::resources::word_embeddings::WordEmbeddings;
+use crate::resources::SharedResources;
+use crate::utils::IntentName;
+use anyhow::{anyhow, bail, Context, Result};
+
+use super::calibration::{renormalize_results, Calibration};
+use super::logreg::MulticlassLogisticRegression;
+
+/// Intent classifier which averages pretrained word vectors and scores intents with a dense
+/// layer followed by a softmax
+pub struct EmbeddingIntentClassifier {
+    language: NluUtilsLanguage,
+    intent_list: Vec<Option<IntentName>>,
+    word_embeddings: Arc<dyn WordEmbeddings>,
+    dense_layer: MulticlassLogisticRegression,
+}
+
+impl EmbeddingIntentClassifier {
+    pub fn from_path<P: AsRef<Path>>(
+        path: P,
+        shared_resources: Arc<SharedResources>,
+    ) -> Result<Self> {
+        info!(
+            "Loading embedding intent classifier ({:?}) ...",
+            path.as_ref()
+        );
+        let classifier_model_path = path.as_ref().join("intent_classifier.json");
+        let model_file = File::open(&classifier_model_path).with_context(|| {
+            format!(
+                "Cannot open EmbeddingIntentClassifier file '{:?}'",
+                &classifier_model_path
+            )
+        })?;
+        let model: EmbeddingIntentClassifierModel = serde_json::from_reader(model_file)
+            .with_context(|| "Cannot deserialize EmbeddingIntentClassifier json data")?;
+        let classifier = Self::new(model, shared_resources)?;
+        info!("Embedding intent classifier loaded");
+        Ok(classifier)
+    }
+
+    pub fn new(
+        model: EmbeddingIntentClassifierModel,
+        shared_resources: Arc<SharedResources>,
+    ) -> Result<Self> {
+        let ontology_language =
+            Language::from_str(model.language_code.as_ref()).map_err(|e| anyhow!(e))?;
+        let language = NluUtilsLanguage::from_language(ontology_language);
+
+        let word_embeddings = shared_resources
+            .word_embeddings
+            .get(&model.word_embeddings_name)
+            .cloned()
+            .ok_or_else(|| {
+                anyhow!(
+                    "Cannot find word embeddings '{}' in shared resources",
+                    model.word_embeddings_name
+                )
+            })?;
+
+        let nb_classes = model.intercept.len();
+        let nb_features = word_embeddings.dimension();
+        if model.intent_list.len() != nb_classes || model.coeffs.len() != nb_classes {
+            bail!(
+                "Inconsistent number of intents: found {} intents, {} intercepts and {} coeffs",
+                model.intent_list.len(),
+                nb_classes,
+                model.coeffs.len()
+            );
+        }
+        if let Some(coeffs) = model.coeffs.iter().find(|c| c.len() != nb_features) {
+            bail!(
+                "Expected coeffs of dimension {} but found {}",
+                nb_features,
+                coeffs.len()
+            );
+        }
+
+        // Note: the deserialized coeffs matrix is transposed
+        let coeffs = model.coeffs;
+        let weights = Array::from_shape_fn((nb_features, nb_classes), |(i, j)| coeffs[j][i]);
+        let softmax = Calibration::new(CalibrationConfiguration {
+            normalization: ScoresNormalization::Softmax,
+            temperature: None,
+            platt_scaling: None,
+        })?;
+        let dense_layer =
+            MulticlassLogisticRegression::new(Array::from_vec(model.intercept), weights)?
+                .with_calibration(softmax)?;
+
+        Ok(Self {
+            language,
+            intent_list: model.intent_list,
+            word_embeddings,
+            dense_layer,
+        })
+    }
+}
+
+impl IntentClassifier for EmbeddingIntentClassifier {
+    fn get_intent(
+        &self,
+        input: &str,
+        intents_whitelist: Option<&[&str]>,
+    ) -> Result<IntentClassifierResult> {
+        debug!("Classifying intent...");
+        let intents_results = self.get_intents_with_whitelist(input, intents_whitelist)?;
+        let intent_result = if intents_results.is_empty() {
+            IntentClassifierResult {
+                intent_name: None,
+                confidence_score: 1.0,
+            }
+        } else {
+            intents_results.into_iter().next().unwrap()
+        };
+        debug!("Intent found: '{:?}'", intent_result.intent_name);
+        Ok(intent_result)
+    }
+
+    fn get_intents(&self, input: &str) -> Result<Vec<IntentClassifierResult>> {
+        self.get_intents_with_whitelist(input, None)
+    }
+}
+
+impl EmbeddingIntentClassifier {
+    fn get_intents_with_whitelist(
+        &self,
+        input: &str,
+        intents_whitelist: Option<&[&str]>,
+    ) -> Result<Vec<IntentClassifierResult>> {
+        if self.intent_list.len() <= 1 {
+            return Ok(vec![IntentClassifierResult {
+                intent_name: self.intent_list.first().cloned().unwrap_or(None),
+                confidence_score: 1.0,
+            }]);
+        }
+
+        let opt_embedding = self.compute_embedding(input);
+        let scores = if let Some(embedding) = opt_embedding {
+            self.dense_layer.run(&embedding.view())?
+        } else {
+            // None of the words is known, which corresponds to the null intent
+            self.intent_list
+                .iter()
+                .map(|intent_name| if intent_name.is_none() { 1.0 } else { 0.0 })
+                .collect()
+        };
+
+        let opt_intents_set: Option<HashSet<&str>> =
+            intents_whitelist.map(|intent_list| HashSet::from_iter(intent_list.iter().cloned()));
+
+        let intents_results = self
+            .intent_list
+            .iter()
+            .zip(scores.iter())
+            .map(|(intent_name, score)| IntentClassifierResult {
+                intent_name: intent_name.clone(),
+                confidence_score: *score,
+            })
+            .filter(|res| {
+                if let Some(intent) = res.intent_name.as_ref() {
+                    opt_intents_set
+                        .as_ref()
+                        .map(|intents| intents.contains(&**intent))
+                        .unwrap_or(true)
+                } else {
+                    true
+                }
+            })
+            .collect_vec();
+        // The softmax probabilities are distributed over the whitelisted intents only
+        let intents_results = if opt_intents_set.is_some() {
+            renormalize_results(intents_results)
+        } else {
+            intents_results
+        };
+        Ok(intents_results
+            .into_iter()
+            .sorted_by(|a, b| b.confidence_score.partial_cmp(&a.confidence_score).unwrap())
+            .collect())
+    }
+
+    /// Averages the vectors of the known words of the input
+    fn compute_embedding(&self, input: &str) -> Option<Array1<f32>> {
+        let mut embedding: Array1<f32> = Array::zeros(self.word_embeddings.dimension());
+        let mut nb_known_words = 0;
+        for token in tokenize_light(input, self.language) {
+            if let Some(word_vector) = self.word_embeddings.get_embedding(&normalize(&token)) {
+                embedding += &word_vector;
+                nb_known_words += 1;
+            }
+        }
+        if nb_known_words == 0 {
+            None
+        } else {
+            Some(embedding / nb_known_words as f32)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::resources::word_embeddings::HashMapWordEmbeddings;
+    use crate::testutils::{epsilon_eq, SharedResourcesBuilder};
+
+    fn get_sample_embedding_classifier() -> EmbeddingIntentClassifier {
+        let embeddings: &[u8] = r#"
+turn 1.0 0.0
+on 1.0 0.0
+lights 1.0 0.5
+play 0.0 1.0
+jazz 0.0 1.0
+"#
+        .as_ref();
+        let word_embeddings = HashMapWordEmbeddings::from_reader(embeddings).unwrap();
+        let resources = SharedResourcesBuilder::default()
+            .word_embeddings("sample_embeddings", word_embeddings)
+            .build();
+
+        let model = EmbeddingIntentClassifierModel {
+            language_code: "en".to_string(),
+            word_embeddings_name: "sample_embeddings".to_string(),
+            intent_list: vec![
+                Some("TurnLightsOn".to_string()),
+                Some("PlayMusic".to_string()),
+                None,
+            ],
+            intercept: vec![0.0, 0.0, 0.5],
+            coeffs: vec![vec![4.0, -2.0], vec![-2.0, 4.0], vec![-1.0, -1.0]],
+        };
+        EmbeddingIntentClassifier::new(model, Arc::new(resources)).unwrap()
+    }
+
+    #[test]
+    fn test_get_intent() {
+        // Given
+        let classifier = get_sample_embedding_classifier();
+
+        // When
+        let lights_result = classifier.get_intent("Turn on the lights", None).unwrap();
+        let music_result = classifier.get_intent("play jazz", None).unwrap();
+
+        // Then
+        assert_eq!(Some("TurnLightsOn".to_string()), lights_result.intent_name);
+        assert_eq!(Some("PlayMusic".to_string()), music_result.intent_name);
+    }
+
+    #[test]
+    fn test_get_intents() {
+        // Given
+        let classifier = get_sample_embedding_classifier();
+
+        // When
+        let intents = classifier.get_intents("play jazz").unwrap();
+
+        // Then
+        let actual_intents: Vec<Option<String>> =
+            intents.iter().map(|res| res.intent_name.clone()).collect();
+        let expected_intents = vec![
+            Some("PlayMusic".to_string()),
+            None,
+            Some("TurnLightsOn".to_string()),
+        ];
+        assert_eq!(expected_intents, actual_intents);
+        let total_score: f32 = intents.iter().map(|res| res.confidence_score).sum();
+        assert!(epsilon_eq(1.0, total_score, 1e-06));
+    }
+
+    #[test]
+    fn test_get_intent_with_unknown_words() {
+        // Given
+        let classifier = get_sample_embedding_classifier();
+
+        // When
+        let result = classifier.get_intent("foo bar", None).unwrap();
+
+        // Then
+        let expected_result = IntentClassifierResult {
+            intent_name: None,
+            confidence_score: 1.0,
+        };
+        assert_eq!(expected_result, result);
+    }
+
+    #[test]
+    fn test_get_intent_with_whitelist() {
+        // Given
+        let classifier = get_sample_embedding_classifier();
+
+        // When
+        let result = classifier
+            .get_intent("play jazz", Some(&["TurnLightsOn"]))
+            .unwrap();
+
+        // Then
+        assert_eq!(None, result.intent_name);
+    }
+
+    #[test]
+    fn test_get_intents_with_whitelist() {
+        // Given
+        let classifier = get_sample_embedding_classifier();
+
+        // When
+        let intents = classifier
+            .get_intents_with_whitelist("play jazz", Some(&["TurnLightsOn"]))
+            .unwrap();
+
+        // Then
+        let total_score: f32 = intents.iter().map(|res| res.confidence_score).sum();
+        assert!(epsilon_eq(1.0, total_score, 1e-06));
+        assert_eq!(2, intents.len());
+    }
+}
@@ -9,11 +9,11 @@ use itertools::Itertools;
 use ndarray::prelude::*;
 use snips_nlu_ontology::{BuiltinEntityKind, Language};
 use snips_nlu_utils::language::Language as NluUtilsLanguage;
-use snips_nlu_utils::string::normalize;
 use snips_nlu_utils::token::{compute_all_ngrams, tokenize_light};
 
 use crate::language::FromLanguage;
 use crate::models::{CooccurrenceVectorizerModel, FeaturizerModel, TfidfVectorizerModel};
+use crate::resources::normalizer::{normalize, Normalizer};
 use crate::resources::stemmer::Stemmer;
 use crate::resources::word_clusterer::WordClusterer;
 use crate::resources::SharedResources;
@@ -84,11 +84,21 @@ impl Featurizer {
         };
         Ok(Array::from_iter(features))
     }
+
+    /// Names of the features returned by `transform`, in the same order
+    pub fn feature_names(&self) -> Vec<String> {
+        let mut names = self.tfidf_vectorizer.feature_names.clone();
+        if let Some(vectorizer) = self.cooccurrence_vectorizer.as_ref() {
+            names.extend(vectorizer.feature_names.iter().cloned());
+        }
+        names
+    }
 }
 
 pub struct TfidfVectorizer {
     builtin_entity_scope: Vec<BuiltinEntityKind>,
     vocabulary: HashMap<String, usize>,
+    feature_names: Vec<String>,
     idf_diag: Vec<f32>,
     word_clusterer: Option<Arc<dyn WordClusterer>>,
     stemmer: Option<Arc<dyn Stemmer>>,
@@ -120,6 +130,11 @@ impl TfidfVectorizer {
         shared_resources: Arc<SharedResources>,
     ) -> Result<Self> {
         let vocabulary = model.vectorizer.vocab;
+        let vocabulary_size = vocabulary.values().max().map(|idx| idx + 1).unwrap_or(0);
+        let mut feature_names = vec![String::new(); vocabulary_size];
+        for (word, idx) in vocabulary.iter() {
+            feature_names[*idx] = word.clone();
+        }
         let idf_diag = model.vectorizer.idf_diag;
 
         let ontology_language =
@@ -167,6 +182,7 @@ impl TfidfVectorizer {
         Ok(Self {
             builtin_entity_scope,
             vocabulary,
+            feature_names,
             idf_diag,
             word_clusterer: opt_word_clusterer,
             stemmer,
@@ -177,7 +193,11 @@ impl TfidfVectorizer {
 
     pub fn transform(&self, utterance: &str) -> Result<Vec<f32>> {
         let tokens = tokenize_light(utterance, self.language);
-        let normalized_tokens = normalize_stem(&tokens, self.stemmer.clone());
+        let normalized_tokens = normalize_stem(
+            &tokens,
+            &*self.shared_resources.normalizer,
+            self.stemmer.clone(),
+        );
 
         // Extract builtin entities on the raw utterance
         let builtin_entities = self
@@ -245,6 +265,7 @@ pub struct CooccurrenceVectorizer {
     language: NluUtilsLanguage,
     builtin_entity_scope: Vec<BuiltinEntityKind>,
     word_pairs: HashMap<WordPair, usize>,
+    feature_names: Vec<String>,
     filter_stop_words: bool,
     window_size: Option<usize>,
     keep_order: bool,
@@ -284,6 +305,13 @@ impl CooccurrenceVectorizer {
             })
             .collect::<Result<Vec<BuiltinEntityKind>>>()?;
 
+        let mut feature_names = vec![String::new(); model.word_pairs.len()];
+        for (index, (first, second)) in model.word_pairs.iter() {
+            if let Some(name) = feature_names.get_mut(*index) {
+                *name = get_word_pair_feature_name(first, second);
+            }
+        }
+
         let word_pairs = model
             .word_pairs
             .into_iter()
@@ -303,6 +331,7 @@ impl CooccurrenceVectorizer {
             language,
             builtin_entity_scope,
             word_pairs,
+            feature_names,
             filter_stop_words,
             window_size,
             keep_order,
@@ -395,6 +424,10 @@ fn get_custom_entity_feature_name(entity_name: &str, language: NluUtilsLanguage)
     format!("entityfeature{}", e)
 }
 
+fn get_word_pair_feature_name(first: &str, second: &str) -> String {
+    format!("{} + {}", first, second)
+}
+
 fn get_word_clusters(
     query_tokens: &[String],
     word_clusterer: Arc<dyn WordClusterer>,
@@ -407,10 +440,19 @@ fn get_word_clusters(
         .collect()
 }
 
-fn normalize_stem(tokens: &[String], opt_stemmer: Option<Arc<dyn Stemmer>>) -> Vec<String> {
+fn normalize_stem(
+    tokens: &[String],
+    normalizer: &dyn Normalizer,
+    opt_stemmer: Option<Arc<dyn Stemmer>>,
+) -> Vec<String> {
     opt_stemmer
-        .map(|stemmer| tokens.iter().map(|t| stemmer.stem(&normalize(t))).collect())
-        .unwrap_or_else(|| tokens.iter().map(|t| normalize(t)).collect())
+        .map(|stemmer| {
+            tokens
+                .iter()
+                .map(|t| stemmer.stem(&normalize(normalizer, t)))
+                .collect()
+        })
+        .unwrap_or_else(|| tokens.iter().map(|t| normalize(normalizer, t)).collect())
 }
 
 #[cfg(test)]
@@ -432,9 +474,11 @@ mod tests {
         CooccurrenceVectorizerConfiguration, CooccurrenceVectorizerModel, SklearnVectorizerModel,
         TfidfVectorizerConfiguration, TfidfVectorizerModel,
     };
+    use crate::resources::normalizer::TextNormalizer;
     use crate::resources::stemmer::HashMapStemmer;
     use crate::resources::word_clusterer::HashMapWordClusterer;
     use crate::resources::SharedResources;
+    use crate::slot_filler::FeatureRegistry;
     use crate::testutils::assert_epsilon_eq_array1;
     use crate::testutils::MockedBuiltinEntityParser;
     use crate::testutils::MockedCustomEntityParser;
@@ -495,8 +539,12 @@ mod tests {
             builtin_entity_parser: Arc::new(mocked_builtin_parser),
             stemmer: Some(Arc::new(mocked_stemmer)),
             word_clusterers: HashMap::new(),
+            word_embeddings: HashMap::new(),
             gazetteers: HashMap::new(),
             stop_words: HashSet::new(),
+            feature_registry: Arc::new(FeatureRegistry::default()),
+            normalizer: Arc::new(TextNormalizer::default()),
+            custom_entities: HashMap::new(),
         };
 
         let vocab = hashmap![
@@ -666,8 +714,12 @@ mod tests {
             builtin_entity_parser: Arc::new(mocked_builtin_parser),
             stemmer: Some(Arc::new(mocked_stemmer)),
             word_clusterers: HashMap::new(),
+            word_embeddings: HashMap::new(),
             gazetteers: HashMap::new(),
             stop_words,
+            feature_registry: Arc::new(FeatureRegistry::default()),
+            normalizer: Arc::new(TextNormalizer::default()),
+            custom_entities: HashMap::new(),
         });
 
         let vocab = hashmap![
@@ -751,8 +803,30 @@ mod tests {
         // When
         let input = "hello this bird is a beautiful bird with 22 wings";
         let features = featurizer.transform(input).unwrap();
+        let feature_names = featurizer.feature_names();
 
         // Then
+        let expected_feature_names = vec![
+            "awful",
+            "beauti",
+            "bird",
+            "blue",
+            "hello",
+            "nice",
+            "world",
+            "entityfeatureanimal",
+            "entityfeatureword",
+            "entityfeaturegreeting",
+            "builtinentityfeaturesnipsnumber",
+            "ANIMAL + beautiful",
+            "hello + ANIMAL",
+            "hello + is",
+            "this + beautiful",
+            "with + SNIPSNUMBER",
+            "hello + wings",
+        ];
+        assert_eq!(expected_feature_names, feature_names);
+
         let expected_features = array![
             0.0,
             0.4022979853278378,
@@ -801,9 +875,7 @@ house	cluster_house
     #[test]
     fn test_extract_word_pairs() {
         // Given
-        let mocked_custom_parser = MockedCustomEntityParser {
-            mocked_outputs: hashmap!(),
-        };
+        let mocked_custom_parser = MockedCustomEntityParser::default();
 
         let mocked_builtin_parser = MockedBuiltinEntityParser {
             mocked_outputs: hashmap!(),
@@ -814,8 +886,12 @@ house	cluster_house
             builtin_entity_parser: Arc::new(mocked_builtin_parser),
             stemmer: None,
             word_clusterers: HashMap::new(),
+            word_embeddings: HashMap::new(),
             gazetteers: HashMap::new(),
             stop_words: hashset!(),
+            feature_registry: Arc::new(FeatureRegistry::default()),
+            normalizer: Arc::new(TextNormalizer::default()),
+            custom_entities: HashMap::new(),
         });
         let config = CooccurrenceVectorizerConfiguration {
             window_size: None,
@@ -861,9 +937,7 @@ house	cluster_house
     #[test]
     fn test_extract_word_pairs_unordered() {
         // Given
-        let mocked_custom_parser = MockedCustomEntityParser {
-            mocked_outputs: hashmap!(),
-        };
+        let mocked_custom_parser = MockedCustomEntityParser::default();
 
         let mocked_builtin_parser = MockedBuiltinEntityParser {
             mocked_outputs: hashmap!(),
@@ -874,8 +948,12 @@ house	cluster_house
             builtin_entity_parser: Arc::new(mocked_builtin_parser),
             stemmer: None,
             word_clusterers: HashMap::new(),
+            word_embeddings: HashMap::new(),
             gazetteers: HashMap::new(),
             stop_words: hashset!(),
+            feature_registry: Arc::new(FeatureRegistry::default()),
+            normalizer: Arc::new(TextNormalizer::default()),
+            custom_entities: HashMap::new(),
         });
         let config = CooccurrenceVectorizerConfiguration {
             window_size: None,
@@ -13,10 +13,23 @@ use crate::intent_classifier::{Featurizer, IntentClassifier};
 use crate::models::IntentClassifierModel;
 use crate::resources::SharedResources;
 use crate::utils::IntentName;
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 
+use super::calibration::{renormalize_results, Calibration};
 use super::logreg::MulticlassLogisticRegression;
 
+/// Contribution of a single feature to the score of an intent
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeatureContribution {
+    /// Name of the feature: a vocabulary term, an entity placeholder, a word cluster or a
+    /// word pair
+    pub feature: String,
+    /// Value of the feature for the input
+    pub value: f32,
+    /// Product of the feature value and its weight for the intent
+    pub contribution: f32,
+}
+
 pub struct LogRegIntentClassifier {
     intent_list: Vec<Option<IntentName>>,
     featurizer: Option<Featurizer>,
@@ -56,7 +69,14 @@ impl LogRegIntentClassifier {
             // Note: the deserialized coeffs matrix is transposed
             let arr_weights =
                 Array::from_shape_fn((nb_features, nb_classes), |(i, j)| coeffs[j][i]);
-            MulticlassLogisticRegression::new(arr_intercept, arr_weights).map(Some)
+            let logreg = MulticlassLogisticRegression::new(arr_intercept, arr_weights)?;
+            if let Some(calibration_config) = model.calibration {
+                logreg
+                    .with_calibration(Calibration::new(calibration_config)?)
+                    .map(Some)
+            } else {
+                Ok(Some(logreg))
+            }
         } else {
             Ok(None)
         }?;
@@ -130,7 +150,7 @@ impl LogRegIntentClassifier {
         let features = featurizer.transform(input)?;
         let scores = logreg.run(&features.view())?;
 
-        Ok(self
+        let intents_results = self
             .intent_list
             .iter()
             .zip(scores.into_iter())
@@ -148,6 +168,15 @@ impl LogRegIntentClassifier {
                     true
                 }
             })
+            .collect_vec();
+        // Normalized probabilities are distributed over the whitelisted intents only
+        let intents_results = if opt_intents_set.is_some() && logreg.is_normalized() {
+            renormalize_results(intents_results)
+        } else {
+            intents_results
+        };
+        Ok(intents_results
+            .into_iter()
             .sorted_by(|a, b| b.confidence_score.partial_cmp(&a.confidence_score).unwrap())
             .collect())
     }
@@ -160,6 +189,50 @@ impl LogRegIntentClassifier {
             .map(|featurizer| featurizer.transform(input))
             .unwrap_or_else(|| Ok(Array::from_iter(vec![])))
     }
+
+    /// Returns the `top_n` features which contribute the most to the score of the provided
+    /// intent, sorted by decreasing absolute contribution
+    ///
+    /// The `None` intent corresponds to the null intent. Features which are absent from the
+    /// input are ignored.
+    pub fn get_feature_contributions(
+        &self,
+        input: &str,
+        intent: Option<&str>,
+        top_n: usize,
+    ) -> Result<Vec<FeatureContribution>> {
+        let intent_index = self
+            .intent_list
+            .iter()
+            .position(|intent_name| intent_name.as_ref().map(|name| &**name) == intent)
+            .ok_or_else(|| anyhow!("Unknown intent {:?}", intent))?;
+
+        let (featurizer, logreg) = match (self.featurizer.as_ref(), self.logreg.as_ref()) {
+            (Some(featurizer), Some(logreg)) => (featurizer, logreg),
+            _ => return Ok(vec![]),
+        };
+
+        let features = featurizer.transform(input)?;
+        let contributions = logreg.feature_contributions(&features.view(), intent_index)?;
+        Ok(featurizer
+            .feature_names()
+            .into_iter()
+            .zip(features.iter().zip(contributions.iter()))
+            .filter(|(_, (value, _))| **value != 0.)
+            .map(|(feature, (value, contribution))| FeatureContribution {
+                feature,
+                value: *value,
+                contribution: *contribution,
+            })
+            .sorted_by(|a, b| {
+                b.contribution
+                    .abs()
+                    .partial_cmp(&a.contribution.abs())
+                    .unwrap()
+            })
+            .take(top_n)
+            .collect())
+    }
 }
 
 #[cfg(test)]
@@ -171,9 +244,11 @@ mod tests {
 
     use crate::intent_classifier::TfidfVectorizer;
     use crate::models::{
-        SklearnVectorizerModel, TfidfVectorizerConfiguration, TfidfVectorizerModel,
+        CalibrationConfiguration, ScoresNormalization, SklearnVectorizerModel,
+        TfidfVectorizerConfiguration, TfidfVectorizerModel,
     };
     use crate::resources::loading::load_engine_shared_resources;
+    use crate::testutils::epsilon_eq;
 
     fn get_sample_log_reg_classifier() -> LogRegIntentClassifier {
         let trained_engine_dir = Path::new("data")
@@ -385,6 +460,85 @@ mod tests {
         assert_eq!(expected_intents, actual_intents);
     }
 
+    #[test]
+    fn test_get_intents_with_softmax_calibration() {
+        // Given
+        let mut classifier = get_sample_log_reg_classifier();
+        let calibration = Calibration::new(CalibrationConfiguration {
+            normalization: ScoresNormalization::Softmax,
+            temperature: None,
+            platt_scaling: None,
+        })
+        .unwrap();
+        classifier.logreg = classifier
+            .logreg
+            .map(|logreg| logreg.with_calibration(calibration).unwrap());
+
+        // When
+        let intents = classifier.get_intents("Make me two cups of tea").unwrap();
+
+        // Then
+        let total_score: f32 = intents.iter().map(|res| res.confidence_score).sum();
+        assert!(epsilon_eq(1.0, total_score, 1e-06));
+        assert_eq!(Some("MakeTea".to_string()), intents[0].intent_name);
+    }
+
+    #[test]
+    fn test_get_intent_with_softmax_calibration_and_whitelist() {
+        // Given
+        let mut classifier = get_sample_log_reg_classifier();
+        let calibration = Calibration::new(CalibrationConfiguration {
+            normalization: ScoresNormalization::Softmax,
+            temperature: None,
+            platt_scaling: None,
+        })
+        .unwrap();
+        classifier.logreg = classifier
+            .logreg
+            .map(|logreg| logreg.with_calibration(calibration).unwrap());
+
+        // When
+        let intents = classifier
+            .get_intents_with_whitelist("Make me two cups of tea", Some(&["MakeCoffee"]))
+            .unwrap();
+
+        // Then
+        let total_score: f32 = intents.iter().map(|res| res.confidence_score).sum();
+        assert!(epsilon_eq(1.0, total_score, 1e-06));
+        assert_eq!(2, intents.len());
+    }
+
+    #[test]
+    fn test_get_feature_contributions() {
+        // Given
+        let classifier = get_sample_log_reg_classifier();
+
+        // When
+        let contributions = classifier
+            .get_feature_contributions("Make me two cups of tea", Some("MakeTea"), 3)
+            .unwrap();
+
+        // Then
+        let features: Vec<&str> = contributions
+            .iter()
+            .map(|contribution| &*contribution.feature)
+            .collect();
+        assert_eq!(vec!["tea", "cups", "me"], features);
+        assert!(contributions.iter().all(|c| c.contribution > 0.));
+    }
+
+    #[test]
+    fn test_get_feature_contributions_with_unknown_intent() {
+        // Given
+        let classifier = get_sample_log_reg_classifier();
+
+        // When
+        let result = classifier.get_feature_contributions("Make me tea", Some("MakePizza"), 3);
+
+        // Then
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_filter_intents() {
         // Given
@@ -1,10 +1,12 @@
 use ndarray::prelude::*;
-use ndarray::{array, stack};
+use ndarray::{array, s, stack};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
+
+use super::calibration::Calibration;
 
 /// The multiclass probability estimates are derived from binary (one-vs.-rest)
-/// estimates by simple normalization
+/// estimates by simple normalization, unless another calibration is provided
 pub struct MulticlassLogisticRegression {
     /// matrix with shape (f, c)
     /// ------------------------
@@ -12,6 +14,7 @@ pub struct MulticlassLogisticRegression {
     /// - f = number of features
     /// - c = number of classes
     weights: Array2<f32>,
+    calibration: Calibration,
 }
 
 impl MulticlassLogisticRegression {
@@ -23,10 +26,6 @@ impl MulticlassLogisticRegression {
     fn nb_classes(&self) -> usize {
         self.weights.dim().1
     }
-
-    fn is_binary(&self) -> bool {
-        self.nb_classes() == 1
-    }
 }
 
 impl MulticlassLogisticRegression {
@@ -36,27 +35,81 @@ impl MulticlassLogisticRegression {
         let weights_with_intercept = stack![Axis(0), reshaped_intercept, weights];
         Ok(Self {
             weights: weights_with_intercept,
+            calibration: Calibration::default(),
         })
     }
 
+    pub fn with_calibration(mut self, calibration: Calibration) -> Result<Self> {
+        if let Some(nb_decision_values) = calibration.nb_decision_values() {
+            if nb_decision_values != self.nb_classes() {
+                bail!(
+                    "Calibration expects {} decision values but logistic regression has {}",
+                    nb_decision_values,
+                    self.nb_classes()
+                );
+            }
+        }
+        self.calibration = calibration;
+        Ok(self)
+    }
+
+    /// Whether the probabilities returned by `run` sum to one
+    pub fn is_normalized(&self) -> bool {
+        self.calibration.is_normalized()
+    }
+
     pub fn run(&self, features: &ArrayView1<f32>) -> Result<Array1<f32>> {
+        let decision_values = self.decision_function(features)?;
+        Ok(self.calibration.calibrate(decision_values))
+    }
+
+    /// Computes the contribution of each feature to the decision value of a class
+    ///
+    /// In the binary case, the contributions to the negative class (index 0) are the opposite
+    /// of the ones of the positive class.
+    pub fn feature_contributions(
+        &self,
+        features: &ArrayView1<f32>,
+        class_index: usize,
+    ) -> Result<Array1<f32>> {
+        if features.dim() != self.nb_features() {
+            bail!(
+                "Expected {} features but found {}",
+                self.nb_features(),
+                features.dim()
+            );
+        }
+        let (column, sign) = if self.nb_classes() == 1 {
+            match class_index {
+                0 => (0, -1.),
+                1 => (0, 1.),
+                _ => bail!("Invalid class index {} for binary classifier", class_index),
+            }
+        } else if class_index < self.nb_classes() {
+            (class_index, 1.)
+        } else {
+            bail!(
+                "Invalid class index {}, number of classes is {}",
+                class_index,
+                self.nb_classes()
+            )
+        };
+        let class_weights = self.weights.slice(s![1.., column]);
+        Ok(&class_weights * features * sign)
+    }
+
+    /// Computes the raw linear scores, before any sigmoid or normalization is applied
+    ///
+    /// In the binary case, a single value is returned which corresponds to the positive class.
+    pub fn decision_function(&self, features: &ArrayView1<f32>) -> Result<Array1<f32>> {
         let reshaped_features = features.into_shape((1, self.nb_features()))?;
         let reshaped_features = stack![Axis(1), array![[1.]], reshaped_features];
-        let mut result = reshaped_features
+        Ok(reshaped_features
             .dot(&self.weights)
-            .into_shape(self.nb_classes())?;
-        result.mapv_inplace(logit);
-        if self.is_binary() {
-            return Ok(arr1(&[1.0 - result[0], result[0]]));
-        }
-        Ok(result)
+            .into_shape(self.nb_classes())?)
     }
 }
 
-fn logit(x: f32) -> f32 {
-    1. / (1. + (-x).exp())
-}
-
 #[cfg(test)]
 mod tests {
     use super::MulticlassLogisticRegression;
@@ -101,4 +154,51 @@ mod tests {
         let expected_predictions = array![0.2890504, 0.7109495];
         assert_epsilon_eq_array1(&predictions, &expected_predictions, 1e-06);
     }
+
+    #[test]
+    fn test_feature_contributions() {
+        // Given
+        let intercept = array![0.98, 0.32, -0.76];
+        let weights = array![
+            [2.5, -0.6, 0.5],
+            [1.2, 1.2, -2.7],
+            [1.5, 0.1, -3.2],
+            [-0.9, 1.4, 1.8]
+        ];
+
+        let features = array![0.4, -2.3, 1.9, 1.3];
+        let regression = MulticlassLogisticRegression::new(intercept, weights).unwrap();
+
+        // When
+        let contributions = regression
+            .feature_contributions(&features.view(), 2)
+            .unwrap();
+
+        // Then
+        let expected_contributions = array![0.2, 6.21, -6.08, 2.34];
+        assert_epsilon_eq_array1(&contributions, &expected_contributions, 1e-06);
+    }
+
+    #[test]
+    fn test_feature_contributions_when_binary() {
+        // Given
+        let intercept = array![0.98];
+        let weights = array![[2.5], [1.2], [1.5], [-0.9]];
+
+        let features = array![0.4, -2.3, 1.9, 1.3];
+        let regression = MulticlassLogisticRegression::new(intercept, weights).unwrap();
+
+        // When
+        let positive_contributions = regression
+            .feature_contributions(&features.view(), 1)
+            .unwrap();
+        let negative_contributions = regression
+            .feature_contributions(&features.view(), 0)
+            .unwrap();
+
+        // Then
+        let expected_contributions = array![1.0, -2.76, 2.85, -1.17];
+        assert_epsilon_eq_array1(&positive_contributions, &expected_contributions, 1e-06);
+        assert_epsilon_eq_array1(&negative_contributions, &-expected_contributions, 1e-06);
+    }
 }
@@ -1,3 +1,5 @@
+mod calibration;
+mod embedding_intent_classifier;
 mod featurizer;
 mod log_reg_intent_classifier;
 mod logreg;
@@ -9,8 +11,9 @@ use std::sync::Arc;
 use anyhow::{anyhow, Context, Result};
 use snips_nlu_ontology::IntentClassifierResult;
 
+pub use self::embedding_intent_classifier::EmbeddingIntentClassifier;
 pub use self::featurizer::{CooccurrenceVectorizer, Featurizer, TfidfVectorizer};
-pub use self::log_reg_intent_classifier::LogRegIntentClassifier;
+pub use self::log_reg_intent_classifier::{FeatureContribution, LogRegIntentClassifier};
 use crate::models::ProcessingUnitMetadata;
 use crate::resources::SharedResources;
 
@@ -41,6 +44,9 @@ pub fn build_intent_classifier<P: AsRef<Path>>(
         ProcessingUnitMetadata::LogRegIntentClassifier => {
             Ok(Box::new(LogRegIntentClassifier::from_path(path, shared_resources)?) as _)
         }
+        ProcessingUnitMetadata::EmbeddingIntentClassifier => Ok(Box::new(
+            EmbeddingIntentClassifier::from_path(path, shared_resources)?,
+        ) as _),
         _ => Err(anyhow!("{:?} is not an intent classifier", metadata)),
     }
 }
@@ -35,6 +35,7 @@ pub struct DeterministicIntentParser {
     stop_words: HashSet<String>,
     specific_stop_words: HashMap<IntentName, HashSet<String>>,
     entity_scopes: HashMap<IntentName, (Vec<BuiltinEntityKind>, Vec<EntityName>)>,
+    allow_nested_slots: bool,
     shared_resources: Arc<SharedResources>,
 }
 
@@ -117,6 +118,7 @@ impl DeterministicIntentParser {
             stop_words,
             specific_stop_words,
             entity_scopes,
+            allow_nested_slots: model.config.allow_nested_slots,
             shared_resources,
         })
     }
@@ -343,9 +345,14 @@ impl DeterministicIntentParser {
                     if let Some(ranges_mapping) = builtin_entities_ranges_mapping {
                         char_range =
                             ranges_mapping.get(&char_range).cloned().unwrap_or_else(|| {
-                                let shift = get_range_shift(&char_range, ranges_mapping);
-                                let range_start = (char_range.start as i32 + shift) as usize;
-                                let range_end = (char_range.end as i32 + shift) as usize;
+                                // The slot may contain entity placeholders when it is nested
+                                let start_shift = get_range_shift(&char_range, ranges_mapping);
+                                let end_shift = get_range_shift(
+                                    &(char_range.end..char_range.end),
+                                    ranges_mapping,
+                                );
+                                let range_start = (char_range.start as i32 + start_shift) as usize;
+                                let range_end = (char_range.end as i32 + end_shift) as usize;
                                 range_start..range_end
                             });
                     }
@@ -358,7 +365,11 @@ impl DeterministicIntentParser {
                     }
                 })
                 .collect();
-            let deduplicated_slots = deduplicate_overlapping_slots(slots, self.language);
+            let deduplicated_slots = if self.allow_nested_slots {
+                deduplicate_crossing_slots(slots, self.language)
+            } else {
+                deduplicate_overlapping_slots(slots, self.language)
+            };
             let result = internal_parsing_result(Some(intent.to_string()), 1.0, deduplicated_slots);
             return Some(result);
         }
@@ -400,6 +411,33 @@ fn deduplicate_overlapping_slots(
     deduped
 }
 
+/// Removes the slots which partially overlap longer slots, while keeping the nested ones
+///
+/// Among slots sharing the same range, only the first one is kept. Slots are sorted by start position and, when they start at the same position, from the
+/// outermost to the innermost.
+fn deduplicate_crossing_slots(slots: Vec<InternalSlot>, language: Language) -> Vec<InternalSlot> {
+    let language = NluUtilsLanguage::from_language(language);
+    let slots_cross = |lhs_slot: &InternalSlot, rhs_slot: &InternalSlot| {
+        // Identical ranges contain each other, hence they are checked first
+        lhs_slot.char_range == rhs_slot.char_range
+            || ranges_overlap(&lhs_slot.char_range, &rhs_slot.char_range)
+                && !range_contains(&lhs_slot.char_range, &rhs_slot.char_range)
+                && !range_contains(&rhs_slot.char_range, &lhs_slot.char_range)
+    };
+    let slot_sort_key = |slot: &InternalSlot| {
+        let tokens_count = tokenize(&slot.value, language).len();
+        let chars_count = slot.value.chars().count();
+        -((tokens_count + chars_count) as i32)
+    };
+    let mut deduped = deduplicate_overlapping_items(slots, slots_cross, slot_sort_key);
+    deduped.sort_by_key(|slot| (slot.char_range.start, -(slot.char_range.end as i64)));
+    deduped
+}
+
+fn range_contains(outer: &Range<usize>, inner: &Range<usize>) -> bool {
+    outer.start <= inner.start && inner.end <= outer.end
+}
+
 fn get_entity_placeholder(entity_label: &str) -> String {
     // Here we don't need language specific tokenization,
     // we just want to generate a feature name, that's why we use EN
@@ -498,7 +536,10 @@ mod tests {
             patterns,
             group_names_to_slot_names,
             slot_names_to_entities,
-            config: DeterministicParserConfig { ignore_stop_words },
+            config: DeterministicParserConfig {
+                ignore_stop_words,
+                allow_nested_slots: false,
+            },
             stop_words_whitelist,
         }
     }
@@ -1326,6 +1367,152 @@ mod tests {
         assert_eq!(Vec::<InternalSlot>::new(), slots_2);
     }
 
+    #[test]
+    fn test_parse_nested_slots() {
+        // Given
+        let text = "fly from the airport in paris";
+        let build_model = |allow_nested_slots: bool| {
+            let mut model = build_sample_model(
+                hashmap![
+                    "intent1" => vec![
+                        r"^\s*fly\s*from\s*(?P<group0>the\s*airport\s*in\s*(?P<group1>%CITY%))\s*$"
+                    ],
+                ],
+                hashmap!["group0" => "location", "group1" => "city"],
+                hashmap![
+                    "intent1" => hashmap!["location" => "location", "city" => "city"],
+                ],
+                false,
+                hashmap![],
+            );
+            model.config.allow_nested_slots = allow_nested_slots;
+            model
+        };
+        let mocked_custom_entity_parser = MockedCustomEntityParser::from_iter(vec![(
+            text.to_string(),
+            vec![CustomEntity {
+                value: "paris".to_string(),
+                resolved_value: "Paris".to_string(),
+                alternative_resolved_values: vec![],
+                range: 24..29,
+                entity_identifier: "city".to_string(),
+            }],
+        )]);
+        let shared_resources = Arc::new(
+            SharedResourcesBuilder::default()
+                .custom_entity_parser(mocked_custom_entity_parser)
+                .build(),
+        );
+        let flat_parser =
+            DeterministicIntentParser::new(build_model(false), shared_resources.clone()).unwrap();
+        let nested_parser =
+            DeterministicIntentParser::new(build_model(true), shared_resources).unwrap();
+
+        // When
+        let flat_slots = flat_parser.get_slots(text, "intent1").unwrap();
+        let nested_slots = nested_parser.get_slots(text, "intent1").unwrap();
+
+        // Then
+        let location_slot = InternalSlot {
+            value: "the airport in paris".to_string(),
+            char_range: 9..29,
+            entity: "location".to_string(),
+            slot_name: "location".to_string(),
+        };
+        let city_slot = InternalSlot {
+            value: "paris".to_string(),
+            char_range: 24..29,
+            entity: "city".to_string(),
+            slot_name: "city".to_string(),
+        };
+        assert_eq!(vec![location_slot.clone()], flat_slots);
+        assert_eq!(vec![location_slot, city_slot], nested_slots);
+    }
+
+    #[test]
+    fn test_deduplicate_crossing_slots() {
+        // Given
+        let language = Language::EN;
+        let slots = vec![
+            InternalSlot {
+                value: "loco".to_string(),
+                char_range: 4..8,
+                entity: "e1".to_string(),
+                slot_name: "s1".to_string(),
+            },
+            InternalSlot {
+                value: "kid loco".to_string(),
+                char_range: 0..8,
+                entity: "e1".to_string(),
+                slot_name: "s2".to_string(),
+            },
+            InternalSlot {
+                value: "loco x".to_string(),
+                char_range: 4..10,
+                entity: "e2".to_string(),
+                slot_name: "s3".to_string(),
+            },
+        ];
+
+        // When
+        let deduplicated_slots = deduplicate_crossing_slots(slots, language);
+
+        // Then
+        let expected_slots = vec![
+            InternalSlot {
+                value: "kid loco".to_string(),
+                char_range: 0..8,
+                entity: "e1".to_string(),
+                slot_name: "s2".to_string(),
+            },
+            InternalSlot {
+                value: "loco".to_string(),
+                char_range: 4..8,
+                entity: "e1".to_string(),
+                slot_name: "s1".to_string(),
+            },
+        ];
+        assert_eq!(expected_slots, deduplicated_slots);
+    }
+
+    #[test]
+    fn test_deduplicate_crossing_slots_with_identical_ranges() {
+        // Given
+        let language = Language::EN;
+        let slots = vec![
+            InternalSlot {
+                value: "kid loco".to_string(),
+                char_range: 0..8,
+                entity: "e1".to_string(),
+                slot_name: "s1".to_string(),
+            },
+            InternalSlot {
+                value: "kid loco".to_string(),
+                char_range: 0..8,
+                entity: "e2".to_string(),
+                slot_name: "s2".to_string(),
+            },
+            InternalSlot {
+                value: "kid loco".to_string(),
+                char_range: 0..8,
+                entity: "e1".to_string(),
+                slot_name: "s1".to_string(),
+            },
+        ];
+
+        // When
+        let deduplicated_slots = deduplicate_crossing_slots(slots, language);
+
+        // Then
+        let expected_slots = vec![InternalSlot {
+            value: "kid loco".to_string(),
+            char_range: 0..8,
+            entity: "e1".to_string(),
+            slot_name: "s1".to_string(),
+        }];
+        assert_eq!(expected_slots, deduplicated_slots);
+    }
+
     #[test]
     fn test_deduplicate_overlapping_slots() {
         // Given
@@ -12,7 +12,8 @@ pub use self::lookup_intent_parser::LookupIntentParser;
 pub use self::probabilistic_intent_parser::ProbabilisticIntentParser;
 use crate::models::ProcessingUnitMetadata;
 use crate::resources::SharedResources;
-pub use crate::slot_utils::InternalSlot;
+use crate::slot_filler::TokenTagging;
+pub use crate::slot_utils::{InternalSlot, InternalSlotSequence};
 use crate::utils::IntentName;
 use anyhow::{anyhow, Result};
 
@@ -58,6 +59,32 @@ pub trait IntentParser: Send + Sync {
     fn get_intents(&self, input: &str) -> Result<Vec<IntentClassifierResult>>;
 
     fn get_slots(&self, input: &str, intent: &str) -> Result<Vec<InternalSlot>>;
+
+    /// Returns up to `n` alternative slot segmentations, sorted by decreasing probability
+    ///
+    /// Parsers which are not probabilistic return a single segmentation, see
+    /// `SlotFiller::get_slots_nbest` for the approximation made by the probabilistic ones.
+    fn get_slots_nbest(
+        &self,
+        input: &str,
+        intent: &str,
+        n: usize,
+    ) -> Result<Vec<InternalSlotSequence>> {
+        if n == 0 {
+            return Ok(vec![]);
+        }
+        Ok(vec![InternalSlotSequence {
+            slots: self.get_slots(input, intent)?,
+            probability: 1.0,
+        }])
+    }
+
+    /// Returns the tags predicted for each token of the input by the slot filler of the intent
+    ///
+    /// Parsers which do not rely on a slot filler return `None`.
+    fn tag_tokens(&self, _input: &str, _intent: &str) -> Result<Option<TokenTagging>> {
+        Ok(None)
+    }
 }
 
 pub fn build_intent_parser<P: AsRef<Path>>(
@@ -1,24 +1,27 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::iter::FromIterator;
 use std::path::Path;
 use std::sync::Arc;
 
+use itertools::Itertools;
+
 use crate::errors::SnipsNluError;
 
 use crate::intent_classifier::{build_intent_classifier, IntentClassifier};
-use crate::models::ProbabilisticParserModel;
+use crate::models::{JointRerankingConfiguration, ProbabilisticParserModel};
 use crate::resources::SharedResources;
-use crate::slot_filler::{build_slot_filler, SlotFiller};
+use crate::slot_filler::{build_slot_filler, with_feature_cache, SlotFiller, TokenTagging};
 use crate::utils::IntentName;
 use anyhow::{anyhow, Context, Result};
 
-use super::{IntentClassifierResult, IntentParser, InternalParsingResult};
-use crate::slot_utils::InternalSlot;
+use super::{internal_parsing_result, IntentClassifierResult, IntentParser, InternalParsingResult};
+use crate::slot_utils::{InternalSlot, InternalSlotSequence};
 
 pub struct ProbabilisticIntentParser {
     intent_classifier: Box<dyn IntentClassifier>,
     slot_fillers: HashMap<IntentName, Box<dyn SlotFiller>>,
+    joint_reranking: Option<JointRerankingConfiguration>,
 }
 
 impl ProbabilisticIntentParser {
@@ -53,8 +56,16 @@ impl ProbabilisticIntentParser {
         Ok(Self {
             intent_classifier,
             slot_fillers,
+            joint_reranking: model.joint_reranking,
         })
     }
+
+    /// Re-ranks the top intents of the classifier by combining their score with the
+    /// probability of their decoded slots
+    pub fn with_joint_reranking(mut self, config: JointRerankingConfiguration) -> Self {
+        self.joint_reranking = Some(config);
+        self
+    }
 }
 
 impl IntentParser for ProbabilisticIntentParser {
@@ -63,6 +74,51 @@ impl IntentParser for ProbabilisticIntentParser {
         input: &str,
         intents_whitelist: Option<&[&str]>,
     ) -> Result<InternalParsingResult> {
+        // Slot fillers share their computed features, as most of them do not depend on the intent
+        with_feature_cache(|| self.parse_with_feature_cache(input, intents_whitelist))
+    }
+
+    fn get_intents(&self, input: &str) -> Result<Vec<IntentClassifierResult>> {
+        self.intent_classifier.get_intents(input)
+    }
+
+    fn get_slots(&self, input: &str, intent: &str) -> Result<Vec<InternalSlot>> {
+        self.slot_fillers
+            .get(intent)
+            .ok_or_else(|| anyhow!("Unknown intent: {}", intent))
+            .and_then(|slot_filler| slot_filler.get_slots(input))
+    }
+
+    fn get_slots_nbest(
+        &self,
+        input: &str,
+        intent: &str,
+        n: usize,
+    ) -> Result<Vec<InternalSlotSequence>> {
+        self.slot_fillers
+            .get(intent)
+            .ok_or_else(|| anyhow!("Unknown intent: {}", intent))
+            .and_then(|slot_filler| slot_filler.get_slots_nbest(input, n))
+    }
+
+    fn tag_tokens(&self, input: &str, intent: &str) -> Result<Option<TokenTagging>> {
+        self.slot_fillers
+            .get(intent)
+            .ok_or_else(|| anyhow!("Unknown intent: {}", intent))
+            .and_then(|slot_filler| slot_filler.tag_tokens(input))
+            .map(Some)
+    }
+}
+
+impl ProbabilisticIntentParser {
+    fn parse_with_feature_cache(
+        &self,
+        input: &str,
+        intents_whitelist: Option<&[&str]>,
+    ) -> Result<InternalParsingResult> {
+        if let Some(config) = self.joint_reranking.as_ref() {
+            return self.parse_with_joint_reranking(input, intents_whitelist, config);
+        }
         let intent_result = self
             .intent_classifier
             .get_intent(input, intents_whitelist)?;
@@ -80,22 +136,102 @@ impl IntentParser for ProbabilisticIntentParser {
         })
     }
 
-    fn get_intents(&self, input: &str) -> Result<Vec<IntentClassifierResult>> {
-        self.intent_classifier.get_intents(input)
-    }
+    fn parse_with_joint_reranking(
+        &self,
+        input: &str,
+        intents_whitelist: Option<&[&str]>,
+        config: &JointRerankingConfiguration,
+    ) -> Result<InternalParsingResult> {
+        let opt_intents_set: Option<HashSet<&str>> =
+            intents_whitelist.map(|intents| intents.iter().cloned().collect());
+        let slots_weight = config.slots_weight.unwrap_or(1.0) as f64;
+        let candidates = self
+            .intent_classifier
+            .get_intents(input)?
+            .into_iter()
+            .filter(
+                |res| match (res.intent_name.as_ref(), opt_intents_set.as_ref()) {
+                    (Some(intent), Some(intents_set)) => intents_set.contains(&**intent),
+                    _ => true,
+                },
+            )
+            .sorted_by(|a, b| b.confidence_score.partial_cmp(&a.confidence_score).unwrap())
+            .take(config.top_k)
+            .map(|intent_result| {
+                // The None intent and the intents without slots have no slots probability,
+                // hence they are not re-ranked and keep their classifier score
+                let slot_sequence = match intent_result.intent_name.as_ref() {
+                    Some(name) => {
+                        let slot_filler = self
+                            .slot_fillers
+                            .get(name)
+                            .ok_or_else(|| SnipsNluError::UnknownIntent(name.to_string()))?;
+                        if slot_filler.has_slots() {
+                            Some(slot_filler.get_slots_with_probability(input)?)
+                        } else {
+                            None
+                        }
+                    }
+                    None => None,
+                };
+                let (slots, joint_score) = match slot_sequence {
+                    Some(slot_sequence) => (
+                        slot_sequence.slots,
+                        Some(
+                            intent_result.confidence_score as f64
+                                * slot_sequence.probability.powf(slots_weight),
+                        ),
+                    ),
+                    None => (vec![], None),
+                };
+                Ok((intent_result, slots, joint_score))
+            })
+            .collect::<Result<Vec<_>>>()?;
 
-    fn get_slots(&self, input: &str, intent: &str) -> Result<Vec<InternalSlot>> {
-        self.slot_fillers
-            .get(intent)
-            .ok_or_else(|| anyhow!("Unknown intent: {}", intent))
-            .and_then(|slot_filler| slot_filler.get_slots(input))
+        // The re-ranked intents share the probability mass given by the classifier
+        let classifier_mass: f32 = candidates
+            .iter()
+            .filter(|(_, _, joint_score)| joint_score.is_some())
+            .map(|(intent_result, _, _)| intent_result.confidence_score)
+            .sum();
+        let joint_mass: f64 = candidates
+            .iter()
+            .filter_map(|(_, _, joint_score)| *joint_score)
+            .sum();
+        let scored_candidates = candidates
+            .into_iter()
+            .map(|(intent_result, slots, joint_score)| {
+                let confidence_score = match joint_score {
+                    Some(joint_score) if joint_mass > 0.0 => {
+                        (joint_score / joint_mass) as f32 * classifier_mass
+                    }
+                    _ => intent_result.confidence_score,
+                };
+                (intent_result.intent_name, confidence_score, slots)
+            })
+            .collect_vec();
+        // The sort is stable, hence ties are broken using the classifier ranking
+        let opt_best_candidate = scored_candidates
+            .into_iter()
+            .sorted_by(|(_, score_a, _), (_, score_b, _)| score_b.partial_cmp(score_a).unwrap())
+            .next();
+        Ok(match opt_best_candidate {
+            Some((intent_name, confidence_score, slots)) => {
+                internal_parsing_result(intent_name, confidence_score, slots)
+            }
+            None => InternalParsingResult::empty(),
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use snips_nlu_utils::token::Token;
+
     use super::*;
+    use crate::models::ProcessingUnitMetadata;
     use crate::resources::loading::load_engine_shared_resources;
+    use crate::slot_filler::TaggingScheme;
     use crate::slot_utils::InternalSlot;
 
     #[test]
@@ -159,4 +295,201 @@ mod tests {
         ];
         assert_eq!(expected_slots, slots);
     }
+
+    struct MockedIntentClassifier {
+        intents: Vec<IntentClassifierResult>,
+    }
+
+    impl IntentClassifier for MockedIntentClassifier {
+        fn get_intent(
+            &self,
+            _input: &str,
+            _intents_whitelist: Option<&[&str]>,
+        ) -> Result<IntentClassifierResult> {
+            Ok(self.intents[0].clone())
+        }
+
+        fn get_intents(&self, _input: &str) -> Result<Vec<IntentClassifierResult>> {
+            Ok(self.intents.clone())
+        }
+    }
+
+    struct MockedSlotFiller {
+        slots: Vec<InternalSlot>,
+        probability: f64,
+    }
+
+    impl SlotFiller for MockedSlotFiller {
+        fn get_tagging_scheme(&self) -> TaggingScheme {
+            TaggingScheme::BIO
+        }
+
+        fn has_slots(&self) -> bool {
+            true
+        }
+
+        fn get_slots(&self, _text: &str) -> Result<Vec<InternalSlot>> {
+            Ok(self.slots.clone())
+        }
+
+        fn get_slots_nbest(&self, text: &str, _n: usize) -> Result<Vec<InternalSlotSequence>> {
+            Ok(vec![self.get_slots_with_probability(text)?])
+        }
+
+        fn get_sequence_probability(&self, _tokens: &[Token], _tags: Vec<String>) -> Result<f64> {
+            Ok(self.probability)
+        }
+
+        fn get_slots_with_probability(&self, _text: &str) -> Result<InternalSlotSequence> {
+            Ok(InternalSlotSequence {
+                slots: self.slots.clone(),
+                probability: self.probability,
+            })
+        }
+
+        fn tag_tokens(&self, _text: &str) -> Result<TokenTagging> {
+            Ok(TokenTagging {
+                slot_filler: ProcessingUnitMetadata::CrfSlotFiller,
+                tagging_scheme: TaggingScheme::BIO,
+                tokens: vec![],
+            })
+        }
+    }
+
+    fn get_podcast_parser() -> ProbabilisticIntentParser {
+        let intent_classifier = MockedIntentClassifier {
+            intents: vec![
+                IntentClassifierResult {
+                    intent_name: Some("PlayMusic".to_string()),
+                    confidence_score: 0.5,
+                },
+                IntentClassifierResult {
+                    intent_name: Some("PlayPodcast".to_string()),
+                    confidence_score: 0.4,
+                },
+                IntentClassifierResult {
+                    intent_name: None,
+                    confidence_score: 0.1,
+                },
+            ],
+        };
+        let music_slot_filler = MockedSlotFiller {
+            slots: vec![],
+            probability: 0.5,
+        };
+        let podcast_slot_filler = MockedSlotFiller {
+            slots: vec![InternalSlot {
+                value: "the daily".to_string(),
+                char_range: 5..14,
+                entity: "podcast".to_string(),
+                slot_name: "podcast_name".to_string(),
+            }],
+            probability: 0.9,
+        };
+        ProbabilisticIntentParser {
+            intent_classifier: Box::new(intent_classifier),
+            slot_fillers: vec![
+                (
+                    "PlayMusic".to_string(),
+                    Box::new(music_slot_filler) as Box<dyn SlotFiller>,
+                ),
+                (
+                    "PlayPodcast".to_string(),
+                    Box::new(podcast_slot_filler) as Box<dyn SlotFiller>,
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            joint_reranking: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_with_joint_reranking() {
+        // Given
+        let intent_parser =
+            get_podcast_parser().with_joint_reranking(JointRerankingConfiguration {
+                top_k: 2,
+                slots_weight: None,
+            });
+
+        // When
+        let parsing_result = intent_parser.parse("play the daily", None).unwrap();
+
+        // Then
+        assert_eq!(
+            Some("PlayPodcast".to_string()),
+            parsing_result.intent.intent_name
+        );
+        assert!((parsing_result.intent.confidence_score - 0.36 / 0.61 * 0.9).abs() < 1e-6);
+        assert_eq!(1, parsing_result.slots.len());
+    }
+
+    #[test]
+    fn test_parse_with_joint_reranking_should_not_rerank_none_intent() {
+        // Given
+        let mut intent_parser =
+            get_podcast_parser().with_joint_reranking(JointRerankingConfiguration {
+                top_k: 2,
+                slots_weight: None,
+            });
+        intent_parser.intent_classifier = Box::new(MockedIntentClassifier {
+            intents: vec![
+                IntentClassifierResult {
+                    intent_name: Some("PlayMusic".to_string()),
+                    confidence_score: 0.55,
+                },
+                IntentClassifierResult {
+                    intent_name: None,
+                    confidence_score: 0.45,
+                },
+            ],
+        });
+
+        // When
+        let parsing_result = intent_parser.parse("play the daily", None).unwrap();
+
+        // Then
+        assert_eq!(
+            Some("PlayMusic".to_string()),
+            parsing_result.intent.intent_name
+        );
+        assert!((parsing_result.intent.confidence_score - 0.55).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_parse_with_joint_reranking_and_whitelist() {
+        // Given
+        let intent_parser =
+            get_podcast_parser().with_joint_reranking(JointRerankingConfiguration {
+                top_k: 2,
+                slots_weight: None,
+            });
+
+        // When
+        let parsing_result = intent_parser
+            .parse("play the daily", Some(&["PlayMusic"]))
+            .unwrap();
+
+        // Then
+        assert_eq!(
+            Some("PlayMusic".to_string()),
+            parsing_result.intent.intent_name
+        );
+    }
+
+    #[test]
+    fn test_parse_without_joint_reranking() {
+        // Given
+        let intent_parser = get_podcast_parser();
+
+        // When
+        let parsing_result = intent_parser.parse("play the daily", None).unwrap();
+
+        // Then
+        assert_eq!(
+            Some("PlayMusic".to_string()),
+            parsing_result.intent.intent_name
+        );
+    }
 }
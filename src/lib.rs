@@ -4,6 +4,7 @@
     clippy::module_inception
 )]
 
+mod dialogue_context;
 mod entity_parser;
 pub mod errors;
 pub mod injection;
@@ -14,6 +15,7 @@ pub mod models;
 mod nlu_engine;
 mod resources;
 mod slot_filler;
+mod slot_schema;
 mod slot_utils;
 #[cfg(test)]
 mod testutils;
@@ -22,14 +24,30 @@ mod utils;
 pub const MODEL_VERSION: &str = "0.20.0";
 
 pub extern crate snips_nlu_ontology as ontology;
-pub use crate::intent_classifier::{IntentClassifier, LogRegIntentClassifier};
+pub use crate::dialogue_context::{
+    ContextualParserResult, ContextualSlot, DialogueContext, SlotOrigin,
+};
+pub use crate::entity_parser::EphemeralEntityValue;
+pub use crate::intent_classifier::{
+    EmbeddingIntentClassifier, FeatureContribution, IntentClassifier, LogRegIntentClassifier,
+};
 pub use crate::intent_parser::{
     DeterministicIntentParser, IntentParser, LookupIntentParser, ProbabilisticIntentParser,
 };
 pub use crate::models::*;
-pub use crate::nlu_engine::SnipsNluEngine;
-pub use crate::resources::loading::load_shared_resources;
+pub use crate::nlu_engine::{
+    MultiIntentParserResult, NestedParserResult, NestedSlot, SlotSequence, SnipsNluEngine,
+};
+pub use crate::resources::loading::{
+    load_shared_resources, load_shared_resources_with_feature_registry,
+    load_shared_resources_with_normalization,
+};
+pub use crate::resources::normalizer::{Normalizer, TextNormalizer};
 pub use crate::resources::SharedResources;
-pub use crate::slot_filler::{CRFSlotFiller, SlotFiller};
+pub use crate::slot_filler::{
+    CRFSlotFiller, EntityMatchSlotFiller, Feature, FeatureRegistry, SlotFiller, TaggedToken,
+    TaggingScheme, TokenFeature, TokenTagging,
+};
+pub use crate::slot_schema::{SchemaParserResult, SlotReport};
 pub use anyhow::{Context, Result};
 pub use snips_nlu_ontology::Language;
@@ -10,6 +10,47 @@ pub struct IntentClassifierModel {
     pub intercept: Option<Vec<f32>>,
     pub coeffs: Option<Vec<Vec<f32>>>,
     pub intent_list: Vec<Option<IntentName>>,
+    #[serde(default)]
+    pub calibration: Option<CalibrationConfiguration>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EmbeddingIntentClassifierModel {
+    pub language_code: String,
+    pub word_embeddings_name: String,
+    pub intent_list: Vec<Option<IntentName>>,
+    pub intercept: Vec<f32>,
+    pub coeffs: Vec<Vec<f32>>,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct CalibrationConfiguration {
+    #[serde(default)]
+    pub normalization: ScoresNormalization,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    #[serde(default)]
+    pub platt_scaling: Option<PlattScalingConfiguration>,
+}
+
+#[derive(Debug, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScoresNormalization {
+    OneVsRest,
+    Renormalization,
+    Softmax,
+}
+
+impl Default for ScoresNormalization {
+    fn default() -> Self {
+        ScoresNormalization::OneVsRest
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct PlattScalingConfiguration {
+    pub slopes: Vec<f32>,
+    pub intercepts: Vec<f32>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -46,6 +46,9 @@ pub struct EntityScope {
 pub struct DeterministicParserConfig {
     #[serde(default)]
     pub ignore_stop_words: bool,
+    /// Keep slots which are contained in other slots instead of only keeping the outermost ones
+    #[serde(default)]
+    pub allow_nested_slots: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -57,6 +60,21 @@ pub struct LookupParserConfig {
 #[derive(Debug, Deserialize)]
 pub struct ProbabilisticParserModel {
     pub slot_fillers: Vec<SlotFillerMetadata>,
+    #[serde(default)]
+    pub joint_reranking: Option<JointRerankingConfiguration>,
+}
+
+/// Re-ranks the top intents of the classifier using the probability of their decoded slots
+///
+/// The None intent and the intents without slots are not re-ranked and keep their classifier
+/// score.
+#[derive(Debug, Deserialize, Copy, Clone, PartialEq)]
+pub struct JointRerankingConfiguration {
+    /// Number of most probable intents which are re-ranked
+    pub top_k: usize,
+    /// Exponent applied to the slots probability in the joint score, which defaults to 1
+    #[serde(default)]
+    pub slots_weight: Option<f32>,
 }
 
 #[derive(Debug, Deserialize)]
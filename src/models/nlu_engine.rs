@@ -17,6 +17,8 @@ pub struct NluEngineModel {
     pub training_package_version: String,
     pub builtin_entity_parser: String,
     pub custom_entity_parser: String,
+    #[serde(default)]
+    pub normalization: NormalizationConfig,
 }
 
 #[derive(Debug, Deserialize)]
@@ -30,3 +32,40 @@ pub struct DatasetMetadata {
 pub struct Entity {
     pub automatically_extensible: bool,
 }
+
+/// Normalizations applied, in addition to lowercasing, to the texts in which entities are
+/// matched and to the trained and injected entity values
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct NormalizationConfig {
+    /// Unicode compatibility normalization (NFKC), which replaces full-width characters and
+    /// ligatures by their standard form
+    #[serde(default)]
+    pub unicode_nfkc: bool,
+    /// Removal of the diacritics, such as accents
+    #[serde(default)]
+    pub fold_accents: bool,
+    /// Replacement of the typographic apostrophes, quotes, dashes and spaces by their ASCII form
+    #[serde(default)]
+    pub fold_punctuation: bool,
+}
+
+/// Optional description of the slots expected by each intent
+///
+/// It is read from the `intent_schema.json` file of the engine directory, when present.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct IntentSchema {
+    pub intents: HashMap<IntentName, IntentSlotsSchema>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct IntentSlotsSchema {
+    /// Slots which must be filled for the intent to be complete
+    #[serde(default)]
+    pub required_slots: Vec<SlotName>,
+    /// Slots which may be filled several times, the other slots are expected at most once
+    #[serde(default)]
+    pub repeatable_slots: Vec<SlotName>,
+    /// Groups of slots among which at most one slot can be filled
+    #[serde(default)]
+    pub mutually_exclusive_slots: Vec<Vec<SlotName>>,
+}
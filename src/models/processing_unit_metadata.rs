@@ -8,7 +8,9 @@ pub enum ProcessingUnitMetadata {
     LookupIntentParser,
     ProbabilisticIntentParser,
     CrfSlotFiller,
+    EntityMatchSlotFiller,
     LogRegIntentClassifier,
+    EmbeddingIntentClassifier,
 }
 
 #[cfg(test)]
@@ -17,6 +17,41 @@ pub struct SlotFillerModel {
 pub struct SlotFillerConfiguration {
     pub tagging_scheme: u8,
     pub feature_factory_configs: Vec<FeatureFactory>,
+    /// Restrict the slots of builtin entities and of the custom entities which are not
+    /// automatically extensible to exact matches of their entity in the input
+    #[serde(default)]
+    pub constrained_decoding: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EntityMatchSlotFillerModel {
+    pub language_code: String,
+    pub intent: IntentName,
+    pub slot_name_mapping: HashMap<SlotName, EntityName>,
+    #[serde(default)]
+    pub config: EntityMatchSlotFillerConfiguration,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct EntityMatchSlotFillerConfiguration {
+    #[serde(default)]
+    pub overlap_strategy: EntityOverlapStrategy,
+}
+
+/// Strategy used to choose between overlapping entity matches
+#[derive(Debug, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityOverlapStrategy {
+    LongestMatch,
+    LeftmostMatch,
+    CustomEntitiesFirst,
+    BuiltinEntitiesFirst,
+}
+
+impl Default for EntityOverlapStrategy {
+    fn default() -> Self {
+        EntityOverlapStrategy::LongestMatch
+    }
 }
 
 #[derive(Debug, Deserialize)]
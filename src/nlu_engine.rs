@@ -1,37 +1,108 @@
 use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::io;
-use std::path::Path;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
 
 use itertools::Itertools;
 use snips_nlu_ontology::{
     BuiltinEntityKind, IntentClassifierResult, IntentParserResult, Language, Slot, SlotValue,
 };
+use snips_nlu_utils::language::Language as NluUtilsLanguage;
+use snips_nlu_utils::range::ranges_overlap;
 use snips_nlu_utils::string::substring_with_char_range;
+use snips_nlu_utils::token::tokenize_light;
 
-use crate::entity_parser::{BuiltinEntityParser, CustomEntityParser};
+use crate::dialogue_context::{
+    apply_intent_boosts, merge_slots, ContextualParserResult, DialogueContext, SlotOrigin,
+};
+use crate::entity_parser::{
+    with_ephemeral_entity_values, BuiltinEntityParser, CustomEntityParser, CustomEntityParserUsage,
+    EphemeralEntityForms, EphemeralEntityValue,
+};
 use crate::errors::SnipsNluError;
+use crate::injection::{recover_interrupted_injection, NluInjector};
 use crate::intent_parser::*;
+use crate::language::FromLanguage;
 use crate::models::{
-    DatasetMetadata, Entity, ModelVersion, NluEngineModel, ProcessingUnitMetadata,
+    DatasetMetadata, Entity, IntentSchema, ModelVersion, NluEngineModel, ProcessingUnitMetadata,
 };
 use crate::ontology::IntentParserAlternative;
-use crate::resources::loading::load_shared_resources;
+use crate::resources::loading::load_shared_resources_with_normalization;
 use crate::resources::SharedResources;
+use crate::slot_filler::{
+    with_feature_cache, with_new_feature_cache, FeatureRegistry, TokenTagging,
+};
+use crate::slot_schema::{check_slots, validate_intent_schema, SchemaParserResult, SlotReport};
 use crate::slot_utils::*;
-use crate::utils::{extract_nlu_engine_zip_archive, EntityName, IterOps, SlotName};
+use crate::utils::{
+    copy_directory, extract_nlu_engine_zip_archive, split_on_conjunctions, EntityName, IntentName,
+    IterOps, SlotName,
+};
 use anyhow::{anyhow, bail, Context, Result};
 
+/// Parsing result of one of the intents found in a multi-intent input
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultiIntentParserResult {
+    pub intent: IntentClassifierResult,
+    /// Character range of the part of the input which is attributed to the intent
+    pub range: Range<usize>,
+    pub slots: Vec<Slot>,
+}
+
+/// Alternative slot segmentation of an input, along with its probability
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlotSequence {
+    pub slots: Vec<Slot>,
+    pub probability: f64,
+}
+
+/// Slot along with a reference to the innermost slot which contains it
+#[derive(Debug, Clone, PartialEq)]
+pub struct NestedSlot {
+    pub slot: Slot,
+    /// Index of the parent slot in the list of slots, if any
+    pub parent: Option<usize>,
+}
+
+/// Parsing result in which slots may be nested in other slots
+#[derive(Debug, Clone, PartialEq)]
+pub struct NestedParserResult {
+    pub input: String,
+    pub intent: IntentClassifierResult,
+    pub slots: Vec<NestedSlot>,
+}
+
 pub struct SnipsNluEngine {
     dataset_metadata: DatasetMetadata,
     intent_parsers: Vec<Box<dyn IntentParser>>,
+    intent_schema: Option<IntentSchema>,
     shared_resources: Arc<SharedResources>,
+    engine_dir: PathBuf,
+    /// Directory of the engine archive, which is kept as long as the engine is used
+    archive_dir: Option<tempfile::TempDir>,
+    /// Entity parsers updated by injections which have not been persisted
+    injection_dir: Mutex<Option<tempfile::TempDir>>,
+    /// Held for reading while parsing and for writing while injections swap the entity
+    /// parsers, so that a parse never mixes parsers of different injections
+    entity_parsers_lock: RwLock<()>,
 }
 
 impl SnipsNluEngine {
     pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::from_path_with_feature_registry(path, FeatureRegistry::default())
+    }
+
+    /// Loads an engine whose CRF slot fillers resolve their feature factories in the provided
+    /// registry, which allows to run models trained with additional features
+    pub fn from_path_with_feature_registry<P: AsRef<Path>>(
+        path: P,
+        feature_registry: FeatureRegistry,
+    ) -> Result<Self> {
+        // An injection interrupted by a crash leaves the entity parsers partially replaced
+        recover_interrupted_injection(path.as_ref())?;
         let model = SnipsNluEngine::load_model(&path)?;
 
         let language =
@@ -41,18 +112,37 @@ impl SnipsNluEngine {
         let builtin_parser_path = path.as_ref().join(&model.builtin_entity_parser);
         let custom_parser_path = path.as_ref().join(&model.custom_entity_parser);
 
-        let shared_resources =
-            load_shared_resources(&resources_path, builtin_parser_path, custom_parser_path)?;
+        let shared_resources = load_shared_resources_with_normalization(
+            &resources_path,
+            builtin_parser_path,
+            custom_parser_path,
+            feature_registry,
+            model.normalization.clone(),
+            model.dataset_metadata.entities.clone(),
+        )?;
 
-        let parsers = Self::load_intent_parsers(path, &model, shared_resources.clone())?;
+        let intent_schema = Self::load_intent_schema(&path, &model)?;
+        let parsers = Self::load_intent_parsers(&path, &model, shared_resources.clone())?;
 
         Ok(SnipsNluEngine {
             dataset_metadata: model.dataset_metadata,
             intent_parsers: parsers,
+            intent_schema,
             shared_resources,
+            engine_dir: path.as_ref().to_path_buf(),
+            archive_dir: None,
+            injection_dir: Mutex::new(None),
+            entity_parsers_lock: RwLock::new(()),
         })
     }
 
+    /// Sets the schema against which the slots of the parsing results are checked
+    pub fn with_intent_schema(mut self, intent_schema: IntentSchema) -> Result<Self> {
+        validate_intent_schema(&intent_schema, &self.dataset_metadata.slot_name_mappings)?;
+        self.intent_schema = Some(intent_schema);
+        Ok(self)
+    }
+
     fn check_model_version<P: AsRef<Path>>(path: P) -> Result<()> {
         let model_file = fs::File::open(&path)?;
 
@@ -78,6 +168,23 @@ impl SnipsNluEngine {
         Ok(model)
     }
 
+    fn load_intent_schema<P: AsRef<Path>>(
+        path: P,
+        model: &NluEngineModel,
+    ) -> Result<Option<IntentSchema>> {
+        let schema_path = path.as_ref().join("intent_schema.json");
+        if !schema_path.exists() {
+            return Ok(None);
+        }
+        let schema_file = fs::File::open(&schema_path)
+            .with_context(|| format!("Could not open intent schema file {:?}", &schema_path))?;
+        let intent_schema: IntentSchema = serde_json::from_reader(schema_file)
+            .with_context(|| format!("Invalid intent schema file {:?}", &schema_path))?;
+        validate_intent_schema(&intent_schema, &model.dataset_metadata.slot_name_mappings)
+            .with_context(|| format!("Invalid intent schema file {:?}", &schema_path))?;
+        Ok(Some(intent_schema))
+    }
+
     fn load_intent_parsers<P: AsRef<Path>>(
         engine_dir: P,
         model: &NluEngineModel,
@@ -112,12 +219,18 @@ impl SnipsNluEngine {
         shared_resources: Arc<SharedResources>,
     ) -> Result<Self> {
         let model = SnipsNluEngine::load_model(&path)?;
-        let parsers = Self::load_intent_parsers(path, &model, shared_resources.clone())?;
+        let intent_schema = Self::load_intent_schema(&path, &model)?;
+        let parsers = Self::load_intent_parsers(&path, &model, shared_resources.clone())?;
 
         Ok(SnipsNluEngine {
             dataset_metadata: model.dataset_metadata,
             intent_parsers: parsers,
+            intent_schema,
             shared_resources,
+            engine_dir: path.as_ref().to_path_buf(),
+            archive_dir: None,
+            injection_dir: Mutex::new(None),
+            entity_parsers_lock: RwLock::new(()),
         })
     }
 }
@@ -127,7 +240,115 @@ impl SnipsNluEngine {
         let temp_dir = tempfile::Builder::new().prefix("temp_dir_nlu_").tempdir()?;
         let temp_dir_path = temp_dir.path();
         let engine_dir_path = extract_nlu_engine_zip_archive(reader, temp_dir_path)?;
-        Ok(SnipsNluEngine::from_path(engine_dir_path)?)
+        let mut nlu_engine = SnipsNluEngine::from_path(engine_dir_path)?;
+        nlu_engine.archive_dir = Some(temp_dir);
+        Ok(nlu_engine)
+    }
+}
+
+impl SnipsNluEngine {
+    /// Injects entity values in the loaded engine without reloading it
+    ///
+    /// The injector, which is provided by `configure_injector` with the values to add, remove or
+    /// replace, updates the entity parsers which are then swapped in the engine. Intent parsers
+    /// and resources are not reloaded.
+    ///
+    /// When `persist` is false, the injection is performed in a copy of the entity parsers which
+    /// is kept as long as the engine, and only the loaded engine is affected. Otherwise, the
+    /// injection is also performed in the engine directory. Values injected previously without
+    /// being persisted are kept in the loaded engine, but they are not written to the engine
+    /// directory.
+    pub fn inject_values<F>(&self, configure_injector: F, persist: bool) -> Result<()>
+    where
+        F: Fn(NluInjector<PathBuf>) -> NluInjector<PathBuf>,
+    {
+        let mut injection_dir = self
+            .injection_dir
+            .lock()
+            .map_err(|e| anyhow!("Poisonous mutex: {}", e))?;
+        let model = Self::load_model(&self.engine_dir)?;
+        let inject = |target_dir: &Path| {
+            let injector = NluInjector::new(target_dir.to_path_buf())
+                .shared_resources(self.shared_resources.clone());
+            configure_injector(injector)
+                .inject()
+                .with_context(|| "Cannot inject entity values".to_string())
+        };
+        if persist {
+            inject(&self.engine_dir)?;
+        }
+        let parsers_dir = match injection_dir.as_ref() {
+            None if persist => self.engine_dir.clone(),
+            _ => {
+                if injection_dir.is_none() {
+                    *injection_dir = Some(self.copy_entity_parsers(&model)?);
+                }
+                let parsers_dir = injection_dir.as_ref().unwrap().path().to_path_buf();
+                inject(&parsers_dir)?;
+                parsers_dir
+            }
+        };
+
+        // Both parsers are loaded before being swapped, so that a failure leaves them untouched
+        let swap_builtin_parser = self
+            .shared_resources
+            .builtin_entity_parser
+            .prepare_reload(&parsers_dir.join(&model.builtin_entity_parser))?;
+        let swap_custom_parser = self
+            .shared_resources
+            .custom_entity_parser
+            .prepare_reload(&parsers_dir.join(&model.custom_entity_parser))?;
+        let _entity_parsers_guard = self
+            .entity_parsers_lock
+            .write()
+            .map_err(|e| anyhow!("Poisonous lock: {}", e))?;
+        swap_builtin_parser();
+        swap_custom_parser();
+        Ok(())
+    }
+
+    /// Prevents the entity parsers from being swapped by an injection until the guard is dropped
+    ///
+    /// The lock must be taken once per public entry point, as a read lock taken twice by the
+    /// same thread deadlocks when an injection is waiting for the write lock.
+    fn read_entity_parsers(&self) -> Result<RwLockReadGuard<'_, ()>> {
+        self.entity_parsers_lock
+            .read()
+            .map_err(|e| anyhow!("Poisonous lock: {}", e))
+    }
+
+    /// Copies the entity parsers of the engine directory, in which injections which are not
+    /// persisted are performed
+    fn copy_entity_parsers(&self, model: &NluEngineModel) -> Result<tempfile::TempDir> {
+        let temp_dir = tempfile::Builder::new()
+            .prefix("temp_dir_nlu_injection_")
+            .tempdir()?;
+        fs::copy(
+            self.engine_dir.join("nlu_engine.json"),
+            temp_dir.path().join("nlu_engine.json"),
+        )?;
+        for parser_dir in &[&model.builtin_entity_parser, &model.custom_entity_parser] {
+            copy_directory(
+                &self.engine_dir.join(parser_dir),
+                &temp_dir.path().join(parser_dir),
+            )?;
+        }
+        Ok(temp_dir)
+    }
+
+    /// Adds a pattern matching values of a custom entity which cannot be listed, such as order
+    /// numbers or flight codes
+    ///
+    /// Patterns are case insensitive and are matched on whole tokens, the matched text being used
+    /// as resolved value. They can also be declared in the `regex_entities.json` file of the
+    /// custom entity parser directory, which maps entities to lists of patterns.
+    pub fn add_regex_entity(&self, entity: &str, pattern: &str) -> Result<()> {
+        if !self.dataset_metadata.entities.contains_key(entity) {
+            bail!("Unknown custom entity: '{}'", entity);
+        }
+        self.shared_resources
+            .custom_entity_parser
+            .add_regex_entity(entity, pattern)
     }
 }
 
@@ -145,6 +366,8 @@ impl SnipsNluEngine {
         self.parse_with_alternatives(input, intents_whitelist, intents_blacklist, 0, 0)
     }
 
+    /// Parses the input and returns up to `intents_alternatives` alternative intents, along
+    /// with up to `slots_alternatives` alternative resolved values for each slot
     pub fn parse_with_alternatives<'a, 'b, W, B>(
         &self,
         input: &str,
@@ -162,6 +385,25 @@ impl SnipsNluEngine {
         let intents_whitelist = intents_whitelist_owned
             .as_ref()
             .map(|whitelist| whitelist.as_ref());
+        let _entity_parsers_guard = self.read_entity_parsers()?;
+        // The slot fillers of the parsed intent and of the alternatives share their features
+        with_feature_cache(|| {
+            self.parse_with_whitelist(
+                input,
+                intents_whitelist,
+                intents_alternatives,
+                slots_alternatives,
+            )
+        })
+    }
+
+    fn parse_with_whitelist(
+        &self,
+        input: &str,
+        intents_whitelist: Option<&[&str]>,
+        intents_alternatives: usize,
+        slots_alternatives: usize,
+    ) -> Result<IntentParserResult> {
         let mut parsing_result: Option<IntentParserResult> = None;
         let mut none_score: f32 = 0.0;
         for parser in &self.intent_parsers {
@@ -201,7 +443,7 @@ impl SnipsNluEngine {
         }
 
         let alternative_results: Vec<IntentParserAlternative> = self
-            .get_intents(input)?
+            .get_intents_unlocked(input)?
             .into_iter()
             .filter(|res| {
                 res.intent_name
@@ -219,11 +461,7 @@ impl SnipsNluEngine {
                 res.intent_name
                     .as_ref()
                     .map(|intent_name| {
-                        Ok(self.get_slots_with_alternatives(
-                            input,
-                            intent_name,
-                            slots_alternatives,
-                        )?)
+                        Ok(self.get_resolved_slots(input, intent_name, slots_alternatives)?)
                     })
                     .unwrap_or_else(|| Ok(vec![]))
                     .map(|slots| IntentParserAlternative { intent: res, slots })
@@ -234,6 +472,458 @@ impl SnipsNluEngine {
         Ok(parsing_result)
     }
 
+    /// Parses an input in which the provided entity values are matched in addition to the
+    /// trained ones
+    ///
+    /// The ephemeral values are only visible during this parse, neither the engine nor the
+    /// entity parsers caches are modified. Features are not shared with an enclosing parse, as
+    /// they depend on the matched entities.
+    pub fn parse_with_ephemeral_values<'a, 'b, W, B>(
+        &self,
+        input: &str,
+        intents_whitelist: W,
+        intents_blacklist: B,
+        ephemeral_values: Vec<EphemeralEntityValue>,
+    ) -> Result<IntentParserResult>
+    where
+        W: Into<Option<Vec<&'a str>>>,
+        B: Into<Option<Vec<&'b str>>>,
+    {
+        let ephemeral_values = self.stem_ephemeral_values(ephemeral_values)?;
+        with_new_feature_cache(|| {
+            with_ephemeral_entity_values(ephemeral_values, || {
+                self.parse(input, intents_whitelist, intents_blacklist)
+            })
+        })
+    }
+
+    /// Computes the forms of the ephemeral values which are matched in the input, which are
+    /// their raw values, their stems, or both, depending on the custom entity parser usage
+    fn stem_ephemeral_values(
+        &self,
+        ephemeral_values: Vec<EphemeralEntityValue>,
+    ) -> Result<Vec<EphemeralEntityForms>> {
+        for ephemeral_value in ephemeral_values.iter() {
+            if !self
+                .dataset_metadata
+                .entities
+                .contains_key(&ephemeral_value.entity)
+            {
+                bail!("Unknown custom entity: '{}'", ephemeral_value.entity);
+            }
+        }
+        let parser_usage = self.shared_resources.custom_entity_parser.parser_usage();
+        if parser_usage == CustomEntityParserUsage::WithoutStems {
+            return Ok(ephemeral_values.into_iter().map(Into::into).collect());
+        }
+        let stemmer = self.shared_resources.stemmer.as_ref().ok_or_else(|| {
+            anyhow!(
+                "Found {:?} parser usage but no stemmer in NLU engine",
+                parser_usage
+            )
+        })?;
+        let language = NluUtilsLanguage::from_language(
+            Language::from_str(&self.dataset_metadata.language_code).map_err(|e| anyhow!(e))?,
+        );
+        Ok(ephemeral_values
+            .into_iter()
+            .map(|ephemeral_value| {
+                let mut forms = EphemeralEntityForms::from(ephemeral_value);
+                let stemmed_values = forms
+                    .raw_values
+                    .iter()
+                    .map(|value| {
+                        tokenize_light(value, language)
+                            .into_iter()
+                            .map(|token| stemmer.stem(&token))
+                            .join(" ")
+                    })
+                    .collect_vec();
+                if parser_usage == CustomEntityParserUsage::WithStems {
+                    forms.raw_values = stemmed_values;
+                } else {
+                    forms.raw_values.extend(stemmed_values);
+                }
+                forms.raw_values = forms.raw_values.into_iter().unique().collect();
+                forms
+            })
+            .collect())
+    }
+
+    /// Parses an utterance of a conversation, taking the dialogue context into account
+    ///
+    /// When a slot of the previous intent is being elicited, the utterance is first interpreted
+    /// as a value of this slot. Otherwise the intents probabilities are biased with the context
+    /// boosts and, when no intent is found, the utterance is interpreted as an elliptical
+    /// follow-up providing new values for the slots of the previous intent. The slots of the
+    /// previous result are carried over as long as the intent does not change.
+    pub fn parse_in_context(
+        &self,
+        input: &str,
+        context: &DialogueContext,
+    ) -> Result<ContextualParserResult> {
+        if let Some(unknown_intent) = context.intent_boosts.keys().find(|intent| {
+            !self
+                .dataset_metadata
+                .slot_name_mappings
+                .contains_key(*intent)
+        }) {
+            bail!("Cannot boost unknown intent '{}'", unknown_intent);
+        }
+        let previous = context.previous_result.as_ref().and_then(|result| {
+            result
+                .intent
+                .intent_name
+                .as_ref()
+                .map(|intent| (result, intent))
+        });
+
+        if let (Some((previous_result, previous_intent)), Some(elicited_slot)) =
+            (previous, context.elicited_slot.as_ref())
+        {
+            if let Some(slot) = self.extract_slot_with_alternatives(
+                input.to_string(),
+                previous_intent,
+                elicited_slot,
+                0,
+            )? {
+                return Ok(ContextualParserResult {
+                    input: input.to_string(),
+                    intent: previous_result.intent.clone(),
+                    slots: merge_slots(vec![slot], SlotOrigin::Elicited, &previous_result.slots),
+                });
+            }
+        }
+
+        let parsing_result = if context.intent_boosts.is_empty() {
+            self.parse(input, None, None)?
+        } else {
+            self.parse_with_intent_boosts(input, &context.intent_boosts)?
+        };
+
+        match (parsing_result.intent.intent_name.as_ref(), previous) {
+            (None, Some((previous_result, previous_intent))) => {
+                let follow_up_slots =
+                    self.extract_follow_up_slots(input, previous_intent, &previous_result.slots)?;
+                if !follow_up_slots.is_empty() {
+                    return Ok(ContextualParserResult {
+                        input: input.to_string(),
+                        intent: previous_result.intent.clone(),
+                        slots: merge_slots(
+                            follow_up_slots,
+                            SlotOrigin::CurrentTurn,
+                            &previous_result.slots,
+                        ),
+                    });
+                }
+                Ok(ContextualParserResult {
+                    input: input.to_string(),
+                    intent: parsing_result.intent,
+                    slots: vec![],
+                })
+            }
+            (Some(intent), Some((previous_result, previous_intent)))
+                if intent == previous_intent =>
+            {
+                Ok(ContextualParserResult {
+                    input: input.to_string(),
+                    slots: merge_slots(
+                        parsing_result.slots,
+                        SlotOrigin::CurrentTurn,
+                        &previous_result.slots,
+                    ),
+                    intent: parsing_result.intent,
+                })
+            }
+            _ => Ok(ContextualParserResult {
+                input: input.to_string(),
+                slots: merge_slots(parsing_result.slots, SlotOrigin::CurrentTurn, &[]),
+                intent: parsing_result.intent,
+            }),
+        }
+    }
+
+    /// Parses the input along with all the intent alternatives, and returns the alternative,
+    /// possibly the parsed intent, whose boosted probability is the highest
+    fn parse_with_intent_boosts(
+        &self,
+        input: &str,
+        intent_boosts: &HashMap<IntentName, f32>,
+    ) -> Result<IntentParserResult> {
+        let nb_intents = self.dataset_metadata.slot_name_mappings.len();
+        let parsing_result = self.parse_with_alternatives(input, None, None, nb_intents, 0)?;
+        let candidates = vec![IntentParserAlternative {
+            intent: parsing_result.intent,
+            slots: parsing_result.slots,
+        }]
+        .into_iter()
+        .chain(parsing_result.alternatives)
+        .unique_by(|candidate| candidate.intent.intent_name.clone())
+        .collect_vec();
+        let boosted_intents = apply_intent_boosts(
+            candidates
+                .iter()
+                .map(|candidate| candidate.intent.clone())
+                .collect(),
+            intent_boosts,
+        );
+        let best_intent = boosted_intents
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("No intent alternative to boost"))?;
+        let slots = candidates
+            .into_iter()
+            .find(|candidate| candidate.intent.intent_name == best_intent.intent_name)
+            .map(|candidate| candidate.slots)
+            .unwrap_or_default();
+        Ok(IntentParserResult {
+            input: input.to_string(),
+            intent: best_intent,
+            slots,
+            alternatives: vec![],
+        })
+    }
+
+    /// Extracts the slots of the previous intent which are explicitly mentioned in an
+    /// elliptical follow-up, giving priority to the slots of the previous result
+    ///
+    /// Slots spanning the whole follow-up with an automatically extensible entity may result
+    /// from the extension of the entity rather than from a match, hence they are only kept when
+    /// no other slot is found.
+    fn extract_follow_up_slots(
+        &self,
+        input: &str,
+        previous_intent: &str,
+        previous_slots: &[Slot],
+    ) -> Result<Vec<Slot>> {
+        let previous_slot_names = previous_slots
+            .iter()
+            .map(|slot| &slot.slot_name)
+            .unique()
+            .collect_vec();
+        let other_slot_names = self
+            .dataset_metadata
+            .slot_name_mappings
+            .get(previous_intent)
+            .into_iter()
+            .flat_map(|mapping| mapping.keys())
+            .filter(|slot_name| !previous_slot_names.contains(slot_name))
+            .sorted();
+        let input_length = input.chars().count();
+        let mut follow_up_slots: Vec<Slot> = vec![];
+        let mut extended_slots: Vec<Slot> = vec![];
+        for slot_name in previous_slot_names.into_iter().chain(other_slot_names) {
+            let opt_slot = self.extract_slot_with_alternatives(
+                input.to_string(),
+                previous_intent,
+                slot_name,
+                0,
+            )?;
+            if let Some(slot) = opt_slot {
+                let is_extensible = self
+                    .dataset_metadata
+                    .entities
+                    .get(&slot.entity)
+                    .map(|entity| entity.automatically_extensible)
+                    .unwrap_or(false);
+                if is_extensible && slot.range == (0..input_length) {
+                    extended_slots.push(slot);
+                } else if !follow_up_slots
+                    .iter()
+                    .any(|other| ranges_overlap(&other.range, &slot.range))
+                {
+                    follow_up_slots.push(slot);
+                }
+            }
+        }
+        if follow_up_slots.is_empty() {
+            follow_up_slots.extend(extended_slots.into_iter().take(1));
+        }
+        Ok(follow_up_slots)
+    }
+
+    /// Parses the input and reports the discrepancies between the extracted slots and the
+    /// intent schema
+    ///
+    /// The report is empty when no schema is defined for the parsed intent.
+    pub fn parse_with_schema<'a, 'b, W, B>(
+        &self,
+        input: &str,
+        intents_whitelist: W,
+        intents_blacklist: B,
+    ) -> Result<SchemaParserResult>
+    where
+        W: Into<Option<Vec<&'a str>>>,
+        B: Into<Option<Vec<&'b str>>>,
+    {
+        let result = self.parse(input, intents_whitelist, intents_blacklist)?;
+        let slot_report = result
+            .intent
+            .intent_name
+            .as_ref()
+            .map(|intent| self.check_slots(intent, &result.slots))
+            .unwrap_or_default();
+        Ok(SchemaParserResult {
+            result,
+            slot_report,
+        })
+    }
+
+    /// Checks the slots of an intent against the intent schema
+    pub fn check_slots(&self, intent: &str, slots: &[Slot]) -> SlotReport {
+        self.intent_schema
+            .as_ref()
+            .and_then(|schema| schema.intents.get(intent))
+            .map(|intent_schema| check_slots(intent_schema, slots))
+            .unwrap_or_default()
+    }
+
+    /// Extracts a missing slot from a follow-up utterance and adds it to the parsing result
+    ///
+    /// The slot report is updated accordingly. The result is left unchanged when the slot
+    /// cannot be extracted from the utterance.
+    pub fn fill_missing_slot(
+        &self,
+        input: String,
+        slot_name: &str,
+        schema_result: SchemaParserResult,
+    ) -> Result<SchemaParserResult> {
+        let intent = schema_result
+            .result
+            .intent
+            .intent_name
+            .clone()
+            .ok_or_else(|| anyhow!("Cannot fill slot '{}' without intent", slot_name))?;
+        let mut result = schema_result.result;
+        if let Some(slot) = self.extract_slot(input, &intent, slot_name)? {
+            result.slots.push(slot);
+        }
+        let slot_report = self.check_slots(&intent, &result.slots);
+        Ok(SchemaParserResult {
+            result,
+            slot_report,
+        })
+    }
+
+    /// Parses the input and links each slot to the innermost slot containing it
+    ///
+    /// Nested slots are only extracted by the deterministic intent parser when its
+    /// `allow_nested_slots` option is enabled, otherwise all slots are disjoint and have no
+    /// parent.
+    pub fn parse_nested<'a, 'b, W, B>(
+        &self,
+        input: &str,
+        intents_whitelist: W,
+        intents_blacklist: B,
+    ) -> Result<NestedParserResult>
+    where
+        W: Into<Option<Vec<&'a str>>>,
+        B: Into<Option<Vec<&'b str>>>,
+    {
+        let parsing_result = self.parse(input, intents_whitelist, intents_blacklist)?;
+        Ok(NestedParserResult {
+            input: parsing_result.input,
+            intent: parsing_result.intent,
+            slots: nest_slots(parsing_result.slots),
+        })
+    }
+
+    /// Parses an input which may express several intents
+    ///
+    /// Every intent whose probability is above `intents_threshold` is detected. When several
+    /// intents are found, the input is split on conjunctions and each segment is attributed to
+    /// the detected intent which scores the best on it. Each intent is then returned along with
+    /// its best scoring segment, on which the slots are extracted, and the intents which are
+    /// attributed no segment are dropped, hence segments never overlap.
+    /// When at most one intent is found, or when no segment is attributed, this is equivalent
+    /// to `parse`.
+    pub fn parse_multi(
+        &self,
+        input: &str,
+        intents_threshold: f32,
+    ) -> Result<Vec<MultiIntentParserResult>> {
+        let detected_intents: Vec<IntentClassifierResult> = self
+            .get_intents(input)?
+            .into_iter()
+            .filter(|res| res.intent_name.is_some() && res.confidence_score >= intents_threshold)
+            .collect();
+        let parse_whole_input = || -> Result<Vec<MultiIntentParserResult>> {
+            let parsing_result = self.parse(input, None, None)?;
+            Ok(vec![MultiIntentParserResult {
+                intent: parsing_result.intent,
+                range: 0..input.chars().count(),
+                slots: parsing_result.slots,
+            }])
+        };
+
+        if detected_intents.len() <= 1 {
+            return parse_whole_input();
+        }
+
+        let language =
+            Language::from_str(&self.dataset_metadata.language_code).map_err(|e| anyhow!(e))?;
+        let detected_intent_names: Vec<&str> = detected_intents
+            .iter()
+            .filter_map(|res| res.intent_name.as_deref())
+            .collect();
+
+        // Each segment is attributed to the detected intent which scores the best on it, and
+        // each intent keeps its best scoring segment
+        let mut intents_segments: HashMap<String, (Range<usize>, f32)> = HashMap::new();
+        for segment in split_on_conjunctions(input, NluUtilsLanguage::from_language(language)) {
+            let segment_input = substring_with_char_range(input.to_string(), &segment);
+            let best_intent = self.get_intents(&segment_input)?.into_iter().find(|res| {
+                res.intent_name
+                    .as_ref()
+                    .map(|name| detected_intent_names.contains(&&**name))
+                    .unwrap_or(false)
+            });
+            if let Some(IntentClassifierResult {
+                intent_name: Some(intent_name),
+                confidence_score,
+            }) = best_intent
+            {
+                let is_best_segment = intents_segments
+                    .get(&intent_name)
+                    .map(|(_, score)| confidence_score > *score)
+                    .unwrap_or(true);
+                if is_best_segment {
+                    intents_segments.insert(intent_name, (segment, confidence_score));
+                }
+            }
+        }
+
+        if intents_segments.is_empty() {
+            return parse_whole_input();
+        }
+
+        detected_intents
+            .into_iter()
+            .filter_map(|intent| {
+                let intent_name = intent.intent_name.clone().unwrap(); // Checked above
+                intents_segments
+                    .remove(&intent_name)
+                    .map(|(segment, _)| (intent, intent_name, segment))
+            })
+            .map(|(intent, intent_name, range)| {
+                let segment_input = substring_with_char_range(input.to_string(), &range);
+                let slots = self
+                    .get_slots(&segment_input, &intent_name)?
+                    .into_iter()
+                    .map(|mut slot| {
+                        slot.range = slot.range.start + range.start..slot.range.end + range.start;
+                        slot
+                    })
+                    .collect();
+                Ok(MultiIntentParserResult {
+                    intent,
+                    range,
+                    slots,
+                })
+            })
+            .collect()
+    }
+
     fn get_intents_whitelist<'a: 'c, 'b: 'c, 'c, W, B>(
         &'c self,
         intents_whitelist: W,
@@ -280,6 +970,12 @@ impl SnipsNluEngine {
     }
 
     pub fn get_intents(&self, input: &str) -> Result<Vec<IntentClassifierResult>> {
+        let _entity_parsers_guard = self.read_entity_parsers()?;
+        self.get_intents_unlocked(input)
+    }
+
+    /// Must only be called while the entity parsers lock is held
+    fn get_intents_unlocked(&self, input: &str) -> Result<Vec<IntentClassifierResult>> {
         let nb_intents = self.dataset_metadata.slot_name_mappings.len();
         let mut results = HashMap::with_capacity(nb_intents + 1);
         for parser in self.intent_parsers.iter() {
@@ -319,6 +1015,16 @@ impl SnipsNluEngine {
         input: &str,
         intent: &str,
         slots_alternatives: usize,
+    ) -> Result<Vec<Slot>> {
+        let _entity_parsers_guard = self.read_entity_parsers()?;
+        self.get_resolved_slots(input, intent, slots_alternatives)
+    }
+
+    fn get_resolved_slots(
+        &self,
+        input: &str,
+        intent: &str,
+        slots_alternatives: usize,
     ) -> Result<Vec<Slot>> {
         for parser in &self.intent_parsers {
             let slots = parser.get_slots(input, intent)?;
@@ -329,6 +1035,50 @@ impl SnipsNluEngine {
         Ok(vec![])
     }
 
+    /// Returns up to `n` alternative slot segmentations of the input for the provided intent,
+    /// sorted by decreasing probability
+    ///
+    /// The segmentations following the most probable one are approximate n-best candidates,
+    /// see `SlotFiller::get_slots_nbest`.
+    pub fn get_slots_nbest(
+        &self,
+        input: &str,
+        intent: &str,
+        n: usize,
+    ) -> Result<Vec<SlotSequence>> {
+        let _entity_parsers_guard = self.read_entity_parsers()?;
+        for parser in &self.intent_parsers {
+            let slot_sequences = parser.get_slots_nbest(input, intent, n)?;
+            if slot_sequences
+                .iter()
+                .any(|sequence| !sequence.slots.is_empty())
+            {
+                return slot_sequences
+                    .into_iter()
+                    .map(|sequence| {
+                        Ok(SlotSequence {
+                            slots: self.resolve_slots(input, sequence.slots, 0)?,
+                            probability: sequence.probability,
+                        })
+                    })
+                    .collect();
+            }
+        }
+        Ok(vec![])
+    }
+
+    /// Returns the tags predicted for each token of the input by the slot filler of the
+    /// provided intent, or `None` when none of the intent parsers relies on a slot filler
+    pub fn tag_tokens(&self, input: &str, intent: &str) -> Result<Option<TokenTagging>> {
+        let _entity_parsers_guard = self.read_entity_parsers()?;
+        for parser in &self.intent_parsers {
+            if let Some(tagging) = parser.tag_tokens(input, intent)? {
+                return Ok(Some(tagging));
+            }
+        }
+        Ok(None)
+    }
+
     fn resolve_slots(
         &self,
         text: &str,
@@ -417,6 +1167,7 @@ impl SnipsNluEngine {
             .ok_or_else(|| anyhow!("Unknown intent: {}", intent_name))?
             .get(slot_name)
             .ok_or_else(|| anyhow!("Unknown slot: {}", &slot_name))?;
+        let _entity_parsers_guard = self.read_entity_parsers()?;
 
         let slot = if let Some(custom_entity) = self.dataset_metadata.entities.get(entity_name) {
             extract_custom_slot(
@@ -440,6 +1191,33 @@ impl SnipsNluEngine {
     }
 }
 
+fn nest_slots(slots: Vec<Slot>) -> Vec<NestedSlot> {
+    let parents: Vec<Option<usize>> = slots
+        .iter()
+        .enumerate()
+        .map(|(slot_index, slot)| {
+            slots
+                .iter()
+                .enumerate()
+                .filter(|(other_index, other)| {
+                    *other_index != slot_index
+                        && other.range.start <= slot.range.start
+                        && slot.range.end <= other.range.end
+                        && (other.range != slot.range || *other_index < slot_index)
+                })
+                .min_by_key(|(other_index, other)| {
+                    (other.range.end - other.range.start, -(*other_index as i64))
+                })
+                .map(|(other_index, _)| other_index)
+        })
+        .collect();
+    slots
+        .into_iter()
+        .zip(parents)
+        .map(|(slot, parent)| NestedSlot { slot, parent })
+        .collect()
+}
+
 fn extract_custom_slot(
     input: String,
     entity_name: EntityName,
@@ -454,6 +1232,7 @@ fn extract_custom_slot(
         slot_alternatives,
     )?;
     Ok(if let Some(matched_entity) = custom_entities.pop() {
+        let match_score = custom_entity_parser.match_score(&matched_entity)?;
         Some(Slot {
             raw_value: matched_entity.value,
             value: SlotValue::Custom(matched_entity.resolved_value.into()),
@@ -465,7 +1244,7 @@ fn extract_custom_slot(
             range: matched_entity.range,
             entity: entity_name.clone(),
             slot_name: slot_name.clone(),
-            confidence_score: None,
+            confidence_score: Some(match_score).filter(|score| *score < 1.0),
         })
     } else if custom_entity.automatically_extensible {
         let range = 0..input.chars().count();
@@ -519,6 +1298,7 @@ mod tests {
     use snips_nlu_ontology::{NumberValue, StringValue};
 
     use crate::entity_parser::custom_entity_parser::CustomEntity;
+    use crate::models::IntentSlotsSchema;
     use crate::testutils::*;
 
     use super::*;
@@ -808,6 +1588,72 @@ mod tests {
         assert_eq!(expected_result, result);
     }
 
+    #[test]
+    fn test_parse_multi() {
+        // Given
+        let path = Path::new("data")
+            .join("tests")
+            .join("models")
+            .join("nlu_engine_beverage");
+        let nlu_engine = SnipsNluEngine::from_path(path).unwrap();
+
+        // When
+        let results = nlu_engine
+            .parse_multi("make me a cup of tea and two cups of coffee", 0.2)
+            .unwrap();
+
+        // Then
+        let mut intents: Vec<(Option<String>, Range<usize>)> = results
+            .iter()
+            .map(|res| (res.intent.intent_name.clone(), res.range.clone()))
+            .collect();
+        intents.sort_by_key(|(_, range)| range.start);
+        let expected_intents = vec![
+            (Some("MakeTea".to_string()), 0..20),
+            (Some("MakeCoffee".to_string()), 25..43),
+        ];
+        assert_eq!(expected_intents, intents);
+
+        let coffee_slots = &results
+            .iter()
+            .find(|res| res.intent.intent_name == Some("MakeCoffee".to_string()))
+            .unwrap()
+            .slots;
+        let expected_coffee_slots = vec![Slot {
+            raw_value: "two".to_string(),
+            value: SlotValue::Number(NumberValue { value: 2.0 }),
+            alternatives: vec![],
+            range: 25..28,
+            entity: "snips/number".to_string(),
+            slot_name: "number_of_cups".to_string(),
+            confidence_score: None,
+        }];
+        assert_eq!(&expected_coffee_slots, coffee_slots);
+    }
+
+    #[test]
+    fn test_parse_multi_when_single_intent() {
+        // Given
+        let path = Path::new("data")
+            .join("tests")
+            .join("models")
+            .join("nlu_engine_beverage");
+        let nlu_engine = SnipsNluEngine::from_path(path).unwrap();
+        let input = "Make me two cups of coffee please";
+
+        // When
+        let results = nlu_engine.parse_multi(input, 1.1).unwrap();
+
+        // Then
+        let parsing_result = nlu_engine.parse(input, None, None).unwrap();
+        let expected_results = vec![MultiIntentParserResult {
+            intent: parsing_result.intent,
+            range: 0..33,
+            slots: parsing_result.slots,
+        }];
+        assert_eq!(expected_results, results);
+    }
+
     #[test]
     fn test_get_intents() {
         // Given
@@ -875,6 +1721,442 @@ mod tests {
         assert_eq!(expected_slots, slots);
     }
 
+    #[test]
+    fn test_parse_with_schema_and_fill_missing_slot() {
+        // Given
+        let path = Path::new("data")
+            .join("tests")
+            .join("models")
+            .join("nlu_engine_beverage");
+        let intent_schema = IntentSchema {
+            intents: vec![(
+                "MakeTea".to_string(),
+                IntentSlotsSchema {
+                    required_slots: vec!["number_of_cups".to_string()],
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+        };
+        let nlu_engine = SnipsNluEngine::from_path(path)
+            .unwrap()
+            .with_intent_schema(intent_schema)
+            .unwrap();
+
+        // When
+        let schema_result = nlu_engine
+            .parse_with_schema("Make me a hot tea please", None, None)
+            .unwrap();
+        let filled_result = nlu_engine
+            .fill_missing_slot(
+                "two please".to_string(),
+                "number_of_cups",
+                schema_result.clone(),
+            )
+            .unwrap();
+
+        // Then
+        let expected_report = SlotReport {
+            missing_slots: vec!["number_of_cups".to_string()],
+            duplicated_slots: vec![],
+            conflicting_slots: vec![],
+        };
+        assert_eq!(
+            Some("MakeTea".to_string()),
+            schema_result.result.intent.intent_name
+        );
+        assert_eq!(expected_report, schema_result.slot_report);
+        assert!(filled_result.slot_report.is_valid());
+        assert!(filled_result
+            .result
+            .slots
+            .iter()
+            .any(|slot| slot.slot_name == "number_of_cups"
+                && slot.value == SlotValue::Number(NumberValue { value: 2.0 })));
+    }
+
+    #[test]
+    fn test_with_intent_schema_should_fail_with_unknown_slot() {
+        // Given
+        let path = Path::new("data")
+            .join("tests")
+            .join("models")
+            .join("nlu_engine_beverage");
+        let intent_schema = IntentSchema {
+            intents: vec![(
+                "MakeCoffee".to_string(),
+                IntentSlotsSchema {
+                    required_slots: vec!["beverage_temperature".to_string()],
+                    ..Default::default()
+                },
+            )]
+            .into_iter()
+            .collect(),
+        };
+
+        // When
+        let nlu_engine = SnipsNluEngine::from_path(path)
+            .unwrap()
+            .with_intent_schema(intent_schema);
+
+        // Then
+        assert!(nlu_engine.is_err());
+    }
+
+    #[test]
+    fn test_parse_in_context_with_elicited_slot() {
+        // Given
+        let path = Path::new("data")
+            .join("tests")
+            .join("models")
+            .join("nlu_engine_beverage");
+        let nlu_engine = SnipsNluEngine::from_path(path).unwrap();
+        let previous_result = nlu_engine.parse("Make me a hot tea", None, None).unwrap();
+        let context = DialogueContext {
+            previous_result: Some(previous_result),
+            elicited_slot: Some("number_of_cups".to_string()),
+            ..Default::default()
+        };
+
+        // When
+        let result = nlu_engine.parse_in_context("three", &context).unwrap();
+
+        // Then
+        assert_eq!(Some("MakeTea".to_string()), result.intent.intent_name);
+        let slots_origins = result
+            .slots
+            .iter()
+            .map(|contextual_slot| {
+                (
+                    contextual_slot.slot.slot_name.as_ref(),
+                    contextual_slot.origin,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ("beverage_temperature", SlotOrigin::CarriedOver),
+                ("number_of_cups", SlotOrigin::Elicited),
+            ],
+            slots_origins
+        );
+        assert_eq!(
+            SlotValue::Number(NumberValue { value: 3.0 }),
+            result.slots[1].slot.value
+        );
+    }
+
+    #[test]
+    fn test_extract_follow_up_slots() {
+        // Given
+        let path = Path::new("data")
+            .join("tests")
+            .join("models")
+            .join("nlu_engine_beverage");
+        let nlu_engine = SnipsNluEngine::from_path(path).unwrap();
+        let previous_slots = nlu_engine
+            .get_slots("Make me a hot tea", "MakeTea")
+            .unwrap();
+
+        // When
+        let follow_up_slots = nlu_engine
+            .extract_follow_up_slots("and two cups", "MakeTea", &previous_slots)
+            .unwrap();
+
+        // Then
+        let expected_slots = vec![Slot {
+            raw_value: "two".to_string(),
+            value: SlotValue::Number(NumberValue { value: 2.0 }),
+            alternatives: vec![],
+            range: 4..7,
+            entity: "snips/number".to_string(),
+            slot_name: "number_of_cups".to_string(),
+            confidence_score: None,
+        }];
+        assert_eq!(expected_slots, follow_up_slots);
+    }
+
+    #[test]
+    fn test_parse_in_context_should_fail_with_unknown_boosted_intent() {
+        // Given
+        let path = Path::new("data")
+            .join("tests")
+            .join("models")
+            .join("nlu_engine_beverage");
+        let nlu_engine = SnipsNluEngine::from_path(path).unwrap();
+        let context = DialogueContext {
+            intent_boosts: vec![("MakeChocolate".to_string(), 2.0)]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+
+        // When
+        let result = nlu_engine.parse_in_context("Make me a hot tea", &context);
+
+        // Then
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_inject_values_without_persisting() {
+        // Given
+        let path = Path::new("data")
+            .join("tests")
+            .join("models")
+            .join("nlu_engine_music");
+        let nlu_engine = SnipsNluEngine::from_path(&path).unwrap();
+        let text = "je voudrais ecouter ma playlist funky";
+        let playlist_scope = vec!["playlist".to_string()];
+        let extract_playlists = |nlu_engine: &SnipsNluEngine| {
+            nlu_engine
+                .shared_resources
+                .custom_entity_parser
+                .extract_entities(text, Some(&playlist_scope), 0)
+                .unwrap()
+                .into_iter()
+                .map(|entity| entity.resolved_value)
+                .collect::<Vec<_>>()
+        };
+        let entities_before_injection = extract_playlists(&nlu_engine);
+
+        // When
+        nlu_engine
+            .inject_values(
+                |injector| injector.add_value("playlist".to_string(), "funky".to_string()),
+                false,
+            )
+            .unwrap();
+
+        // Then
+        let reloaded_engine = SnipsNluEngine::from_path(&path).unwrap();
+        assert!(entities_before_injection.is_empty());
+        assert_eq!(vec!["funky".to_string()], extract_playlists(&nlu_engine));
+        assert!(extract_playlists(&reloaded_engine).is_empty());
+    }
+
+    #[test]
+    fn test_persisted_injection_keeps_values_which_are_not_persisted() {
+        // Given
+        let path = Path::new("data")
+            .join("tests")
+            .join("models")
+            .join("nlu_engine_music");
+        let tdir = tempfile::tempdir().unwrap();
+        fs_extra::dir::copy(path, tdir.as_ref(), &fs_extra::dir::CopyOptions::new()).unwrap();
+        let engine_dir = tdir.as_ref().join("nlu_engine_music");
+        let nlu_engine = SnipsNluEngine::from_path(&engine_dir).unwrap();
+        let playlist_scope = vec!["playlist".to_string()];
+        let extract_playlists = |nlu_engine: &SnipsNluEngine| {
+            nlu_engine
+                .shared_resources
+                .custom_entity_parser
+                .extract_entities("ma playlist funky ou jazzy", Some(&playlist_scope), 0)
+                .unwrap()
+                .into_iter()
+                .map(|entity| entity.resolved_value)
+                .collect::<Vec<_>>()
+        };
+
+        // When
+        nlu_engine
+            .inject_values(
+                |injector| injector.add_value("playlist".to_string(), "funky".to_string()),
+                false,
+            )
+            .unwrap();
+        nlu_engine
+            .inject_values(
+                |injector| injector.add_value("playlist".to_string(), "jazzy".to_string()),
+                true,
+            )
+            .unwrap();
+
+        // Then
+        let reloaded_engine = SnipsNluEngine::from_path(&engine_dir).unwrap();
+        assert_eq!(
+            vec!["funky".to_string(), "jazzy".to_string()],
+            extract_playlists(&nlu_engine)
+        );
+        assert_eq!(
+            vec!["jazzy".to_string()],
+            extract_playlists(&reloaded_engine)
+        );
+    }
+
+    #[test]
+    fn test_parse_with_ephemeral_values_should_not_reuse_features() {
+        // Given
+        let path = Path::new("data")
+            .join("tests")
+            .join("models")
+            .join("nlu_engine_music");
+        let nlu_engine = SnipsNluEngine::from_path(path).unwrap();
+        let input = "joue la playlist funky";
+        let ephemeral_values = vec![EphemeralEntityValue {
+            entity: "playlist".to_string(),
+            value: "funky".to_string(),
+            synonyms: vec![],
+        }];
+
+        // When
+        let (result, ephemeral_result) = with_feature_cache(|| {
+            let result = nlu_engine.parse(input, None, None).unwrap();
+            let ephemeral_result = nlu_engine
+                .parse_with_ephemeral_values(input, None, None, ephemeral_values)
+                .unwrap();
+            (result, ephemeral_result)
+        });
+
+        // Then
+        let get_playlists = |result: &IntentParserResult| {
+            result
+                .slots
+                .iter()
+                .filter(|slot| slot.slot_name == "playlist")
+                .map(|slot| slot.raw_value.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_ne!(result.slots, ephemeral_result.slots);
+        assert!(get_playlists(&result).is_empty());
+        assert_eq!(vec!["funky".to_string()], get_playlists(&ephemeral_result));
+    }
+
+    #[test]
+    fn test_parse_with_ephemeral_values_should_fail_with_unknown_entity() {
+        // Given
+        let path = Path::new("data")
+            .join("tests")
+            .join("models")
+            .join("nlu_engine_music");
+        let nlu_engine = SnipsNluEngine::from_path(path).unwrap();
+        let ephemeral_values = vec![EphemeralEntityValue {
+            entity: "unknown_entity".to_string(),
+            value: "foo".to_string(),
+            synonyms: vec![],
+        }];
+
+        // When
+        let result = nlu_engine.parse_with_ephemeral_values(
+            "je voudrais ecouter foo",
+            None,
+            None,
+            ephemeral_values,
+        );
+
+        // Then
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_add_regex_entity_should_fail_with_unknown_entity() {
+        // Given
+        let path = Path::new("data")
+            .join("tests")
+            .join("models")
+            .join("nlu_engine_music");
+        let nlu_engine = SnipsNluEngine::from_path(path).unwrap();
+
+        // When
+        let result = nlu_engine.add_regex_entity("unknown_entity", r"\d+");
+
+        // Then
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_nest_slots() {
+        // Given
+        let get_slot = |raw_value: &str, range: Range<usize>, slot_name: &str| Slot {
+            raw_value: raw_value.to_string(),
+            value: SlotValue::Custom(raw_value.into()),
+            alternatives: vec![],
+            range,
+            entity: slot_name.to_string(),
+            slot_name: slot_name.to_string(),
+            confidence_score: None,
+        };
+        let slots = vec![
+            get_slot("the airport in paris", 9..29, "location"),
+            get_slot("airport in paris", 13..29, "place"),
+            get_slot("paris", 24..29, "city"),
+            get_slot("tomorrow", 30..38, "date"),
+        ];
+
+        // When
+        let nested_slots = nest_slots(slots.clone());
+
+        // Then
+        let expected_parents = vec![None, Some(0), Some(1), None];
+        assert_eq!(
+            expected_parents,
+            nested_slots
+                .iter()
+                .map(|nested_slot| nested_slot.parent)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            slots,
+            nested_slots
+                .into_iter()
+                .map(|nested_slot| nested_slot.slot)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_get_slots_nbest() {
+        // Given
+        let path = Path::new("data")
+            .join("tests")
+            .join("models")
+            .join("nlu_engine_beverage");
+        let nlu_engine = SnipsNluEngine::from_path(path).unwrap();
+        let input = "Make me two cups of coffee please";
+
+        // When
+        let slot_sequences = nlu_engine.get_slots_nbest(input, "MakeCoffee", 2).unwrap();
+
+        // Then
+        let expected_first_slots = nlu_engine.get_slots(input, "MakeCoffee").unwrap();
+        assert_eq!(2, slot_sequences.len());
+        assert_eq!(expected_first_slots, slot_sequences[0].slots);
+        assert!(slot_sequences[0].probability >= slot_sequences[1].probability);
+    }
+
+    #[test]
+    fn test_tag_tokens() {
+        // Given
+        let path = Path::new("data")
+            .join("tests")
+            .join("models")
+            .join("nlu_engine_beverage");
+        let nlu_engine = SnipsNluEngine::from_path(path).unwrap();
+
+        // When
+        let tagging = nlu_engine
+            .tag_tokens("Make me two cups of coffee", "MakeCoffee")
+            .unwrap()
+            .unwrap();
+
+        // Then
+        let actual_tokens = tagging
+            .tokens
+            .iter()
+            .map(|token| (token.value.as_str(), token.tag.as_str()))
+            .collect_vec();
+        let expected_tokens = vec![
+            ("Make", "O"),
+            ("me", "O"),
+            ("two", "B-number_of_cups"),
+            ("cups", "O"),
+            ("of", "O"),
+            ("coffee", "O"),
+        ];
+        assert_eq!(expected_tokens, actual_tokens);
+    }
+
     #[test]
     fn test_get_slots_with_alternatives() {
         // Given
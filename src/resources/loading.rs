@@ -10,11 +10,15 @@ use serde::Deserialize;
 use snips_nlu_ontology::Language;
 
 use crate::entity_parser::{CachingBuiltinEntityParser, CachingCustomEntityParser};
-use crate::models::nlu_engine::NluEngineModel;
+use crate::models::nlu_engine::{Entity, NluEngineModel, NormalizationConfig};
 use crate::resources::gazetteer::{Gazetteer, HashSetGazetteer};
+use crate::resources::normalizer::{Normalizer, TextNormalizer};
 use crate::resources::stemmer::{HashMapStemmer, Stemmer};
 use crate::resources::word_clusterer::{HashMapWordClusterer, WordClusterer};
+use crate::resources::word_embeddings::{HashMapWordEmbeddings, WordEmbeddings};
 use crate::resources::SharedResources;
+use crate::slot_filler::FeatureRegistry;
+use crate::utils::EntityName;
 use anyhow::{anyhow, Context, Result};
 
 #[derive(Debug, Deserialize, Clone)]
@@ -22,14 +26,63 @@ struct ResourcesMetadata {
     language: String,
     gazetteers: Option<Vec<String>>,
     word_clusters: Option<Vec<String>>,
+    word_embeddings: Option<Vec<String>>,
     stems: Option<String>,
     stop_words: Option<String>,
 }
 
+/// Loads the shared resources
+///
+/// `custom_entities` are the custom entities of the dataset the resources are used with, the
+/// ones which are not automatically extensible constraining the slots of the CRF slot fillers.
 pub fn load_shared_resources<P: AsRef<Path>, Q: AsRef<Path>, R: AsRef<Path>>(
     resources_dir: P,
     builtin_entity_parser_path: Q,
     custom_entity_parser_path: R,
+    custom_entities: HashMap<EntityName, Entity>,
+) -> Result<Arc<SharedResources>> {
+    load_shared_resources_with_feature_registry(
+        resources_dir,
+        builtin_entity_parser_path,
+        custom_entity_parser_path,
+        custom_entities,
+        FeatureRegistry::default(),
+    )
+}
+
+/// Loads the shared resources along with the registry used to build the CRF features
+pub fn load_shared_resources_with_feature_registry<
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+    R: AsRef<Path>,
+>(
+    resources_dir: P,
+    builtin_entity_parser_path: Q,
+    custom_entity_parser_path: R,
+    custom_entities: HashMap<EntityName, Entity>,
+    feature_registry: FeatureRegistry,
+) -> Result<Arc<SharedResources>> {
+    load_shared_resources_with_normalization(
+        resources_dir,
+        builtin_entity_parser_path,
+        custom_entity_parser_path,
+        feature_registry,
+        NormalizationConfig::default(),
+        custom_entities,
+    )
+}
+
+/// Loads the shared resources, the entity parsers normalizing texts according to the provided
+/// configuration
+///
+/// `custom_entities` are the custom entities of the dataset the resources are used with.
+pub fn load_shared_resources_with_normalization<P: AsRef<Path>, Q: AsRef<Path>, R: AsRef<Path>>(
+    resources_dir: P,
+    builtin_entity_parser_path: Q,
+    custom_entity_parser_path: R,
+    feature_registry: FeatureRegistry,
+    normalization_config: NormalizationConfig,
+    custom_entities: HashMap<EntityName, Entity>,
 ) -> Result<Arc<SharedResources>> {
     let metadata_file_path = resources_dir.as_ref().join("metadata.json");
     let metadata_file = File::open(&metadata_file_path)?;
@@ -43,19 +96,28 @@ pub fn load_shared_resources<P: AsRef<Path>, Q: AsRef<Path>, R: AsRef<Path>>(
     let stemmer = load_stemmer(&resources_dir, &metadata)?;
     let gazetteers = load_gazetteers(&resources_dir, &metadata)?;
     let word_clusterers = load_word_clusterers(&resources_dir, &metadata)?;
+    let word_embeddings = load_word_embeddings(&resources_dir, &metadata)?;
     let stop_words = load_stop_words(&resources_dir, &metadata)?;
-    let builtin_entity_parser =
-        CachingBuiltinEntityParser::from_path(builtin_entity_parser_path, 1000)?;
+    let normalizer: Arc<dyn Normalizer> = Arc::new(TextNormalizer::new(normalization_config));
+    let builtin_entity_parser = CachingBuiltinEntityParser::from_path(
+        builtin_entity_parser_path,
+        1000,
+        normalizer.clone(),
+    )?;
     let custom_entity_parser =
-        CachingCustomEntityParser::from_path(custom_entity_parser_path, 1000)?;
+        CachingCustomEntityParser::from_path(custom_entity_parser_path, 1000, normalizer.clone())?;
 
     Ok(Arc::new(SharedResources {
         builtin_entity_parser: Arc::new(builtin_entity_parser),
         custom_entity_parser: Arc::new(custom_entity_parser),
         gazetteers,
         stemmer,
+        normalizer,
+        custom_entities,
         word_clusterers,
+        word_embeddings,
         stop_words,
+        feature_registry: Arc::new(feature_registry),
     }))
 }
 
@@ -73,7 +135,14 @@ pub fn load_engine_shared_resources<P: AsRef<Path>>(engine_dir: P) -> Result<Arc
         .join(language.to_string());
     let builtin_parser_path = engine_dir.as_ref().join(&model.builtin_entity_parser);
     let custom_parser_path = engine_dir.as_ref().join(&model.custom_entity_parser);
-    load_shared_resources(&resources_path, builtin_parser_path, custom_parser_path)
+    load_shared_resources_with_normalization(
+        &resources_path,
+        builtin_parser_path,
+        custom_parser_path,
+        FeatureRegistry::default(),
+        model.normalization,
+        model.dataset_metadata.entities,
+    )
 }
 
 fn load_stemmer<P: AsRef<Path>>(
@@ -147,6 +216,35 @@ fn load_word_clusterers<P: AsRef<Path>>(
     Ok(word_clusterers)
 }
 
+fn load_word_embeddings<P: AsRef<Path>>(
+    resources_dir: &P,
+    metadata: &ResourcesMetadata,
+) -> Result<HashMap<String, Arc<dyn WordEmbeddings>>> {
+    let mut word_embeddings: HashMap<String, Arc<dyn WordEmbeddings>> = HashMap::new();
+    if let Some(embeddings_names) = metadata.word_embeddings.as_ref() {
+        let word_embeddings_directory = resources_dir.as_ref().join("word_embeddings");
+        for embeddings_name in embeddings_names {
+            let embeddings_path = word_embeddings_directory
+                .join(embeddings_name.clone())
+                .with_extension("txt");
+            info!(
+                "Loading word embeddings '{}' ({:?}) ...",
+                embeddings_name, embeddings_path
+            );
+            let embeddings_reader = File::open(&embeddings_path).with_context(|| {
+                format!("Cannot open word embeddings file {:?}", embeddings_path)
+            })?;
+            let embeddings =
+                HashMapWordEmbeddings::from_reader(embeddings_reader).with_context(|| {
+                    format!("Cannot read word embeddings file {:?}", embeddings_path)
+                })?;
+            word_embeddings.insert(embeddings_name.to_string(), Arc::new(embeddings));
+            info!("Word embeddings '{}' loaded", embeddings_name);
+        }
+    }
+    Ok(word_embeddings)
+}
+
 fn load_stop_words<P: AsRef<Path>>(
     resources_dir: &P,
     metadata: &ResourcesMetadata,
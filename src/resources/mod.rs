@@ -1,21 +1,33 @@
 pub mod gazetteer;
 pub mod loading;
+pub mod normalizer;
 pub mod stemmer;
 pub mod word_clusterer;
+pub mod word_embeddings;
 
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 use self::gazetteer::Gazetteer;
+use self::normalizer::Normalizer;
 use self::stemmer::Stemmer;
 use self::word_clusterer::WordClusterer;
+use self::word_embeddings::WordEmbeddings;
 use super::entity_parser::{BuiltinEntityParser, CustomEntityParser};
+use super::models::Entity;
+use super::slot_filler::FeatureRegistry;
+use super::utils::EntityName;
 
 pub struct SharedResources {
     pub builtin_entity_parser: Arc<dyn BuiltinEntityParser>,
     pub custom_entity_parser: Arc<dyn CustomEntityParser>,
     pub gazetteers: HashMap<String, Arc<dyn Gazetteer>>,
     pub stemmer: Option<Arc<dyn Stemmer>>,
+    pub normalizer: Arc<dyn Normalizer>,
+    /// Custom entities of the dataset, which are only known when loading an engine
+    pub custom_entities: HashMap<EntityName, Entity>,
     pub word_clusterers: HashMap<String, Arc<dyn WordClusterer>>,
+    pub word_embeddings: HashMap<String, Arc<dyn WordEmbeddings>>,
     pub stop_words: HashSet<String>,
+    pub feature_registry: Arc<FeatureRegistry>,
 }
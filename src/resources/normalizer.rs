@@ -0,0 +1,219 @@
+use std::ops::Range;
+
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::models::NormalizationConfig;
+
+/// Normalization of the texts in which entities are matched
+///
+/// Texts are normalized cluster by cluster, a cluster being a character followed by its
+/// combining marks, so that the ranges of the entities found in a normalized text can be mapped
+/// back to the original text.
+pub trait Normalizer: Send + Sync {
+    /// Appends the normalized form of a cluster, which may be empty or contain several
+    /// characters
+    fn normalize_cluster(&self, cluster: &str, normalized: &mut String);
+}
+
+/// Text normalized along with, for each of its characters, the character range of the
+/// original cluster it comes from
+#[derive(Debug, Clone, PartialEq)]
+pub struct NormalizedText {
+    pub text: String,
+    pub alignment: Vec<Range<usize>>,
+}
+
+impl NormalizedText {
+    /// Maps a character range of the normalized text to the original text
+    pub fn original_range(&self, range: &Range<usize>) -> Range<usize> {
+        if range.start >= range.end || range.end > self.alignment.len() {
+            return range.clone();
+        }
+        self.alignment[range.start].start..self.alignment[range.end - 1].end
+    }
+}
+
+pub fn normalize(normalizer: &dyn Normalizer, text: &str) -> String {
+    normalize_with_alignment(normalizer, text).text
+}
+
+pub fn normalize_with_alignment(normalizer: &dyn Normalizer, text: &str) -> NormalizedText {
+    let mut normalized_text = String::with_capacity(text.len());
+    let mut alignment = Vec::with_capacity(text.len());
+    let mut cluster = String::new();
+    let mut cluster_start = 0;
+    for (char_index, c) in text.chars().enumerate() {
+        if !is_combining_mark(c) && !cluster.is_empty() {
+            normalize_cluster(
+                normalizer,
+                &cluster,
+                cluster_start..char_index,
+                &mut normalized_text,
+                &mut alignment,
+            );
+            cluster.clear();
+            cluster_start = char_index;
+        }
+        cluster.push(c);
+    }
+    if !cluster.is_empty() {
+        let cluster_end = cluster_start + cluster.chars().count();
+        normalize_cluster(
+            normalizer,
+            &cluster,
+            cluster_start..cluster_end,
+            &mut normalized_text,
+            &mut alignment,
+        );
+    }
+    NormalizedText {
+        text: normalized_text,
+        alignment,
+    }
+}
+
+fn normalize_cluster(
+    normalizer: &dyn Normalizer,
+    cluster: &str,
+    cluster_range: Range<usize>,
+    normalized_text: &mut String,
+    alignment: &mut Vec<Range<usize>>,
+) {
+    let normalized_start = normalized_text.len();
+    normalizer.normalize_cluster(cluster, normalized_text);
+    let nb_normalized_chars = normalized_text[normalized_start..].chars().count();
+    alignment.resize(alignment.len() + nb_normalized_chars, cluster_range);
+}
+
+/// Normalizer which composes the characters of texts with their combining marks, lowercases
+/// them and applies the normalizations of its configuration
+#[derive(Debug, Clone, Default)]
+pub struct TextNormalizer {
+    config: NormalizationConfig,
+}
+
+impl TextNormalizer {
+    pub fn new(config: NormalizationConfig) -> Self {
+        Self { config }
+    }
+}
+
+impl Normalizer for TextNormalizer {
+    fn normalize_cluster(&self, cluster: &str, normalized: &mut String) {
+        let mut chars: Vec<char> = if self.config.unicode_nfkc {
+            cluster.nfkc().collect()
+        } else {
+            cluster.nfc().collect()
+        };
+        if self.config.fold_accents {
+            chars = chars.into_iter().flat_map(fold_accents).collect();
+        }
+        if self.config.fold_punctuation {
+            chars = chars.into_iter().flat_map(fold_punctuation).collect();
+        }
+        normalized.extend(chars.into_iter().flat_map(char::to_lowercase));
+    }
+}
+
+fn fold_accents(c: char) -> Vec<char> {
+    match c {
+        'æ' => vec!['a', 'e'],
+        'Æ' => vec!['A', 'E'],
+        'œ' => vec!['o', 'e'],
+        'Œ' => vec!['O', 'E'],
+        'ß' => vec!['s', 's'],
+        'ø' => vec!['o'],
+        'Ø' => vec!['O'],
+        'ł' => vec!['l'],
+        'Ł' => vec!['L'],
+        _ => std::iter::once(c)
+            .nfd()
+            .filter(|c| !is_combining_mark(*c))
+            .collect(),
+    }
+}
+
+fn fold_punctuation(c: char) -> Vec<char> {
+    match c {
+        '\u{2018}' | '\u{2019}' | '\u{201B}' | '\u{02BC}' | '\u{2032}' | '`' | '´' => vec!['\''],
+        '\u{201C}' | '\u{201D}' | '\u{201E}' | '«' | '»' => vec!['"'],
+        '\u{2010}' | '\u{2011}' | '\u{2012}' | '\u{2013}' | '\u{2014}' | '\u{2015}' => vec!['-'],
+        '\u{00A0}' | '\u{202F}' | '\u{2007}' => vec![' '],
+        '\u{2026}' => vec!['.', '.', '.'],
+        _ => vec![c],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_normalizer() {
+        // Given
+        let normalizer = TextNormalizer::new(NormalizationConfig {
+            unicode_nfkc: true,
+            fold_accents: true,
+            fold_punctuation: true,
+        });
+
+        // When
+        let normalized_text = normalize_with_alignment(&normalizer, "L\u{2019}Œuvre du Café");
+
+        // Then
+        let expected_text = NormalizedText {
+            text: "l'oeuvre du cafe".to_string(),
+            alignment: vec![0, 1, 2, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14]
+                .into_iter()
+                .map(|char_index| char_index..char_index + 1)
+                .collect(),
+        };
+        assert_eq!(expected_text, normalized_text);
+        assert_eq!(2..7, normalized_text.original_range(&(2..8)));
+    }
+
+    #[test]
+    fn test_text_normalizer_composes_combining_marks() {
+        // Given
+        let normalizer = TextNormalizer::default();
+
+        // When
+        let normalized_text = normalize_with_alignment(&normalizer, "Cafe\u{301} noir");
+
+        // Then
+        assert_eq!("caf\u{e9} noir", normalized_text.text);
+        assert_eq!(0..5, normalized_text.original_range(&(0..4)));
+        assert_eq!(6..10, normalized_text.original_range(&(5..9)));
+    }
+
+    #[test]
+    fn test_nfkc_is_applied_to_whole_clusters() {
+        // Given
+        let normalizer = TextNormalizer::new(NormalizationConfig {
+            unicode_nfkc: true,
+            fold_accents: false,
+            fold_punctuation: false,
+        });
+
+        // When
+        let normalized_text = normalize_with_alignment(&normalizer, "\u{FB01}n\u{E9}e\u{301}");
+
+        // Then
+        assert_eq!("fin\u{e9}\u{e9}", normalized_text.text);
+        assert_eq!(0..2, normalized_text.original_range(&(0..3)));
+        assert_eq!(3..5, normalized_text.original_range(&(4..5)));
+    }
+
+    #[test]
+    fn test_default_text_normalizer_only_lowercases() {
+        // Given
+        let normalizer = TextNormalizer::default();
+
+        // When
+        let normalized_text = normalize(&normalizer, "L\u{2019}Œuvre du Café");
+
+        // Then
+        assert_eq!("l\u{2019}œuvre du café", normalized_text);
+    }
+}
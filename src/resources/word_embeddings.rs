@@ -0,0 +1,134 @@
+use anyhow::{bail, Context, Result};
+use ndarray::prelude::*;
+use snips_nlu_utils::string::hash_str_to_i32;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
+use std::str::FromStr;
+
+pub trait WordEmbeddings: Send + Sync {
+    fn dimension(&self) -> usize;
+    fn get_embedding(&self, word: &str) -> Option<ArrayView1<'_, f32>>;
+}
+
+pub struct HashMapWordEmbeddings {
+    dimension: usize,
+    values: HashMap<i32, Array1<f32>>,
+}
+
+impl HashMapWordEmbeddings {
+    /// Reads dense word vectors in the text format used by GloVe and word2vec
+    ///
+    /// Each line contains a word followed by the components of its vector, separated by
+    /// whitespaces. The optional word2vec header line is skipped.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self> {
+        let mut dimension: Option<usize> = None;
+        let mut values = HashMap::new();
+        for (line_index, line) in BufReader::new(reader).lines().enumerate() {
+            let line = line?;
+            let elements: Vec<&str> = line.split_whitespace().collect();
+            if elements.is_empty() {
+                continue;
+            }
+            if line_index == 0 && elements.len() == 2 && usize::from_str(elements[1]).is_ok() {
+                continue;
+            }
+            let vector = elements[1..]
+                .iter()
+                .map(|v| f32::from_str(v))
+                .collect::<std::result::Result<Vec<f32>, _>>()
+                .with_context(|| format!("Invalid word vector at line {}", line_index + 1))?;
+            let expected_dimension = *dimension.get_or_insert(vector.len());
+            if vector.len() != expected_dimension {
+                bail!(
+                    "Expected word vector of dimension {} at line {} but found {}",
+                    expected_dimension,
+                    line_index + 1,
+                    vector.len()
+                );
+            }
+            values.insert(hash_str_to_i32(elements[0]), Array::from_vec(vector));
+        }
+        Ok(Self {
+            dimension: dimension.unwrap_or(0),
+            values,
+        })
+    }
+}
+
+impl WordEmbeddings for HashMapWordEmbeddings {
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn get_embedding(&self, word: &str) -> Option<ArrayView1<'_, f32>> {
+        self.values
+            .get(&hash_str_to_i32(word))
+            .map(|vector| vector.view())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::array;
+
+    #[test]
+    fn test_hashmap_word_embeddings() {
+        // Given
+        let embeddings: &[u8] = r#"
+hello 0.5 -1.0 2.0
+world 1.5 0.0 -0.25
+"#
+        .as_ref();
+
+        // When
+        let word_embeddings = HashMapWordEmbeddings::from_reader(embeddings);
+
+        // Then
+        assert!(word_embeddings.is_ok());
+        let word_embeddings = word_embeddings.unwrap();
+        assert_eq!(3, word_embeddings.dimension());
+        assert_eq!(
+            Some(array![0.5, -1.0, 2.0].view()),
+            word_embeddings.get_embedding("hello")
+        );
+        assert_eq!(
+            Some(array![1.5, 0.0, -0.25].view()),
+            word_embeddings.get_embedding("world")
+        );
+        assert_eq!(None, word_embeddings.get_embedding("unknown"));
+    }
+
+    #[test]
+    fn test_hashmap_word_embeddings_with_header() {
+        // Given
+        let embeddings: &[u8] = r#"2 2
+hello 0.5 -1.0
+world 1.5 0.0
+"#
+        .as_ref();
+
+        // When
+        let word_embeddings = HashMapWordEmbeddings::from_reader(embeddings).unwrap();
+
+        // Then
+        assert_eq!(2, word_embeddings.dimension());
+        assert_eq!(None, word_embeddings.get_embedding("2"));
+    }
+
+    #[test]
+    fn test_hashmap_word_embeddings_with_inconsistent_dimensions() {
+        // Given
+        let embeddings: &[u8] = r#"
+hello 0.5 -1.0 2.0
+world 1.5 0.0
+"#
+        .as_ref();
+
+        // When
+        let word_embeddings = HashMapWordEmbeddings::from_reader(embeddings);
+
+        // Then
+        assert!(word_embeddings.is_err());
+    }
+}
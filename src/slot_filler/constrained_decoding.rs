@@ -0,0 +1,464 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::sync::Arc;
+
+use itertools::Itertools;
+use snips_nlu_ontology::BuiltinEntityKind;
+use snips_nlu_utils::token::Token;
+
+use crate::entity_parser::{BuiltinEntityParser, CustomEntityParser, CustomEntityParserUsage};
+use crate::resources::stemmer::Stemmer;
+use crate::resources::SharedResources;
+use crate::slot_filler::crf_utils::{get_scheme_prefix, get_token_indexes, TaggingScheme, OUTSIDE};
+use crate::slot_filler::features::transform_tokens;
+use crate::slot_filler::features_utils::initial_string_from_tokens;
+use crate::slot_utils::InternalSlot;
+use crate::utils::EntityName;
+use anyhow::Result;
+
+/// Char ranges of the entity matches found in an input, for each constrained entity
+pub type EntitySpans = HashMap<EntityName, Vec<Range<usize>>>;
+
+/// Restricts the slots of builtin entities and of non automatically extensible custom entities
+/// to exact entity matches
+///
+/// Entities which are not automatically extensible are the ones flagged as such in the dataset
+/// metadata.
+pub struct DecodingConstraints {
+    non_extensible_entities: HashSet<EntityName>,
+    builtin_entity_parser: Arc<dyn BuiltinEntityParser>,
+    custom_entity_parser: Arc<dyn CustomEntityParser>,
+    stemmer: Option<Arc<dyn Stemmer>>,
+}
+
+impl DecodingConstraints {
+    pub fn new(shared_resources: &SharedResources) -> Self {
+        Self {
+            non_extensible_entities: shared_resources
+                .custom_entities
+                .iter()
+                .filter(|(_, entity)| !entity.automatically_extensible)
+                .map(|(entity_name, _)| entity_name.clone())
+                .collect(),
+            builtin_entity_parser: shared_resources.builtin_entity_parser.clone(),
+            custom_entity_parser: shared_resources.custom_entity_parser.clone(),
+            stemmer: shared_resources.stemmer.clone(),
+        }
+    }
+
+    /// Extracts the spans of the constrained entities among `entities`
+    ///
+    /// Every constrained entity has an entry in the result, possibly empty, while unconstrained
+    /// entities have none.
+    pub fn get_entity_spans<'a, I>(
+        &self,
+        text: &str,
+        tokens: &[Token],
+        entities: I,
+    ) -> Result<EntitySpans>
+    where
+        I: IntoIterator<Item = &'a EntityName>,
+    {
+        let entities: HashSet<&EntityName> = entities.into_iter().collect();
+        let builtin_entity_kinds = entities
+            .iter()
+            .filter_map(|entity| BuiltinEntityKind::from_identifier(entity).ok())
+            .collect_vec();
+        let custom_entities = entities
+            .iter()
+            .filter(|entity| self.non_extensible_entities.contains(**entity))
+            .map(|entity| entity.to_string())
+            .collect_vec();
+
+        let mut entity_spans: EntitySpans = builtin_entity_kinds
+            .iter()
+            .map(|kind| kind.identifier().to_string())
+            .chain(custom_entities.iter().cloned())
+            .map(|entity| (entity, vec![]))
+            .collect();
+        if !builtin_entity_kinds.is_empty() {
+            for builtin_entity in self.builtin_entity_parser.extract_entities(
+                text,
+                Some(&builtin_entity_kinds),
+                true,
+                0,
+            )? {
+                if let Some(spans) = entity_spans.get_mut(builtin_entity.entity_kind.identifier()) {
+                    spans.push(builtin_entity.range);
+                }
+            }
+        }
+        if !custom_entities.is_empty() {
+            for (entity, range) in extract_custom_entities(
+                &*self.custom_entity_parser,
+                self.stemmer.as_ref(),
+                text,
+                tokens,
+                &custom_entities,
+            )? {
+                if let Some(spans) = entity_spans.get_mut(&entity) {
+                    spans.push(range);
+                }
+            }
+        }
+        Ok(entity_spans)
+    }
+}
+
+/// Extracts the custom entities in the text and, when the custom entity parser matches stemmed
+/// values, in the stemmed tokens
+///
+/// The ranges of the entities found in the stemmed tokens are mapped back to the text.
+pub fn extract_custom_entities(
+    custom_entity_parser: &dyn CustomEntityParser,
+    stemmer: Option<&Arc<dyn Stemmer>>,
+    text: &str,
+    tokens: &[Token],
+    custom_entities: &[EntityName],
+) -> Result<Vec<(EntityName, Range<usize>)>> {
+    let parser_usage = custom_entity_parser.parser_usage();
+    let opt_stemmer = match parser_usage {
+        CustomEntityParserUsage::WithoutStems => None,
+        _ => stemmer,
+    };
+    let mut entities = vec![];
+    if parser_usage != CustomEntityParserUsage::WithStems || opt_stemmer.is_none() {
+        entities.extend(
+            custom_entity_parser
+                .extract_entities(text, Some(custom_entities), 0)?
+                .into_iter()
+                .map(|entity| (entity.entity_identifier, entity.range)),
+        );
+    }
+    if let Some(stemmer) = opt_stemmer {
+        let stemmed_tokens = transform_tokens(tokens, Some(stemmer.clone()));
+        let stemmed_text = initial_string_from_tokens(&stemmed_tokens);
+        for entity in
+            custom_entity_parser.extract_entities(&stemmed_text, Some(custom_entities), 0)?
+        {
+            // Spans are mapped back to the tokens of the original text
+            let token_indexes = get_token_indexes(&stemmed_tokens, &entity.range);
+            if let (Some(first), Some(last)) = (token_indexes.first(), token_indexes.last()) {
+                let range = tokens[*first].char_range.start..tokens[*last].char_range.end;
+                entities.push((entity.entity_identifier, range));
+            }
+        }
+    }
+    Ok(entities)
+}
+
+/// A slot is valid when its entity is unconstrained or when it matches one of its entity spans
+pub fn is_valid_slot(slot: &InternalSlot, entity_spans: &EntitySpans) -> bool {
+    entity_spans
+        .get(&slot.entity)
+        .map(|spans| spans.contains(&slot.char_range))
+        .unwrap_or(true)
+}
+
+/// Maximum number of tag sequences built by `repair_tags`
+const MAX_REPAIRED_CANDIDATES: usize = 16;
+
+/// Builds alternative tag sequences in which each invalid slot is either removed or realigned
+/// on an overlapping span of its entity
+///
+/// Slots are repaired in order and only the first `MAX_REPAIRED_CANDIDATES` sequences are kept
+/// after each slot, the first sequence realigning all the slots and the last one removing them.
+pub fn repair_tags(
+    tags: &[String],
+    tokens: &[Token],
+    slots: &[InternalSlot],
+    entity_spans: &EntitySpans,
+    tagging_scheme: TaggingScheme,
+) -> Vec<Vec<String>> {
+    let mut candidates = vec![tags.to_vec()];
+    for slot in slots
+        .iter()
+        .filter(|slot| !is_valid_slot(slot, entity_spans))
+    {
+        let slot_indexes = get_token_indexes(tokens, &slot.char_range);
+        let realigned_indexes = entity_spans
+            .get(&slot.entity)
+            .into_iter()
+            .flatten()
+            .filter(|span| span.start < slot.char_range.end && span.end > slot.char_range.start)
+            .map(|span| get_token_indexes(tokens, span))
+            .filter(|indexes| !indexes.is_empty())
+            .collect_vec();
+
+        candidates = candidates
+            .into_iter()
+            .flat_map(|candidate| {
+                let mut removed = candidate;
+                for index in slot_indexes.iter() {
+                    removed[*index] = OUTSIDE.to_string();
+                }
+                let realigned = realigned_indexes
+                    .iter()
+                    .map(|indexes| {
+                        let mut realigned = removed.clone();
+                        for index in indexes.iter() {
+                            realigned[*index] = format!(
+                                "{}{}",
+                                get_scheme_prefix(*index, indexes, tagging_scheme),
+                                slot.slot_name
+                            );
+                        }
+                        realigned
+                    })
+                    .collect_vec();
+                realigned.into_iter().chain(vec![removed])
+            })
+            .collect();
+        if candidates.len() > MAX_REPAIRED_CANDIDATES {
+            // The sequence in which all the invalid slots are removed is always kept
+            let removed = candidates.pop();
+            candidates.truncate(MAX_REPAIRED_CANDIDATES - 1);
+            candidates.extend(removed);
+        }
+    }
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter::FromIterator;
+
+    use snips_nlu_ontology::{BuiltinEntity, NumberValue, SlotValue};
+    use snips_nlu_utils::language::Language;
+    use snips_nlu_utils::token::tokenize;
+
+    use super::*;
+    use crate::entity_parser::custom_entity_parser::CustomEntity;
+    use crate::models::Entity;
+    use crate::resources::stemmer::HashMapStemmer;
+    use crate::testutils::{
+        MockedBuiltinEntityParser, MockedCustomEntityParser, SharedResourcesBuilder,
+    };
+
+    fn get_custom_entities(non_extensible_entities: &[&str]) -> HashMap<EntityName, Entity> {
+        vec!["beverage", "extensible_beverage"]
+            .into_iter()
+            .map(|entity| {
+                let automatically_extensible = !non_extensible_entities.contains(&entity);
+                (
+                    entity.to_string(),
+                    Entity {
+                        automatically_extensible,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    fn get_slot(
+        value: &str,
+        char_range: Range<usize>,
+        entity: &str,
+        slot_name: &str,
+    ) -> InternalSlot {
+        InternalSlot {
+            value: value.to_string(),
+            char_range,
+            entity: entity.to_string(),
+            slot_name: slot_name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_get_entity_spans() {
+        // Given
+        let text = "make me two cups of green tea";
+        let builtin_entity_parser = MockedBuiltinEntityParser::from_iter(vec![(
+            text.to_string(),
+            vec![BuiltinEntity {
+                value: "two".to_string(),
+                range: 8..11,
+                entity: SlotValue::Number(NumberValue { value: 2.0 }),
+                alternatives: vec![],
+                entity_kind: BuiltinEntityKind::Number,
+            }],
+        )]);
+        let custom_entity_parser = MockedCustomEntityParser::from_iter(vec![(
+            text.to_string(),
+            vec![
+                CustomEntity {
+                    value: "green tea".to_string(),
+                    resolved_value: "green tea".to_string(),
+                    alternative_resolved_values: vec![],
+                    range: 20..29,
+                    entity_identifier: "beverage".to_string(),
+                },
+                CustomEntity {
+                    value: "tea".to_string(),
+                    resolved_value: "tea".to_string(),
+                    alternative_resolved_values: vec![],
+                    range: 26..29,
+                    entity_identifier: "extensible_beverage".to_string(),
+                },
+            ],
+        )]);
+        let shared_resources = SharedResourcesBuilder::default()
+            .builtin_entity_parser(builtin_entity_parser)
+            .custom_entity_parser(custom_entity_parser)
+            .custom_entities(get_custom_entities(&["beverage"]))
+            .build();
+        let constraints = DecodingConstraints::new(&shared_resources);
+        let tokens = tokenize(text, Language::EN);
+        let entities = vec![
+            "snips/number".to_string(),
+            "beverage".to_string(),
+            "extensible_beverage".to_string(),
+        ];
+
+        // When
+        let entity_spans = constraints
+            .get_entity_spans(text, &tokens, &entities)
+            .unwrap();
+
+        // Then
+        let expected_entity_spans: EntitySpans = vec![
+            ("snips/number".to_string(), vec![8..11]),
+            ("beverage".to_string(), vec![20..29]),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(expected_entity_spans, entity_spans);
+    }
+
+    #[test]
+    fn test_get_entity_spans_with_stems() {
+        // Given
+        let text = "two green teas";
+        let mut custom_entity_parser = MockedCustomEntityParser::from_iter(vec![(
+            "two green tea".to_string(),
+            vec![CustomEntity {
+                value: "green tea".to_string(),
+                resolved_value: "green tea".to_string(),
+                alternative_resolved_values: vec![],
+                range: 4..13,
+                entity_identifier: "beverage".to_string(),
+            }],
+        )]);
+        custom_entity_parser.parser_usage = Some(CustomEntityParserUsage::WithStems);
+        let stemmer = HashMapStemmer::from_iter(vec![("teas".to_string(), "tea".to_string())]);
+        let shared_resources = SharedResourcesBuilder::default()
+            .custom_entity_parser(custom_entity_parser)
+            .stemmer(stemmer)
+            .custom_entities(get_custom_entities(&["beverage"]))
+            .build();
+        let constraints = DecodingConstraints::new(&shared_resources);
+        let tokens = tokenize(text, Language::EN);
+
+        // When
+        let entity_spans = constraints
+            .get_entity_spans(text, &tokens, &["beverage".to_string()])
+            .unwrap();
+
+        // Then
+        let expected_entity_spans: EntitySpans = vec![("beverage".to_string(), vec![4..14])]
+            .into_iter()
+            .collect();
+        assert_eq!(expected_entity_spans, entity_spans);
+    }
+
+    #[test]
+    fn test_is_valid_slot() {
+        // Given
+        let entity_spans: EntitySpans = vec![("beverage".to_string(), vec![20..29])]
+            .into_iter()
+            .collect();
+        let exact_slot = get_slot("green tea", 20..29, "beverage", "beverage_type");
+        let partial_slot = get_slot("tea", 26..29, "beverage", "beverage_type");
+        let unconstrained_slot = get_slot("two", 8..11, "snips/number", "number_of_cups");
+
+        // When / Then
+        assert!(is_valid_slot(&exact_slot, &entity_spans));
+        assert!(!is_valid_slot(&partial_slot, &entity_spans));
+        assert!(is_valid_slot(&unconstrained_slot, &entity_spans));
+    }
+
+    #[test]
+    fn test_repair_tags() {
+        // Given
+        let text = "make me two cups of green tea";
+        let tokens = tokenize(text, Language::EN);
+        let tags = vec![
+            OUTSIDE.to_string(),
+            OUTSIDE.to_string(),
+            "B-number_of_cups".to_string(),
+            OUTSIDE.to_string(),
+            OUTSIDE.to_string(),
+            OUTSIDE.to_string(),
+            "B-beverage_type".to_string(),
+        ];
+        let slots = vec![
+            get_slot("two", 8..11, "snips/number", "number_of_cups"),
+            get_slot("tea", 26..29, "beverage", "beverage_type"),
+        ];
+        let entity_spans: EntitySpans = vec![("beverage".to_string(), vec![20..29])]
+            .into_iter()
+            .collect();
+
+        // When
+        let candidates = repair_tags(&tags, &tokens, &slots, &entity_spans, TaggingScheme::BIO);
+
+        // Then
+        let expected_candidates = vec![
+            vec![
+                OUTSIDE.to_string(),
+                OUTSIDE.to_string(),
+                "B-number_of_cups".to_string(),
+                OUTSIDE.to_string(),
+                OUTSIDE.to_string(),
+                "B-beverage_type".to_string(),
+                "I-beverage_type".to_string(),
+            ],
+            vec![
+                OUTSIDE.to_string(),
+                OUTSIDE.to_string(),
+                "B-number_of_cups".to_string(),
+                OUTSIDE.to_string(),
+                OUTSIDE.to_string(),
+                OUTSIDE.to_string(),
+                OUTSIDE.to_string(),
+            ],
+        ];
+        assert_eq!(expected_candidates, candidates);
+    }
+
+    #[test]
+    fn test_repair_tags_should_bound_the_number_of_candidates() {
+        // Given
+        let text = "a b c d e f g h i j";
+        let tokens = tokenize(text, Language::EN);
+        let tags = tokens
+            .iter()
+            .map(|_| "B-beverage_type".to_string())
+            .collect_vec();
+        let slots = tokens
+            .iter()
+            .map(|token| {
+                get_slot(
+                    &token.value,
+                    token.char_range.clone(),
+                    "beverage",
+                    "beverage_type",
+                )
+            })
+            .collect_vec();
+        let spans = tokens
+            .iter()
+            .tuple_windows()
+            .map(|(token, next_token)| token.char_range.start..next_token.char_range.end)
+            .collect_vec();
+        let entity_spans: EntitySpans = vec![("beverage".to_string(), spans)].into_iter().collect();
+
+        // When
+        let candidates = repair_tags(&tags, &tokens, &slots, &entity_spans, TaggingScheme::BIO);
+
+        // Then
+        assert_eq!(MAX_REPAIRED_CANDIDATES, candidates.len());
+        let removed_tags = tokens.iter().map(|_| OUTSIDE.to_string()).collect_vec();
+        assert_eq!(Some(&removed_tags), candidates.last());
+    }
+}
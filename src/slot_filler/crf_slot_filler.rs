@@ -12,11 +12,12 @@ use snips_nlu_utils::language::Language as NluUtilsLanguage;
 use snips_nlu_utils::token::{tokenize, Token};
 
 use crate::language::FromLanguage;
-use crate::models::SlotFillerModel;
+use crate::models::{ProcessingUnitMetadata, SlotFillerModel};
 use crate::resources::SharedResources;
+use crate::slot_filler::constrained_decoding::*;
 use crate::slot_filler::crf_utils::*;
 use crate::slot_filler::feature_processor::ProbabilisticFeatureProcessor;
-use crate::slot_filler::SlotFiller;
+use crate::slot_filler::{SlotFiller, TaggedToken, TokenTagging};
 use crate::slot_utils::*;
 use crate::utils::{EntityName, SlotName};
 use anyhow::{anyhow, Context, Result};
@@ -27,6 +28,7 @@ pub struct CRFSlotFiller {
     tagger: Option<Mutex<CRFSuiteTagger>>,
     feature_processor: Option<ProbabilisticFeatureProcessor>,
     slot_name_mapping: HashMap<SlotName, EntityName>,
+    decoding_constraints: Option<DecodingConstraints>,
 }
 
 impl CRFSlotFiller {
@@ -62,6 +64,11 @@ impl CRFSlotFiller {
                 (None, None)
             };
         let language = Language::from_str(&model.language_code).map_err(|e| anyhow!(e))?;
+        let decoding_constraints = if model.config.constrained_decoding {
+            Some(DecodingConstraints::new(&shared_resources))
+        } else {
+            None
+        };
 
         info!("CRF slot filler loaded");
 
@@ -71,6 +78,7 @@ impl CRFSlotFiller {
             tagger,
             feature_processor,
             slot_name_mapping,
+            decoding_constraints,
         })
     }
 }
@@ -80,6 +88,11 @@ impl SlotFiller for CRFSlotFiller {
         self.tagging_scheme
     }
 
+    fn has_slots(&self) -> bool {
+        // No tagger defined corresponds to an intent without slots
+        self.tagger.is_some()
+    }
+
     fn get_slots(&self, text: &str) -> Result<Vec<InternalSlot>> {
         debug!("Extracting slots...");
         let slots = if let (Some(ref tagger), Some(ref feature_processor)) =
@@ -90,21 +103,13 @@ impl SlotFiller for CRFSlotFiller {
                 return Ok(vec![]);
             }
             let features = feature_processor.compute_features(&&*tokens)?;
-            let tags = tagger
+            let entity_spans = self.get_entity_spans(text, &tokens)?;
+            let tagger = tagger
                 .lock()
-                .map_err(|e| anyhow!("Poisonous mutex: {}", e))?
-                .tag(&features)?
-                .into_iter()
-                .map(|tag| decode_tag(&*tag))
-                .collect::<Result<Vec<String>>>()?;
-
-            tags_to_slots(
-                text,
-                &tokens,
-                &tags,
-                self.tagging_scheme,
-                &self.slot_name_mapping,
-            )?
+                .map_err(|e| anyhow!("Poisonous mutex: {}", e))?;
+            let (_, slots) =
+                self.decode(text, &tokens, &features, &tagger, entity_spans.as_ref())?;
+            slots
         } else {
             vec![]
         };
@@ -112,6 +117,78 @@ impl SlotFiller for CRFSlotFiller {
         Ok(slots)
     }
 
+    fn get_slots_nbest(&self, text: &str, n: usize) -> Result<Vec<InternalSlotSequence>> {
+        if n == 0 {
+            return Ok(vec![]);
+        }
+        let (tagger, feature_processor) =
+            match (self.tagger.as_ref(), self.feature_processor.as_ref()) {
+                (Some(tagger), Some(feature_processor)) => (tagger, feature_processor),
+                // No tagger defined corresponds to an intent without slots
+                _ => {
+                    return Ok(vec![InternalSlotSequence {
+                        slots: vec![],
+                        probability: 1.0,
+                    }])
+                }
+            };
+        let tokens = tokenize(text, NluUtilsLanguage::from_language(self.language));
+        if tokens.is_empty() {
+            return Ok(vec![InternalSlotSequence {
+                slots: vec![],
+                probability: 1.0,
+            }]);
+        }
+        let features = feature_processor.compute_features(&&*tokens)?;
+        let entity_spans = self.get_entity_spans(text, &tokens)?;
+        let tagger = tagger
+            .lock()
+            .map_err(|e| anyhow!("Poisonous mutex: {}", e))?;
+        let (best_tags, _) =
+            self.decode(text, &tokens, &features, &tagger, entity_spans.as_ref())?;
+        let best_tags = encode_known_tags(&tagger, &best_tags)?;
+        let (labels, marginals) = compute_marginals(&tagger, &features)?;
+
+        // Candidates are generated from the marginals and then ranked by their exact
+        // probability, the Viterbi sequence, or the constrained one, being always part of them
+        let beam_width = n * NBEST_BEAM_WIDTH_FACTOR;
+        let candidates = marginal_beam_search(&marginals, beam_width)
+            .into_iter()
+            .map(|label_indexes| {
+                label_indexes
+                    .into_iter()
+                    .map(|i| labels[i].clone())
+                    .collect()
+            })
+            .chain(vec![best_tags]);
+
+        let mut slot_sequences = vec![];
+        for (probability, tags) in rank_by_probability(&tagger, candidates)? {
+            let decoded_tags = tags
+                .iter()
+                .map(|tag| decode_tag(tag))
+                .collect::<Result<Vec<String>>>()?;
+            let slots = tags_to_slots(
+                text,
+                &tokens,
+                &decoded_tags,
+                self.tagging_scheme,
+                &self.slot_name_mapping,
+            )?;
+            let is_valid = entity_spans
+                .as_ref()
+                .map(|spans| slots.iter().all(|slot| is_valid_slot(slot, spans)))
+                .unwrap_or(true);
+            if is_valid {
+                slot_sequences.push(InternalSlotSequence { slots, probability });
+            }
+            if slot_sequences.len() == n {
+                break;
+            }
+        }
+        Ok(slot_sequences)
+    }
+
     fn get_sequence_probability(&self, tokens: &[Token], tags: Vec<String>) -> Result<f64> {
         if let (Some(ref tagger), Some(ref feature_processor)) =
             (self.tagger.as_ref(), self.feature_processor.as_ref())
@@ -120,24 +197,7 @@ impl SlotFiller for CRFSlotFiller {
             let tagger = tagger
                 .lock()
                 .map_err(|e| anyhow!("poisonous mutex: {}", e))?;
-            let tagger_labels = tagger
-                .labels()?
-                .into_iter()
-                .map(|label| decode_tag(&*label))
-                .collect::<Result<Vec<String>>>()?;
-            let tagger_labels_slice = tagger_labels.iter().map(|l| &**l).collect_vec();
-            // Substitute tags that were not seen during training
-            let cleaned_tags = tags
-                .iter()
-                .map(|t| {
-                    if tagger_labels.contains(t) {
-                        t
-                    } else {
-                        get_substitution_label(&*tagger_labels_slice)
-                    }
-                })
-                .map(|t| encode_tag(t))
-                .collect_vec();
+            let cleaned_tags = encode_known_tags(&tagger, &tags)?;
             tagger.set(&features)?;
             Ok(tagger.probability(&cleaned_tags)?)
         } else {
@@ -149,9 +209,219 @@ impl SlotFiller for CRFSlotFiller {
                 .unwrap_or(1.0))
         }
     }
+
+    fn get_slots_with_probability(&self, text: &str) -> Result<InternalSlotSequence> {
+        let (tagger, feature_processor) =
+            match (self.tagger.as_ref(), self.feature_processor.as_ref()) {
+                (Some(tagger), Some(feature_processor)) => (tagger, feature_processor),
+                // No tagger defined corresponds to an intent without slots
+                _ => {
+                    return Ok(InternalSlotSequence {
+                        slots: vec![],
+                        probability: 1.0,
+                    })
+                }
+            };
+        let tokens = tokenize(text, NluUtilsLanguage::from_language(self.language));
+        if tokens.is_empty() {
+            return Ok(InternalSlotSequence {
+                slots: vec![],
+                probability: 1.0,
+            });
+        }
+        let features = feature_processor.compute_features(&&*tokens)?;
+        let entity_spans = self.get_entity_spans(text, &tokens)?;
+        let tagger = tagger
+            .lock()
+            .map_err(|e| anyhow!("Poisonous mutex: {}", e))?;
+        let (tags, slots) =
+            self.decode(text, &tokens, &features, &tagger, entity_spans.as_ref())?;
+        // The decoded sequence is scored as is, rather than re-encoded from its slots
+        let encoded_tags = encode_known_tags(&tagger, &tags)?;
+        tagger.set(&features)?;
+        let probability = tagger.probability(&encoded_tags)?;
+        Ok(InternalSlotSequence { slots, probability })
+    }
+
+    fn tag_tokens(&self, text: &str) -> Result<TokenTagging> {
+        let tokens = tokenize(text, NluUtilsLanguage::from_language(self.language));
+        let tags_with_marginals = match (self.tagger.as_ref(), self.feature_processor.as_ref()) {
+            (Some(tagger), Some(feature_processor)) if !tokens.is_empty() => {
+                let features = feature_processor.compute_features(&&*tokens)?;
+                let entity_spans = self.get_entity_spans(text, &tokens)?;
+                let tagger = tagger
+                    .lock()
+                    .map_err(|e| anyhow!("Poisonous mutex: {}", e))?;
+                let (tags, _) =
+                    self.decode(text, &tokens, &features, &tagger, entity_spans.as_ref())?;
+                // Marginals are the ones of the unconstrained model
+                let (labels, marginals) = compute_marginals(&tagger, &features)?;
+                let decoded_labels = labels
+                    .iter()
+                    .map(|label| decode_tag(label))
+                    .collect::<Result<Vec<String>>>()?;
+                tags.into_iter()
+                    .zip(marginals.into_iter())
+                    .map(|(tag, token_marginals)| {
+                        Ok((
+                            tag,
+                            decoded_labels
+                                .iter()
+                                .cloned()
+                                .zip(token_marginals.into_iter())
+                                .collect(),
+                        ))
+                    })
+                    .collect::<Result<Vec<_>>>()?
+            }
+            // No tagger defined corresponds to an intent without slots
+            _ => tokens
+                .iter()
+                .map(|_| {
+                    (
+                        OUTSIDE.to_string(),
+                        vec![(OUTSIDE.to_string(), 1.0)].into_iter().collect(),
+                    )
+                })
+                .collect(),
+        };
+        let tagged_tokens = tokens
+            .into_iter()
+            .zip(tags_with_marginals.into_iter())
+            .map(|(token, (tag, marginals))| TaggedToken {
+                value: token.value,
+                range: token.range,
+                char_range: token.char_range,
+                tag,
+                marginals,
+            })
+            .collect();
+        Ok(TokenTagging {
+            slot_filler: ProcessingUnitMetadata::CrfSlotFiller,
+            tagging_scheme: self.tagging_scheme,
+            tokens: tagged_tokens,
+        })
+    }
 }
 
 impl CRFSlotFiller {
+    /// Returns the spans of the constrained entities, when decoding constraints are enabled
+    fn get_entity_spans(&self, text: &str, tokens: &[Token]) -> Result<Option<EntitySpans>> {
+        self.decoding_constraints
+            .as_ref()
+            .map(|constraints| {
+                constraints.get_entity_spans(text, tokens, self.slot_name_mapping.values())
+            })
+            .transpose()
+    }
+
+    /// Returns the decoded tags of the Viterbi sequence along with its slots
+    ///
+    /// When some of the slots do not match the provided entity spans, the Viterbi sequence is
+    /// replaced by the result of `apply_decoding_constraints`.
+    fn decode(
+        &self,
+        text: &str,
+        tokens: &[Token],
+        features: &[Vec<(String, String)>],
+        tagger: &CRFSuiteTagger,
+        entity_spans: Option<&EntitySpans>,
+    ) -> Result<(Vec<String>, Vec<InternalSlot>)> {
+        let viterbi_tags = tagger.tag(features)?;
+        let decoded_tags = viterbi_tags
+            .iter()
+            .map(|tag| decode_tag(tag))
+            .collect::<Result<Vec<String>>>()?;
+        let slots = tags_to_slots(
+            text,
+            tokens,
+            &decoded_tags,
+            self.tagging_scheme,
+            &self.slot_name_mapping,
+        )?;
+        match entity_spans {
+            Some(entity_spans) if !slots.iter().all(|slot| is_valid_slot(slot, entity_spans)) => {
+                self.apply_decoding_constraints(
+                    entity_spans,
+                    text,
+                    tokens,
+                    features,
+                    tagger,
+                    viterbi_tags,
+                    slots,
+                )
+            }
+            _ => Ok((decoded_tags, slots)),
+        }
+    }
+
+    /// Returns the most probable sequence whose slots all match their entity spans, along with
+    /// its slots
+    ///
+    /// This is a heuristic rather than a constrained Viterbi: the valid sequence is searched
+    /// among a beam of candidates built from the token marginals and among repairs of the
+    /// Viterbi sequence, the invalid slots being dropped when none of them is valid.
+    #[allow(clippy::too_many_arguments)]
+    fn apply_decoding_constraints(
+        &self,
+        entity_spans: &EntitySpans,
+        text: &str,
+        tokens: &[Token],
+        features: &[Vec<(String, String)>],
+        tagger: &CRFSuiteTagger,
+        viterbi_tags: Vec<String>,
+        viterbi_slots: Vec<InternalSlot>,
+    ) -> Result<(Vec<String>, Vec<InternalSlot>)> {
+        let (labels, marginals) = compute_marginals(tagger, features)?;
+        let decoded_viterbi_tags = viterbi_tags
+            .iter()
+            .map(|tag| decode_tag(tag))
+            .collect::<Result<Vec<String>>>()?;
+        let repaired_candidates = repair_tags(
+            &decoded_viterbi_tags,
+            tokens,
+            &viterbi_slots,
+            entity_spans,
+            self.tagging_scheme,
+        )
+        .into_iter()
+        .map(|tags| tags.iter().map(|tag| encode_tag(tag)).collect_vec())
+        // Sequences containing labels unseen during training cannot be scored
+        .filter(|tags| tags.iter().all(|tag| labels.contains(tag)));
+        let candidates = marginal_beam_search(&marginals, CONSTRAINED_DECODING_BEAM_WIDTH)
+            .into_iter()
+            .map(|label_indexes| {
+                label_indexes
+                    .into_iter()
+                    .map(|i| labels[i].clone())
+                    .collect_vec()
+            })
+            .chain(repaired_candidates);
+
+        for (_, tags) in rank_by_probability(tagger, candidates)? {
+            let decoded_tags = tags
+                .iter()
+                .map(|tag| decode_tag(tag))
+                .collect::<Result<Vec<String>>>()?;
+            let slots = tags_to_slots(
+                text,
+                tokens,
+                &decoded_tags,
+                self.tagging_scheme,
+                &self.slot_name_mapping,
+            )?;
+            if slots.iter().all(|slot| is_valid_slot(slot, entity_spans)) {
+                return Ok((decoded_tags, slots));
+            }
+        }
+        let valid_slots = viterbi_slots
+            .into_iter()
+            .filter(|slot| is_valid_slot(slot, entity_spans))
+            .collect_vec();
+        let valid_tags = slots_to_tags(tokens, &valid_slots, self.tagging_scheme);
+        Ok((valid_tags, valid_slots))
+    }
+
     pub fn compute_features(&self, text: &str) -> Result<Vec<Vec<(String, String)>>> {
         let tokens = tokenize(text, NluUtilsLanguage::from_language(self.language));
         if tokens.is_empty() {
@@ -167,6 +437,101 @@ impl CRFSlotFiller {
     }
 }
 
+const NBEST_BEAM_WIDTH_FACTOR: usize = 3;
+const CONSTRAINED_DECODING_BEAM_WIDTH: usize = 10;
+
+/// Encodes decoded tags so that they can be scored by the tagger, the tags which were not seen
+/// during training, such as the ones of repaired slots, being substituted
+fn encode_known_tags(tagger: &CRFSuiteTagger, tags: &[String]) -> Result<Vec<String>> {
+    let tagger_labels = tagger
+        .labels()?
+        .into_iter()
+        .map(|label| decode_tag(&*label))
+        .collect::<Result<Vec<String>>>()?;
+    let tagger_labels_slice = tagger_labels.iter().map(|l| &**l).collect_vec();
+    Ok(tags
+        .iter()
+        .map(|t| {
+            if tagger_labels.contains(t) {
+                &**t
+            } else {
+                get_substitution_label(&*tagger_labels_slice)
+            }
+        })
+        .map(|t| encode_tag(t))
+        .collect())
+}
+
+/// Returns the labels of the tagger along with the marginal probability of each label at each
+/// token
+fn compute_marginals(
+    tagger: &CRFSuiteTagger,
+    features: &[Vec<(String, String)>],
+) -> Result<(Vec<String>, Vec<Vec<f64>>)> {
+    tagger.set(features)?;
+    let labels = tagger.labels()?;
+    let marginals = (0..features.len())
+        .map(|token_index| {
+            labels
+                .iter()
+                .map(|label| Ok(tagger.marginal(label, token_index as i32)?))
+                .collect::<Result<Vec<f64>>>()
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok((labels, marginals))
+}
+
+/// Beam search over the label sequences, in which partial sequences are pruned using the
+/// product of the token marginals
+///
+/// The marginals ignore the transitions between labels, hence the returned candidates are not
+/// the exact best sequences and must be ranked with `rank_by_probability`. The last token is
+/// not pruned, so that up to `beam_width` times the number of labels candidates are returned.
+///
+/// `marginals[i][j]` is the marginal probability of the label `j` at the token `i`.
+fn marginal_beam_search(marginals: &[Vec<f64>], beam_width: usize) -> Vec<Vec<usize>> {
+    let mut beam: Vec<(f64, Vec<usize>)> = vec![(0.0, vec![])];
+    for (token_index, token_marginals) in marginals.iter().enumerate() {
+        let expanded_beam = beam
+            .into_iter()
+            .flat_map(|(log_proba, label_indexes)| {
+                token_marginals
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, marginal)| **marginal > 0.0)
+                    .map(move |(label_index, marginal)| {
+                        let mut new_label_indexes = label_indexes.clone();
+                        new_label_indexes.push(label_index);
+                        (log_proba + marginal.ln(), new_label_indexes)
+                    })
+            })
+            .sorted_by(|(proba_a, _), (proba_b, _)| proba_b.partial_cmp(proba_a).unwrap());
+        beam = if token_index + 1 < marginals.len() {
+            expanded_beam.take(beam_width).collect()
+        } else {
+            expanded_beam.collect()
+        };
+    }
+    beam.into_iter()
+        .map(|(_, label_indexes)| label_indexes)
+        .collect()
+}
+
+/// Deduplicates the candidate tag sequences and sorts them by decreasing exact probability
+fn rank_by_probability<I>(tagger: &CRFSuiteTagger, candidates: I) -> Result<Vec<(f64, Vec<String>)>>
+where
+    I: IntoIterator<Item = Vec<String>>,
+{
+    Ok(candidates
+        .into_iter()
+        .unique()
+        .map(|tags| Ok((tagger.probability(&tags)?, tags)))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .sorted_by(|(proba_a, _), (proba_b, _)| proba_b.partial_cmp(proba_a).unwrap())
+        .collect())
+}
+
 // We need to use base64 encoding to ensure ascii encoding because of encoding issues in
 // python-crfsuite
 
@@ -212,4 +577,106 @@ mod tests {
         }];
         assert_eq!(expected_slots, slots);
     }
+
+    #[test]
+    fn test_get_slots_nbest() {
+        // Given
+        let trained_engine_path = Path::new("data")
+            .join("tests")
+            .join("models")
+            .join("nlu_engine_beverage");
+
+        let slot_filler_path = trained_engine_path
+            .join("probabilistic_intent_parser")
+            .join("slot_filler_0");
+
+        let resources = load_engine_shared_resources(trained_engine_path).unwrap();
+        let slot_filler = CRFSlotFiller::from_path(slot_filler_path, resources).unwrap();
+
+        // When
+        let text = "make me two cups of coffee";
+        let slot_sequences = slot_filler.get_slots_nbest(text, 3).unwrap();
+
+        // Then
+        assert_eq!(3, slot_sequences.len());
+        assert_eq!(
+            slot_filler.get_slots(text).unwrap(),
+            slot_sequences[0].slots
+        );
+        assert!(slot_sequences
+            .windows(2)
+            .all(|pair| pair[0].probability >= pair[1].probability));
+        assert!(slot_sequences[0].probability <= 1.0);
+    }
+
+    #[test]
+    fn test_get_slots_with_probability() {
+        // Given
+        let trained_engine_path = Path::new("data")
+            .join("tests")
+            .join("models")
+            .join("nlu_engine_beverage");
+
+        let slot_filler_path = trained_engine_path
+            .join("probabilistic_intent_parser")
+            .join("slot_filler_0");
+
+        let resources = load_engine_shared_resources(trained_engine_path).unwrap();
+        let slot_filler = CRFSlotFiller::from_path(slot_filler_path, resources).unwrap();
+        let text = "make me two cups of coffee";
+
+        // When
+        let slot_sequence = slot_filler.get_slots_with_probability(text).unwrap();
+
+        // Then
+        let best_sequence = slot_filler.get_slots_nbest(text, 1).unwrap().remove(0);
+        assert_eq!(best_sequence.slots, slot_sequence.slots);
+        assert!((best_sequence.probability - slot_sequence.probability).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_tag_tokens() {
+        // Given
+        let trained_engine_path = Path::new("data")
+            .join("tests")
+            .join("models")
+            .join("nlu_engine_beverage");
+
+        let slot_filler_path = trained_engine_path
+            .join("probabilistic_intent_parser")
+            .join("slot_filler_0");
+
+        let resources = load_engine_shared_resources(trained_engine_path).unwrap();
+        let slot_filler = CRFSlotFiller::from_path(slot_filler_path, resources).unwrap();
+
+        // When
+        let tagging = slot_filler
+            .tag_tokens("make me two cups of coffee")
+            .unwrap();
+
+        // Then
+        assert_eq!(ProcessingUnitMetadata::CrfSlotFiller, tagging.slot_filler);
+        assert_eq!(TaggingScheme::BIO, tagging.tagging_scheme);
+        let actual_tags = tagging.tokens.iter().map(|token| &*token.tag).collect_vec();
+        let expected_tags = vec!["O", "O", "B-number_of_cups", "O", "O", "O"];
+        assert_eq!(expected_tags, actual_tags);
+        assert_eq!(8..11, tagging.tokens[2].char_range);
+        assert!(tagging.tokens.iter().all(|token| {
+            let total_marginal: f64 = token.marginals.values().sum();
+            (total_marginal - 1.0).abs() < 1e-6
+        }));
+    }
+
+    #[test]
+    fn test_marginal_beam_search() {
+        // Given
+        let marginals = vec![vec![0.7, 0.2, 0.1, 0.0], vec![0.4, 0.6, 0.0, 0.0]];
+
+        // When
+        let candidates = marginal_beam_search(&marginals, 2);
+
+        // Then
+        let expected_candidates = vec![vec![0, 1], vec![0, 0], vec![1, 1], vec![1, 0]];
+        assert_eq!(expected_candidates, candidates);
+    }
 }
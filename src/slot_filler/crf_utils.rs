@@ -12,7 +12,7 @@ const LAST_PREFIX: &str = "L-";
 const UNIT_PREFIX: &str = "U-";
 pub const OUTSIDE: &str = "O";
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum TaggingScheme {
     IO,
     BIO,
@@ -215,6 +215,38 @@ pub fn get_scheme_prefix(index: usize, indexes: &[usize], tagging_scheme: Taggin
     }
 }
 
+/// Indexes of the tokens lying within the char range
+pub fn get_token_indexes(tokens: &[Token], char_range: &Range<usize>) -> Vec<usize> {
+    tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, token)| {
+            token.char_range.start >= char_range.start && token.char_range.end <= char_range.end
+        })
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// Encodes slots as a sequence of tags, which is the reverse operation of `tags_to_slots`
+pub fn slots_to_tags(
+    tokens: &[Token],
+    slots: &[InternalSlot],
+    tagging_scheme: TaggingScheme,
+) -> Vec<String> {
+    let mut tags = vec![OUTSIDE.to_string(); tokens.len()];
+    for slot in slots {
+        let indexes = get_token_indexes(tokens, &slot.char_range);
+        for index in indexes.iter() {
+            tags[*index] = format!(
+                "{}{}",
+                get_scheme_prefix(*index, &indexes, tagging_scheme),
+                slot.slot_name
+            );
+        }
+    }
+    tags
+}
+
 #[cfg(test)]
 mod tests {
     use itertools::Itertools;
@@ -695,6 +727,46 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_slots_to_tags() {
+        // Given
+        let tokens = tokenize("i want to go to new york city", Language::EN);
+        let slots = vec![InternalSlot {
+            value: "new york city".to_string(),
+            char_range: 16..29,
+            entity: "location".to_string(),
+            slot_name: "destination".to_string(),
+        }];
+
+        // When
+        let bio_tags = slots_to_tags(&tokens, &slots, TaggingScheme::BIO);
+        let bilou_tags = slots_to_tags(&tokens, &slots, TaggingScheme::BILOU);
+
+        // Then
+        let expected_bio_tags = vec![
+            "O",
+            "O",
+            "O",
+            "O",
+            "O",
+            "B-destination",
+            "I-destination",
+            "I-destination",
+        ];
+        let expected_bilou_tags = vec![
+            "O",
+            "O",
+            "O",
+            "O",
+            "O",
+            "B-destination",
+            "I-destination",
+            "L-destination",
+        ];
+        assert_eq!(expected_bio_tags, bio_tags);
+        assert_eq!(expected_bilou_tags, bilou_tags);
+    }
+
     #[test]
     fn test_is_start_of_bio_slot() {
         // Given
@@ -0,0 +1,399 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::ops::Range;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use itertools::Itertools;
+use log::{debug, info};
+use snips_nlu_ontology::{BuiltinEntityKind, Language};
+use snips_nlu_utils::language::Language as NluUtilsLanguage;
+use snips_nlu_utils::range::ranges_overlap;
+use snips_nlu_utils::string::substring_with_char_range;
+use snips_nlu_utils::token::{tokenize, Token};
+
+use crate::entity_parser::{BuiltinEntityParser, CustomEntityParser};
+use crate::language::FromLanguage;
+use crate::models::{EntityMatchSlotFillerModel, EntityOverlapStrategy, ProcessingUnitMetadata};
+use crate::resources::stemmer::Stemmer;
+use crate::resources::SharedResources;
+use crate::slot_filler::constrained_decoding::extract_custom_entities;
+use crate::slot_filler::crf_utils::{slots_to_tags, TaggingScheme};
+use crate::slot_filler::features_utils::initial_string_from_tokens;
+use crate::slot_filler::{SlotFiller, TaggedToken, TokenTagging};
+use crate::slot_utils::{InternalSlot, InternalSlotSequence};
+use crate::utils::{deduplicate_overlapping_items, EntityName, SlotName};
+use anyhow::{anyhow, bail, Context, Result};
+
+/// Slot filler which assigns slots directly from the custom and builtin entity matches
+///
+/// Each entity must be mapped to a single slot of the intent.
+pub struct EntityMatchSlotFiller {
+    language: Language,
+    entity_slot_mapping: HashMap<EntityName, SlotName>,
+    overlap_strategy: EntityOverlapStrategy,
+    builtin_entity_parser: Arc<dyn BuiltinEntityParser>,
+    custom_entity_parser: Arc<dyn CustomEntityParser>,
+    stemmer: Option<Arc<dyn Stemmer>>,
+}
+
+impl EntityMatchSlotFiller {
+    pub fn from_path<P: AsRef<Path>>(
+        path: P,
+        shared_resources: Arc<SharedResources>,
+    ) -> Result<Self> {
+        info!("Loading entity match slot filler ({:?}) ...", path.as_ref());
+        let slot_filler_model_path = path.as_ref().join("slot_filler.json");
+        let model_file = File::open(&slot_filler_model_path).with_context(|| {
+            format!(
+                "Cannot open EntityMatchSlotFiller file '{:?}'",
+                &slot_filler_model_path
+            )
+        })?;
+        let model: EntityMatchSlotFillerModel = serde_json::from_reader(model_file)
+            .with_context(|| "Cannot deserialize EntityMatchSlotFiller json data")?;
+        let slot_filler = Self::new(model, shared_resources)?;
+        info!("Entity match slot filler loaded");
+        Ok(slot_filler)
+    }
+
+    pub fn new(
+        model: EntityMatchSlotFillerModel,
+        shared_resources: Arc<SharedResources>,
+    ) -> Result<Self> {
+        let language = Language::from_str(&model.language_code).map_err(|e| anyhow!(e))?;
+        let mut entity_slot_mapping = HashMap::with_capacity(model.slot_name_mapping.len());
+        for (slot_name, entity) in model.slot_name_mapping.into_iter() {
+            if let Some(other_slot_name) = entity_slot_mapping.get(&entity) {
+                bail!(
+                    "Entity '{}' of intent '{}' is mapped to several slots: '{}' and '{}'",
+                    entity,
+                    model.intent,
+                    other_slot_name,
+                    slot_name
+                );
+            }
+            entity_slot_mapping.insert(entity, slot_name);
+        }
+        Ok(Self {
+            language,
+            entity_slot_mapping,
+            overlap_strategy: model.config.overlap_strategy,
+            builtin_entity_parser: shared_resources.builtin_entity_parser.clone(),
+            custom_entity_parser: shared_resources.custom_entity_parser.clone(),
+            stemmer: shared_resources.stemmer.clone(),
+        })
+    }
+}
+
+impl SlotFiller for EntityMatchSlotFiller {
+    fn get_tagging_scheme(&self) -> TaggingScheme {
+        TaggingScheme::BIO
+    }
+
+    fn has_slots(&self) -> bool {
+        !self.entity_slot_mapping.is_empty()
+    }
+
+    fn get_slots(&self, text: &str) -> Result<Vec<InternalSlot>> {
+        debug!("Extracting slots...");
+        let builtin_entity_kinds = self
+            .entity_slot_mapping
+            .keys()
+            .filter_map(|entity| BuiltinEntityKind::from_identifier(entity).ok())
+            .collect_vec();
+        let custom_entities = self
+            .entity_slot_mapping
+            .keys()
+            .filter(|entity| BuiltinEntityKind::from_identifier(entity).is_err())
+            .cloned()
+            .collect_vec();
+
+        let mut matches: Vec<EntityMatch> = vec![];
+        if !builtin_entity_kinds.is_empty() {
+            matches.extend(
+                self.builtin_entity_parser
+                    .extract_entities(text, Some(&builtin_entity_kinds), true, 0)?
+                    .into_iter()
+                    .map(|entity| EntityMatch {
+                        entity: entity.entity_kind.identifier().to_string(),
+                        range: entity.range,
+                        is_builtin: true,
+                    }),
+            );
+        }
+        if !custom_entities.is_empty() {
+            let tokens = tokenize(text, NluUtilsLanguage::from_language(self.language));
+            matches.extend(
+                extract_custom_entities(
+                    &*self.custom_entity_parser,
+                    self.stemmer.as_ref(),
+                    text,
+                    &tokens,
+                    &custom_entities,
+                )?
+                .into_iter()
+                .map(|(entity, range)| EntityMatch {
+                    entity,
+                    range,
+                    is_builtin: false,
+                }),
+            );
+        }
+        let matches = matches
+            .into_iter()
+            .filter(|entity_match| self.entity_slot_mapping.contains_key(&entity_match.entity))
+            .collect_vec();
+
+        let overlap_strategy = self.overlap_strategy;
+        let slots = deduplicate_overlapping_items(
+            matches,
+            |lhs, rhs| ranges_overlap(&lhs.range, &rhs.range),
+            |entity_match| entity_match.sort_key(overlap_strategy),
+        )
+        .into_iter()
+        .sorted_by_key(|entity_match| entity_match.range.start)
+        .map(|entity_match| InternalSlot {
+            value: substring_with_char_range(text.to_string(), &entity_match.range),
+            char_range: entity_match.range,
+            slot_name: self.entity_slot_mapping[&entity_match.entity].clone(),
+            entity: entity_match.entity,
+        })
+        .collect_vec();
+        debug!("{} slots extracted", slots.len());
+        Ok(slots)
+    }
+
+    fn get_slots_nbest(&self, text: &str, n: usize) -> Result<Vec<InternalSlotSequence>> {
+        if n == 0 {
+            return Ok(vec![]);
+        }
+        Ok(vec![self.get_slots_with_probability(text)?])
+    }
+
+    fn get_sequence_probability(&self, tokens: &[Token], tags: Vec<String>) -> Result<f64> {
+        if tokens.is_empty() {
+            return Ok(1.0);
+        }
+        let slots = self.get_slots(&initial_string_from_tokens(tokens))?;
+        let expected_tags = slots_to_tags(tokens, &slots, self.get_tagging_scheme());
+        Ok(if expected_tags == tags { 1.0 } else { 0.0 })
+    }
+
+    fn get_slots_with_probability(&self, text: &str) -> Result<InternalSlotSequence> {
+        Ok(InternalSlotSequence {
+            slots: self.get_slots(text)?,
+            probability: 1.0,
+        })
+    }
+
+    fn tag_tokens(&self, text: &str) -> Result<TokenTagging> {
+        let tokens = tokenize(text, NluUtilsLanguage::from_language(self.language));
+        let slots = self.get_slots(text)?;
+        let tags = slots_to_tags(&tokens, &slots, self.get_tagging_scheme());
+        let tagged_tokens = tokens
+            .into_iter()
+            .zip(tags.into_iter())
+            .map(|(token, tag)| TaggedToken {
+                value: token.value,
+                range: token.range,
+                char_range: token.char_range,
+                marginals: vec![(tag.clone(), 1.0)].into_iter().collect(),
+                tag,
+            })
+            .collect();
+        Ok(TokenTagging {
+            slot_filler: ProcessingUnitMetadata::EntityMatchSlotFiller,
+            tagging_scheme: self.get_tagging_scheme(),
+            tokens: tagged_tokens,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct EntityMatch {
+    entity: EntityName,
+    range: Range<usize>,
+    is_builtin: bool,
+}
+
+impl EntityMatch {
+    /// Matches with the lowest key are kept first when resolving overlaps
+    fn sort_key(&self, overlap_strategy: EntityOverlapStrategy) -> (i32, i32, usize) {
+        let length = -(self.range.len() as i32);
+        let start = self.range.start;
+        match overlap_strategy {
+            EntityOverlapStrategy::LongestMatch => (0, length, start),
+            EntityOverlapStrategy::LeftmostMatch => (start as i32, length, start),
+            EntityOverlapStrategy::CustomEntitiesFirst => (self.is_builtin as i32, length, start),
+            EntityOverlapStrategy::BuiltinEntitiesFirst => (!self.is_builtin as i32, length, start),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter::FromIterator;
+
+    use snips_nlu_ontology::{BuiltinEntity, NumberValue, SlotValue};
+
+    use super::*;
+    use crate::entity_parser::custom_entity_parser::CustomEntity;
+    use crate::entity_parser::CustomEntityParserUsage;
+    use crate::models::EntityMatchSlotFillerConfiguration;
+    use crate::resources::stemmer::HashMapStemmer;
+    use crate::testutils::{
+        MockedBuiltinEntityParser, MockedCustomEntityParser, SharedResourcesBuilder,
+    };
+
+    fn get_sample_slot_filler(overlap_strategy: EntityOverlapStrategy) -> EntityMatchSlotFiller {
+        let text = "play the 7 wonders album";
+        let builtin_entity_parser = MockedBuiltinEntityParser::from_iter(vec![(
+            text.to_string(),
+            vec![BuiltinEntity {
+                value: "7".to_string(),
+                range: 9..10,
+                entity: SlotValue::Number(NumberValue { value: 7.0 }),
+                alternatives: vec![],
+                entity_kind: BuiltinEntityKind::Number,
+            }],
+        )]);
+        let custom_entity_parser = MockedCustomEntityParser::from_iter(vec![(
+            text.to_string(),
+            vec![
+                CustomEntity {
+                    value: "the 7 wonders".to_string(),
+                    resolved_value: "The 7 Wonders".to_string(),
+                    alternative_resolved_values: vec![],
+                    range: 5..18,
+                    entity_identifier: "album".to_string(),
+                },
+                CustomEntity {
+                    value: "album".to_string(),
+                    resolved_value: "album".to_string(),
+                    alternative_resolved_values: vec![],
+                    range: 19..24,
+                    entity_identifier: "unmapped_entity".to_string(),
+                },
+            ],
+        )]);
+        let shared_resources = SharedResourcesBuilder::default()
+            .builtin_entity_parser(builtin_entity_parser)
+            .custom_entity_parser(custom_entity_parser)
+            .build();
+        let model = EntityMatchSlotFillerModel {
+            language_code: "en".to_string(),
+            intent: "PlayAlbum".to_string(),
+            slot_name_mapping: vec![
+                ("album_name".to_string(), "album".to_string()),
+                ("number".to_string(), "snips/number".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+            config: EntityMatchSlotFillerConfiguration { overlap_strategy },
+        };
+        EntityMatchSlotFiller::new(model, Arc::new(shared_resources)).unwrap()
+    }
+
+    #[test]
+    fn test_get_slots() {
+        // Given
+        let slot_filler = get_sample_slot_filler(EntityOverlapStrategy::LongestMatch);
+
+        // When
+        let slots = slot_filler.get_slots("play the 7 wonders album").unwrap();
+
+        // Then
+        let expected_slots = vec![InternalSlot {
+            value: "the 7 wonders".to_string(),
+            char_range: 5..18,
+            entity: "album".to_string(),
+            slot_name: "album_name".to_string(),
+        }];
+        assert_eq!(expected_slots, slots);
+    }
+
+    #[test]
+    fn test_get_slots_with_builtin_entities_first() {
+        // Given
+        let slot_filler = get_sample_slot_filler(EntityOverlapStrategy::BuiltinEntitiesFirst);
+
+        // When
+        let slots = slot_filler.get_slots("play the 7 wonders album").unwrap();
+
+        // Then
+        let expected_slots = vec![InternalSlot {
+            value: "7".to_string(),
+            char_range: 9..10,
+            entity: "snips/number".to_string(),
+            slot_name: "number".to_string(),
+        }];
+        assert_eq!(expected_slots, slots);
+    }
+
+    #[test]
+    fn test_get_slots_with_stems() {
+        // Given
+        let mut custom_entity_parser = MockedCustomEntityParser::from_iter(vec![(
+            "two green tea".to_string(),
+            vec![CustomEntity {
+                value: "green tea".to_string(),
+                resolved_value: "green tea".to_string(),
+                alternative_resolved_values: vec![],
+                range: 4..13,
+                entity_identifier: "beverage".to_string(),
+            }],
+        )]);
+        custom_entity_parser.parser_usage = Some(CustomEntityParserUsage::WithStems);
+        let stemmer = HashMapStemmer::from_iter(vec![("teas".to_string(), "tea".to_string())]);
+        let shared_resources = SharedResourcesBuilder::default()
+            .custom_entity_parser(custom_entity_parser)
+            .stemmer(stemmer)
+            .build();
+        let model = EntityMatchSlotFillerModel {
+            language_code: "en".to_string(),
+            intent: "MakeTea".to_string(),
+            slot_name_mapping: vec![("beverage_type".to_string(), "beverage".to_string())]
+                .into_iter()
+                .collect(),
+            config: EntityMatchSlotFillerConfiguration::default(),
+        };
+        let slot_filler = EntityMatchSlotFiller::new(model, Arc::new(shared_resources)).unwrap();
+
+        // When
+        let slots = slot_filler.get_slots("two green teas").unwrap();
+
+        // Then
+        let expected_slots = vec![InternalSlot {
+            value: "green teas".to_string(),
+            char_range: 4..14,
+            entity: "beverage".to_string(),
+            slot_name: "beverage_type".to_string(),
+        }];
+        assert_eq!(expected_slots, slots);
+    }
+
+    #[test]
+    fn test_entity_mapped_to_several_slots() {
+        // Given
+        let model = EntityMatchSlotFillerModel {
+            language_code: "en".to_string(),
+            intent: "BookFlight".to_string(),
+            slot_name_mapping: vec![
+                ("departure".to_string(), "city".to_string()),
+                ("destination".to_string(), "city".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+            config: EntityMatchSlotFillerConfiguration::default(),
+        };
+        let shared_resources = Arc::new(SharedResourcesBuilder::default().build());
+
+        // When
+        let slot_filler = EntityMatchSlotFiller::new(model, shared_resources);
+
+        // Then
+        assert!(slot_filler.is_err());
+    }
+}
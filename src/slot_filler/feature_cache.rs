@@ -0,0 +1,185 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use itertools::Itertools;
+use snips_nlu_utils::token::Token;
+
+use anyhow::Result;
+
+/// Attributes fired by a feature on each token of an input
+type FeatureValues = Vec<Vec<(String, String)>>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct FeatureCacheKey {
+    feature: String,
+    tokens: String,
+}
+
+thread_local! {
+    static FEATURE_CACHE: RefCell<Option<HashMap<FeatureCacheKey, FeatureValues>>> =
+        const { RefCell::new(None) };
+}
+
+/// Restores the feature cache which was active before the scope, even when the scope panics
+struct FeatureCacheGuard {
+    previous_cache: Option<Option<HashMap<FeatureCacheKey, FeatureValues>>>,
+}
+
+impl Drop for FeatureCacheGuard {
+    fn drop(&mut self) {
+        if let Some(previous_cache) = self.previous_cache.take() {
+            FEATURE_CACHE.with(|cache| *cache.borrow_mut() = previous_cache);
+        }
+    }
+}
+
+/// Runs `f` with a cache of the CRF features computed by the slot fillers of the current
+/// thread, so that the features which do not depend on the intent are computed once per parse
+///
+/// The cache is dropped when `f` returns, hence features depending on the entity parsers
+/// never outlive a parse. Nested scopes share the cache of the outermost one.
+pub fn with_feature_cache<T, F>(f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    if FEATURE_CACHE.with(|cache| cache.borrow().is_some()) {
+        return f();
+    }
+    with_new_feature_cache(f)
+}
+
+/// Runs `f` with an empty feature cache, the features cached by an enclosing scope being
+/// neither read nor updated
+pub(crate) fn with_new_feature_cache<T, F>(f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    let previous_cache = FEATURE_CACHE.with(|cache| cache.replace(Some(HashMap::new())));
+    let _guard = FeatureCacheGuard {
+        previous_cache: Some(previous_cache),
+    };
+    f()
+}
+
+/// Returns the attributes of the feature identified by `feature_key` on all the tokens,
+/// computing them with `producer` when they are not cached or when no cache is active
+pub(crate) fn get_or_compute_feature<F>(
+    feature_key: &str,
+    tokens: &[Token],
+    producer: F,
+) -> Result<FeatureValues>
+where
+    F: FnOnce() -> Result<FeatureValues>,
+{
+    if !FEATURE_CACHE.with(|cache| cache.borrow().is_some()) {
+        return producer();
+    }
+    let cache_key = FeatureCacheKey {
+        feature: feature_key.to_string(),
+        tokens: get_tokens_key(tokens),
+    };
+    let cached_values = FEATURE_CACHE.with(|cache| {
+        cache
+            .borrow()
+            .as_ref()
+            .and_then(|cache| cache.get(&cache_key).cloned())
+    });
+    if let Some(values) = cached_values {
+        return Ok(values);
+    }
+    // The cache is not borrowed while computing, as some features parse entities
+    let values = producer()?;
+    FEATURE_CACHE.with(|cache| {
+        if let Some(cache) = cache.borrow_mut().as_mut() {
+            cache.insert(cache_key, values.clone());
+        }
+    });
+    Ok(values)
+}
+
+/// Features may depend on the token positions, which are thus part of the key
+fn get_tokens_key(tokens: &[Token]) -> String {
+    tokens
+        .iter()
+        .map(|token| {
+            format!(
+                "{}@{}:{}",
+                token.value, token.char_range.start, token.char_range.end
+            )
+        })
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::Cell;
+
+    use snips_nlu_utils::language::Language;
+    use snips_nlu_utils::token::tokenize;
+
+    #[test]
+    fn test_feature_cache_computes_features_once_per_scope() {
+        // Given
+        let tokens = tokenize("hello world", Language::EN);
+        let nb_computations = Cell::new(0);
+        let producer = || {
+            nb_computations.set(nb_computations.get() + 1);
+            Ok(vec![
+                vec![("is_first".to_string(), "1".to_string())],
+                vec![],
+            ])
+        };
+
+        // When
+        let (first_values, second_values) = with_feature_cache(|| {
+            let first_values = get_or_compute_feature("is_first", &tokens, producer).unwrap();
+            let second_values =
+                with_feature_cache(|| get_or_compute_feature("is_first", &tokens, producer))
+                    .unwrap();
+            get_or_compute_feature("is_last", &tokens, producer).unwrap();
+            (first_values, second_values)
+        });
+        let nb_computations_in_scope = nb_computations.get();
+        with_feature_cache(|| get_or_compute_feature("is_first", &tokens, producer)).unwrap();
+        get_or_compute_feature("is_first", &tokens, producer).unwrap();
+
+        // Then
+        assert_eq!(first_values, second_values);
+        assert_eq!(2, nb_computations_in_scope);
+        assert_eq!(4, nb_computations.get());
+    }
+
+    #[test]
+    fn test_new_feature_cache_is_isolated() {
+        // Given
+        let tokens = tokenize("hello world", Language::EN);
+        let producer = |value: &str| {
+            let value = value.to_string();
+            move || Ok(vec![vec![("entity_match".to_string(), value)], vec![]])
+        };
+
+        // When
+        let (inner_values, outer_values) = with_feature_cache(|| {
+            get_or_compute_feature("entity_match", &tokens, producer("outer")).unwrap();
+            let inner_values = with_new_feature_cache(|| {
+                get_or_compute_feature("entity_match", &tokens, producer("inner"))
+            })
+            .unwrap();
+            let outer_values =
+                get_or_compute_feature("entity_match", &tokens, producer("other")).unwrap();
+            (inner_values, outer_values)
+        });
+
+        // Then
+        let get_values = |value: &str| {
+            vec![
+                vec![("entity_match".to_string(), value.to_string())],
+                vec![],
+            ]
+        };
+        assert_eq!(get_values("inner"), inner_values);
+        assert_eq!(get_values("outer"), outer_values);
+    }
+}
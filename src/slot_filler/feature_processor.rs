@@ -1,12 +1,14 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
+use itertools::Itertools;
 use snips_nlu_utils::token::Token;
 
 use crate::models::FeatureFactory;
 use crate::resources::SharedResources;
+use crate::slot_filler::feature_cache::get_or_compute_feature;
 use crate::slot_filler::features::*;
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 
 pub struct ProbabilisticFeatureProcessor {
     features_offsetters: Vec<FeatureOffsetter>,
@@ -19,7 +21,11 @@ impl ProbabilisticFeatureProcessor {
     ) -> Result<ProbabilisticFeatureProcessor> {
         let features_offsetters = features
             .iter()
-            .map(|f| get_features(f, shared_resources.clone()))
+            .map(|f| {
+                shared_resources
+                    .feature_registry
+                    .build_features(f, shared_resources.clone())
+            })
             .collect::<Result<Vec<Vec<_>>>>()?
             .into_iter()
             .flat_map(|fs| fs)
@@ -36,12 +42,17 @@ impl ProbabilisticFeatureProcessor {
     pub fn compute_features(&self, input: &&[Token]) -> Result<Vec<Vec<(String, String)>>> {
         let mut features = vec![vec![]; input.len()];
         for offsetter in self.features_offsetters.iter() {
-            for i in 0..input.len() {
-                if let Some(value) = offsetter.feature.compute(input, i)? {
-                    offsetter.offsets_with_name().iter().for_each(|&(offset, ref key)| {
+            let attributes = get_or_compute_feature(
+                &offsetter.cache_key,
+                input,
+                || offsetter.feature.compute_attributes(input)
+            )?;
+            for (i, token_attributes) in attributes.into_iter().enumerate() {
+                for (name, value) in token_attributes {
+                    offsetter.offsets.iter().for_each(|&offset| {
                         if i as i32 - offset >= 0 && i as i32 - offset < input.len() as i32 {
                             features[(i as i32 - offset) as usize].push(
-                                (key.clone(), value.clone())
+                                (get_offset_name(&name, offset), value.clone())
                             );
                         }
                     });
@@ -55,23 +66,15 @@ impl ProbabilisticFeatureProcessor {
 struct FeatureOffsetter {
     feature: Box<dyn Feature>,
     offsets: Vec<i32>,
+    /// Identifies the feature along with its configuration in the feature cache
+    cache_key: String,
 }
 
-impl FeatureOffsetter {
-    fn offsets_with_name(&self) -> Vec<(i32, String)> {
-        self.offsets
-            .iter()
-            .map(|i| {
-                (
-                    *i,
-                    if *i == 0 {
-                        self.feature.name().to_string()
-                    } else {
-                        format!("{}[{:+}]", self.feature.name(), i)
-                    },
-                )
-            })
-            .collect()
+fn get_offset_name(name: &str, offset: i32) -> String {
+    if offset == 0 {
+        name.to_string()
+    } else {
+        format!("{}[{:+}]", name, offset)
     }
 }
 
@@ -79,17 +82,109 @@ pub trait FeatureKindRepr {
     fn feature_kind(&self) -> FeatureKind;
 }
 
-pub trait Feature: FeatureKindRepr + Send + Sync {
+/// Token level feature of the CRF slot fillers
+///
+/// Features are built from the arguments of their factory by the builders of the
+/// `FeatureRegistry`.
+pub trait Feature: Send + Sync {
+    fn name(&self) -> String;
+    /// Returns the attributes fired on each token, as pairs of attribute name and value
+    fn compute_attributes(&self, tokens: &[Token]) -> Result<Vec<Vec<(String, String)>>>;
+}
+
+/// Feature firing at most one attribute per token, named after the feature
+pub trait TokenFeature: Send + Sync {
+    fn name(&self) -> String;
+    fn compute(&self, tokens: &[Token], token_index: usize) -> Result<Option<String>>;
+    fn compute_all(&self, tokens: &[Token]) -> Result<Vec<Option<String>>> {
+        (0..tokens.len())
+            .map(|token_index| self.compute(tokens, token_index))
+            .collect()
+    }
+}
+
+impl<T: TokenFeature> Feature for T {
     fn name(&self) -> String {
-        self.feature_kind().identifier().to_string()
+        TokenFeature::name(self)
+    }
+
+    fn compute_attributes(&self, tokens: &[Token]) -> Result<Vec<Vec<(String, String)>>> {
+        let name = TokenFeature::name(self);
+        Ok(self
+            .compute_all(tokens)?
+            .into_iter()
+            .map(|opt_value| {
+                opt_value
+                    .map(|value| (name.clone(), value))
+                    .into_iter()
+                    .collect()
+            })
+            .collect())
+    }
+}
+
+type FeatureBuilder = dyn Fn(&HashMap<String, serde_json::Value>, Arc<SharedResources>) -> Result<Vec<Box<dyn Feature>>>
+    + Send
+    + Sync;
+
+/// Feature factories available to the CRF slot fillers, indexed by factory name
+///
+/// The default registry contains the builtin feature factories.
+pub struct FeatureRegistry {
+    builders: HashMap<String, Box<FeatureBuilder>>,
+}
+
+impl FeatureRegistry {
+    /// Registers a feature factory, which builds features from the arguments found in the
+    /// slot filler configuration
+    pub fn register<F>(&mut self, factory_name: &str, builder: F) -> Result<()>
+    where
+        F: Fn(
+                &HashMap<String, serde_json::Value>,
+                Arc<SharedResources>,
+            ) -> Result<Vec<Box<dyn Feature>>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        if self.builders.contains_key(factory_name) {
+            bail!("Feature factory '{}' is already registered", factory_name);
+        }
+        self.builders
+            .insert(factory_name.to_string(), Box::new(builder));
+        Ok(())
     }
+
     fn build_features(
-        args: &HashMap<String, serde_json::Value>,
+        &self,
+        f: &FeatureFactory,
         shared_resources: Arc<SharedResources>,
-    ) -> Result<Vec<Box<dyn Feature>>>
-    where
-        Self: Sized;
-    fn compute(&self, tokens: &[Token], token_index: usize) -> Result<Option<String>>;
+    ) -> Result<Vec<FeatureOffsetter>> {
+        let builder = self
+            .builders
+            .get(&f.factory_name)
+            .ok_or_else(|| anyhow!("Feature {} not implemented", f.factory_name))?;
+        Ok(builder(&f.args, shared_resources)?
+            .into_iter()
+            .map(|feature| FeatureOffsetter {
+                cache_key: get_feature_cache_key(f, &*feature),
+                feature,
+                offsets: f.offsets.clone(),
+            })
+            .collect())
+    }
+}
+
+/// Two features with the same name may have different configurations, hence the factory
+/// arguments are part of the key
+fn get_feature_cache_key(factory: &FeatureFactory, feature: &dyn Feature) -> String {
+    let args = factory
+        .args
+        .iter()
+        .sorted_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(name, value)| format!("{}={}", name, value))
+        .join(",");
+    format!("{}({})", feature.name(), args)
 }
 
 get_features!([
@@ -101,6 +196,10 @@ get_features!([
     (ShapeNgramFeature, shape_ngram),
     (PrefixFeature, prefix),
     (SuffixFeature, suffix),
+    (CharNgramFeature, char_ngram),
+    (IsCapitalizedFeature, is_capitalized),
+    (CasingPatternFeature, casing_pattern),
+    (AdjacentPunctuationFeature, adjacent_punctuation),
     (CustomEntityMatchFeature, entity_match),
     (BuiltinEntityMatchFeature, builtin_entity_match),
     (WordClusterFeature, word_cluster)
@@ -113,6 +212,8 @@ mod tests {
     use snips_nlu_utils::language::Language;
     use snips_nlu_utils::token::tokenize;
 
+    use crate::testutils::SharedResourcesBuilder;
+
     #[test]
     fn test_compute_features() {
         // Given
@@ -122,10 +223,12 @@ mod tests {
                 FeatureOffsetter {
                     offsets: vec![0],
                     feature: Box::new(IsDigitFeature {}) as Box<_>,
+                    cache_key: "is_digit".to_string(),
                 },
                 FeatureOffsetter {
                     offsets: vec![0],
                     feature: Box::new(LengthFeature {}) as Box<_>,
+                    cache_key: "length".to_string(),
                 },
             ],
         };
@@ -161,10 +264,12 @@ mod tests {
                 FeatureOffsetter {
                     offsets: vec![-2, 0, 3],
                     feature: Box::new(IsDigitFeature {}) as Box<_>,
+                    cache_key: "is_digit".to_string(),
                 },
                 FeatureOffsetter {
                     offsets: vec![-1, 1],
                     feature: Box::new(LengthFeature {}) as Box<_>,
+                    cache_key: "length".to_string(),
                 },
             ],
         };
@@ -198,4 +303,98 @@ mod tests {
         ];
         assert_eq!(expected_features, computed_features);
     }
+
+    struct IsUppercaseFeature {}
+
+    impl IsUppercaseFeature {
+        fn build_features(
+            _args: &HashMap<String, serde_json::Value>,
+            _shared_resources: Arc<SharedResources>,
+        ) -> Result<Vec<Box<dyn Feature>>> {
+            Ok(vec![Box::new(Self {})])
+        }
+    }
+
+    impl TokenFeature for IsUppercaseFeature {
+        fn name(&self) -> String {
+            "is_uppercase".to_string()
+        }
+
+        fn compute(&self, tokens: &[Token], token_index: usize) -> Result<Option<String>> {
+            Ok(
+                if tokens[token_index].value.chars().all(|c| c.is_uppercase()) {
+                    Some("1".to_string())
+                } else {
+                    None
+                },
+            )
+        }
+    }
+
+    #[test]
+    fn test_registered_feature() {
+        // Given
+        let mut feature_registry = FeatureRegistry::default();
+        feature_registry
+            .register("is_uppercase", IsUppercaseFeature::build_features)
+            .unwrap();
+        let shared_resources = SharedResourcesBuilder::default()
+            .feature_registry(feature_registry)
+            .build();
+        let feature_factories = vec![
+            FeatureFactory {
+                factory_name: "is_uppercase".to_string(),
+                offsets: vec![0],
+                args: HashMap::new(),
+            },
+            FeatureFactory {
+                factory_name: "is_digit".to_string(),
+                offsets: vec![0],
+                args: HashMap::new(),
+            },
+        ];
+        let fp = ProbabilisticFeatureProcessor::new(&feature_factories, Arc::new(shared_resources))
+            .unwrap();
+        let tokens = tokenize("call NASA 911", Language::EN);
+
+        // When
+        let computed_features = fp.compute_features(&tokens.as_slice()).unwrap();
+
+        // Then
+        let expected_features = vec![
+            vec![],
+            vec![("is_uppercase".to_string(), "1".to_string())],
+            vec![("is_digit".to_string(), "1".to_string())],
+        ];
+        assert_eq!(expected_features, computed_features);
+    }
+
+    #[test]
+    fn test_unknown_feature() {
+        // Given
+        let shared_resources = SharedResourcesBuilder::default().build();
+        let feature_factories = vec![FeatureFactory {
+            factory_name: "is_uppercase".to_string(),
+            offsets: vec![0],
+            args: HashMap::new(),
+        }];
+
+        // When
+        let fp = ProbabilisticFeatureProcessor::new(&feature_factories, Arc::new(shared_resources));
+
+        // Then
+        assert!(fp.is_err());
+    }
+
+    #[test]
+    fn test_register_existing_feature() {
+        // Given
+        let mut feature_registry = FeatureRegistry::default();
+
+        // When
+        let result = feature_registry.register("is_digit", IsUppercaseFeature::build_features);
+
+        // Then
+        assert!(result.is_err());
+    }
 }
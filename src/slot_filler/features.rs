@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::ops::Range;
 use std::sync::Arc;
 
 use itertools::Itertools;
@@ -12,21 +13,25 @@ use crate::resources::gazetteer::Gazetteer;
 use crate::resources::stemmer::Stemmer;
 use crate::resources::word_clusterer::WordClusterer;
 use crate::resources::SharedResources;
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 
 use super::crf_utils::{get_scheme_prefix, TaggingScheme};
-use super::feature_processor::{Feature, FeatureKindRepr};
-use super::features_utils::{get_word_chunk, initial_string_from_tokens};
+use super::feature_processor::{Feature, FeatureKindRepr, TokenFeature};
+use super::features_utils::{get_char_ngram_buckets, get_word_chunk, initial_string_from_tokens};
 
 pub struct IsDigitFeature {}
 
-impl Feature for IsDigitFeature {
-    fn build_features(
+impl IsDigitFeature {
+    pub fn build_features(
         _args: &HashMap<String, serde_json::Value>,
         _shared_resources: Arc<SharedResources>,
     ) -> Result<Vec<Box<dyn Feature>>> {
         Ok(vec![Box::new(Self {})])
     }
+}
+
+impl TokenFeature for IsDigitFeature {
+    feature_name!();
 
     fn compute(&self, tokens: &[Token], token_index: usize) -> Result<Option<String>> {
         Ok(
@@ -41,13 +46,17 @@ impl Feature for IsDigitFeature {
 
 pub struct LengthFeature {}
 
-impl Feature for LengthFeature {
-    fn build_features(
+impl LengthFeature {
+    pub fn build_features(
         _args: &HashMap<String, serde_json::Value>,
         _shared_resources: Arc<SharedResources>,
     ) -> Result<Vec<Box<dyn Feature>>> {
         Ok(vec![Box::new(Self {})])
     }
+}
+
+impl TokenFeature for LengthFeature {
+    feature_name!();
 
     fn compute(&self, tokens: &[Token], token_index: usize) -> Result<Option<String>> {
         Ok(Some(format!(
@@ -59,13 +68,17 @@ impl Feature for LengthFeature {
 
 pub struct IsFirstFeature {}
 
-impl Feature for IsFirstFeature {
-    fn build_features(
+impl IsFirstFeature {
+    pub fn build_features(
         _args: &HashMap<String, serde_json::Value>,
         _shared_resources: Arc<SharedResources>,
     ) -> Result<Vec<Box<dyn Feature>>> {
         Ok(vec![Box::new(Self {})])
     }
+}
+
+impl TokenFeature for IsFirstFeature {
+    feature_name!();
 
     fn compute(&self, _tokens: &[Token], token_index: usize) -> Result<Option<String>> {
         Ok(if token_index == 0 {
@@ -78,13 +91,17 @@ impl Feature for IsFirstFeature {
 
 pub struct IsLastFeature {}
 
-impl Feature for IsLastFeature {
-    fn build_features(
+impl IsLastFeature {
+    pub fn build_features(
         _args: &HashMap<String, serde_json::Value>,
         _shared_resources: Arc<SharedResources>,
     ) -> Result<Vec<Box<dyn Feature>>> {
         Ok(vec![Box::new(Self {})])
     }
+}
+
+impl TokenFeature for IsLastFeature {
+    feature_name!();
 
     fn compute(&self, tokens: &[Token], token_index: usize) -> Result<Option<String>> {
         Ok(if token_index == tokens.len() - 1 {
@@ -101,12 +118,8 @@ pub struct NgramFeature {
     opt_stemmer: Option<Arc<dyn Stemmer>>,
 }
 
-impl Feature for NgramFeature {
-    fn name(&self) -> String {
-        format!("{}_{}", self.feature_kind().identifier(), self.ngram_size)
-    }
-
-    fn build_features(
+impl NgramFeature {
+    pub fn build_features(
         args: &HashMap<String, serde_json::Value>,
         shared_resources: Arc<SharedResources>,
     ) -> Result<Vec<Box<dyn Feature>>> {
@@ -145,6 +158,10 @@ impl Feature for NgramFeature {
             opt_stemmer,
         })])
     }
+}
+
+impl TokenFeature for NgramFeature {
+    feature_name!(ngram_size);
 
     fn compute(&self, tokens: &[Token], token_index: usize) -> Result<Option<String>> {
         // TODO we should precompute the lowercase value somewhere, perhaps use NormalizedToken ?
@@ -179,18 +196,18 @@ pub struct ShapeNgramFeature {
     ngram_size: usize,
 }
 
-impl Feature for ShapeNgramFeature {
-    fn name(&self) -> String {
-        format!("{}_{}", self.feature_kind().identifier(), self.ngram_size)
-    }
-
-    fn build_features(
+impl ShapeNgramFeature {
+    pub fn build_features(
         args: &HashMap<String, serde_json::Value>,
         _shared_resources: Arc<SharedResources>,
     ) -> Result<Vec<Box<dyn Feature>>> {
         let ngram_size = parse_as_u64(args, "n")? as usize;
         Ok(vec![Box::new(Self { ngram_size })])
     }
+}
+
+impl TokenFeature for ShapeNgramFeature {
+    feature_name!(ngram_size);
 
     fn compute(&self, tokens: &[Token], token_index: usize) -> Result<Option<String>> {
         let max_len = tokens.len();
@@ -212,18 +229,18 @@ pub struct PrefixFeature {
     prefix_size: usize,
 }
 
-impl Feature for PrefixFeature {
-    fn name(&self) -> String {
-        format!("{}_{}", self.feature_kind().identifier(), self.prefix_size)
-    }
-
-    fn build_features(
+impl PrefixFeature {
+    pub fn build_features(
         args: &HashMap<String, serde_json::Value>,
         _shared_resources: Arc<SharedResources>,
     ) -> Result<Vec<Box<dyn Feature>>> {
         let prefix_size = parse_as_u64(args, "prefix_size")? as usize;
         Ok(vec![Box::new(Self { prefix_size })])
     }
+}
+
+impl TokenFeature for PrefixFeature {
+    feature_name!(prefix_size);
 
     fn compute(&self, tokens: &[Token], token_index: usize) -> Result<Option<String>> {
         let normalized = normalize(&tokens[token_index].value);
@@ -235,18 +252,18 @@ pub struct SuffixFeature {
     suffix_size: usize,
 }
 
-impl Feature for SuffixFeature {
-    fn name(&self) -> String {
-        format!("{}_{}", self.feature_kind().identifier(), self.suffix_size)
-    }
-
-    fn build_features(
+impl SuffixFeature {
+    pub fn build_features(
         args: &HashMap<String, serde_json::Value>,
         _shared_resources: Arc<SharedResources>,
     ) -> Result<Vec<Box<dyn Feature>>> {
         let suffix_size = parse_as_u64(args, "suffix_size")? as usize;
         Ok(vec![Box::new(Self { suffix_size })])
     }
+}
+
+impl TokenFeature for SuffixFeature {
+    feature_name!(suffix_size);
 
     fn compute(&self, tokens: &[Token], token_index: usize) -> Result<Option<String>> {
         let normalized = normalize(&tokens[token_index].value);
@@ -260,6 +277,158 @@ impl Feature for SuffixFeature {
     }
 }
 
+/// Fires, for each hashing bucket containing one of the character n-grams of the normalized
+/// token, an attribute named after the bucket
+pub struct CharNgramFeature {
+    ngram_size: usize,
+    num_buckets: usize,
+}
+
+impl CharNgramFeature {
+    pub fn build_features(
+        args: &HashMap<String, serde_json::Value>,
+        _shared_resources: Arc<SharedResources>,
+    ) -> Result<Vec<Box<dyn Feature>>> {
+        let ngram_size = parse_as_u64(args, "n")? as usize;
+        let num_buckets = parse_as_u64(args, "num_buckets")? as usize;
+        if ngram_size == 0 || num_buckets == 0 {
+            bail!("'n' and 'num_buckets' must be positive");
+        }
+        Ok(vec![Box::new(Self {
+            ngram_size,
+            num_buckets,
+        })])
+    }
+}
+
+impl Feature for CharNgramFeature {
+    feature_name!(ngram_size);
+
+    fn compute_attributes(&self, tokens: &[Token]) -> Result<Vec<Vec<(String, String)>>> {
+        let name = self.name();
+        Ok(tokens
+            .iter()
+            .map(|token| {
+                let normalized = normalize(&token.value);
+                get_char_ngram_buckets(&normalized, self.ngram_size, self.num_buckets)
+                    .into_iter()
+                    .map(|bucket| (format!("{}_{}", name, bucket), "1".to_string()))
+                    .collect()
+            })
+            .collect())
+    }
+}
+
+pub struct IsCapitalizedFeature {}
+
+impl IsCapitalizedFeature {
+    pub fn build_features(
+        _args: &HashMap<String, serde_json::Value>,
+        _shared_resources: Arc<SharedResources>,
+    ) -> Result<Vec<Box<dyn Feature>>> {
+        Ok(vec![Box::new(Self {})])
+    }
+}
+
+impl TokenFeature for IsCapitalizedFeature {
+    feature_name!();
+
+    fn compute(&self, tokens: &[Token], token_index: usize) -> Result<Option<String>> {
+        Ok(
+            if tokens[token_index]
+                .value
+                .chars()
+                .next()
+                .map(|c| c.is_uppercase())
+                .unwrap_or(false)
+            {
+                Some("1".to_string())
+            } else {
+                None
+            },
+        )
+    }
+}
+
+/// Casing of the letters of the original token: `lower`, `upper`, `title` or `mixed`
+pub struct CasingPatternFeature {}
+
+impl CasingPatternFeature {
+    pub fn build_features(
+        _args: &HashMap<String, serde_json::Value>,
+        _shared_resources: Arc<SharedResources>,
+    ) -> Result<Vec<Box<dyn Feature>>> {
+        Ok(vec![Box::new(Self {})])
+    }
+}
+
+impl TokenFeature for CasingPatternFeature {
+    feature_name!();
+
+    fn compute(&self, tokens: &[Token], token_index: usize) -> Result<Option<String>> {
+        let letters = tokens[token_index]
+            .value
+            .chars()
+            .filter(|c| c.is_alphabetic())
+            .collect_vec();
+        if letters.is_empty() {
+            return Ok(None);
+        }
+        let pattern = if letters.iter().all(|c| !c.is_uppercase()) {
+            "lower"
+        } else if letters.iter().all(|c| !c.is_lowercase()) {
+            "upper"
+        } else if letters[0].is_uppercase() && letters[1..].iter().all(|c| !c.is_uppercase()) {
+            "title"
+        } else {
+            "mixed"
+        };
+        Ok(Some(pattern.to_string()))
+    }
+}
+
+/// Indicates whether the token is directly attached, without whitespace, to a punctuation
+/// token on its `left`, its `right` or on `both` sides
+pub struct AdjacentPunctuationFeature {}
+
+impl AdjacentPunctuationFeature {
+    pub fn build_features(
+        _args: &HashMap<String, serde_json::Value>,
+        _shared_resources: Arc<SharedResources>,
+    ) -> Result<Vec<Box<dyn Feature>>> {
+        Ok(vec![Box::new(Self {})])
+    }
+}
+
+impl TokenFeature for AdjacentPunctuationFeature {
+    feature_name!();
+
+    fn compute(&self, tokens: &[Token], token_index: usize) -> Result<Option<String>> {
+        let is_punctuation = |token: &Token| {
+            token
+                .value
+                .chars()
+                .all(|c| !c.is_alphanumeric() && !c.is_whitespace())
+        };
+        let token = &tokens[token_index];
+        let left = token_index > 0 && {
+            let previous_token = &tokens[token_index - 1];
+            previous_token.char_range.end == token.char_range.start
+                && is_punctuation(previous_token)
+        };
+        let right = token_index + 1 < tokens.len() && {
+            let next_token = &tokens[token_index + 1];
+            next_token.char_range.start == token.char_range.end && is_punctuation(next_token)
+        };
+        Ok(match (left, right) {
+            (true, true) => Some("both".to_string()),
+            (true, false) => Some("left".to_string()),
+            (false, true) => Some("right".to_string()),
+            (false, false) => None,
+        })
+    }
+}
+
 pub struct CustomEntityMatchFeature {
     entity_name: String,
     tagging_scheme: TaggingScheme,
@@ -267,12 +436,8 @@ pub struct CustomEntityMatchFeature {
     custom_entity_parser: Arc<dyn CustomEntityParser>,
 }
 
-impl Feature for CustomEntityMatchFeature {
-    fn name(&self) -> String {
-        format!("{}_{}", self.feature_kind().identifier(), &self.entity_name)
-    }
-
-    fn build_features(
+impl CustomEntityMatchFeature {
+    pub fn build_features(
         args: &HashMap<String, serde_json::Value>,
         shared_resources: Arc<SharedResources>,
     ) -> Result<Vec<Box<dyn Feature>>> {
@@ -302,23 +467,29 @@ impl Feature for CustomEntityMatchFeature {
             })
             .collect())
     }
+}
+
+impl TokenFeature for CustomEntityMatchFeature {
+    feature_name!(entity_name);
 
     fn compute(&self, tokens: &[Token], token_index: usize) -> Result<Option<String>> {
+        Ok(self.compute_all(tokens)?.swap_remove(token_index))
+    }
+
+    fn compute_all(&self, tokens: &[Token]) -> Result<Vec<Option<String>>> {
         let normalized_tokens = transform_tokens(tokens, self.opt_stemmer.clone());
         let normalized_text = initial_string_from_tokens(&*normalized_tokens);
-
-        Ok(self
+        let entity_ranges = self
             .custom_entity_parser
             .extract_entities(&normalized_text, Some(&[self.entity_name.clone()]), 0)?
             .into_iter()
-            .find(|e| ranges_overlap(&e.range, &normalized_tokens[token_index].char_range))
-            .map(|e| {
-                let entity_token_indexes = (0..normalized_tokens.len())
-                    .filter(|i| ranges_overlap(&normalized_tokens[*i].char_range, &e.range))
-                    .collect_vec();
-                get_scheme_prefix(token_index, &entity_token_indexes, self.tagging_scheme)
-                    .to_string()
-            }))
+            .map(|e| e.range)
+            .collect_vec();
+        Ok(get_entity_match_features(
+            &normalized_tokens,
+            &entity_ranges,
+            self.tagging_scheme,
+        ))
     }
 }
 
@@ -328,16 +499,8 @@ pub struct BuiltinEntityMatchFeature {
     builtin_entity_parser: Arc<dyn BuiltinEntityParser>,
 }
 
-impl Feature for BuiltinEntityMatchFeature {
-    fn name(&self) -> String {
-        format!(
-            "{}_{}",
-            self.feature_kind().identifier(),
-            self.builtin_entity_kind.identifier()
-        )
-    }
-
-    fn build_features(
+impl BuiltinEntityMatchFeature {
+    pub fn build_features(
         args: &HashMap<String, serde_json::Value>,
         shared_resources: Arc<SharedResources>,
     ) -> Result<Vec<Box<dyn Feature>>> {
@@ -357,21 +520,28 @@ impl Feature for BuiltinEntityMatchFeature {
             })
             .collect()
     }
+}
+
+impl TokenFeature for BuiltinEntityMatchFeature {
+    feature_name!(builtin_entity_kind.identifier());
 
     fn compute(&self, tokens: &[Token], token_index: usize) -> Result<Option<String>> {
+        Ok(self.compute_all(tokens)?.swap_remove(token_index))
+    }
+
+    fn compute_all(&self, tokens: &[Token]) -> Result<Vec<Option<String>>> {
         let text = initial_string_from_tokens(tokens);
-        Ok(self
+        let entity_ranges = self
             .builtin_entity_parser
             .extract_entities(&text, Some(&[self.builtin_entity_kind]), true, 0)?
             .into_iter()
-            .find(|e| ranges_overlap(&e.range, &tokens[token_index].char_range))
-            .map(|e| {
-                let entity_token_indexes = (0..tokens.len())
-                    .filter(|i| ranges_overlap(&tokens[*i].char_range, &e.range))
-                    .collect_vec();
-                get_scheme_prefix(token_index, &entity_token_indexes, self.tagging_scheme)
-                    .to_string()
-            }))
+            .map(|e| e.range)
+            .collect_vec();
+        Ok(get_entity_match_features(
+            tokens,
+            &entity_ranges,
+            self.tagging_scheme,
+        ))
     }
 }
 
@@ -380,12 +550,8 @@ pub struct WordClusterFeature {
     word_clusterer: Arc<dyn WordClusterer>,
 }
 
-impl Feature for WordClusterFeature {
-    fn name(&self) -> String {
-        format!("{}_{}", self.feature_kind().identifier(), self.cluster_name)
-    }
-
-    fn build_features(
+impl WordClusterFeature {
+    pub fn build_features(
         args: &HashMap<String, serde_json::Value>,
         shared_resources: Arc<SharedResources>,
     ) -> Result<Vec<Box<dyn Feature>>> {
@@ -405,6 +571,10 @@ impl Feature for WordClusterFeature {
             word_clusterer,
         })])
     }
+}
+
+impl TokenFeature for WordClusterFeature {
+    feature_name!(cluster_name);
 
     fn compute(&self, tokens: &[Token], token_index: usize) -> Result<Option<String>> {
         Ok(self
@@ -413,7 +583,30 @@ impl Feature for WordClusterFeature {
     }
 }
 
-fn transform_tokens(tokens: &[Token], stemmer: Option<Arc<dyn Stemmer>>) -> Vec<Token> {
+/// Entities are extracted once for all tokens, and each token gets the scheme prefix of the
+/// first entity it overlaps with
+fn get_entity_match_features(
+    tokens: &[Token],
+    entity_ranges: &[Range<usize>],
+    tagging_scheme: TaggingScheme,
+) -> Vec<Option<String>> {
+    (0..tokens.len())
+        .map(|token_index| {
+            entity_ranges
+                .iter()
+                .find(|range| ranges_overlap(range, &tokens[token_index].char_range))
+                .map(|range| {
+                    let entity_token_indexes = (0..tokens.len())
+                        .filter(|i| ranges_overlap(&tokens[*i].char_range, range))
+                        .collect_vec();
+                    get_scheme_prefix(token_index, &entity_token_indexes, tagging_scheme)
+                        .to_string()
+                })
+        })
+        .collect()
+}
+
+pub fn transform_tokens(tokens: &[Token], stemmer: Option<Arc<dyn Stemmer>>) -> Vec<Token> {
     let mut current_char_index = 0;
     let mut current_byte_index = 0;
     tokens
@@ -489,6 +682,7 @@ fn parse_as_u64(args: &HashMap<String, serde_json::Value>, arg_name: &str) -> Re
 mod tests {
     use std::iter::FromIterator;
 
+    use maplit::hashmap;
     use snips_nlu_ontology::{BuiltinEntity, SlotValue, TemperatureValue};
     use snips_nlu_utils::language::Language as NluUtilsLanguage;
     use snips_nlu_utils::token::tokenize;
@@ -498,7 +692,9 @@ mod tests {
     use crate::resources::gazetteer::HashSetGazetteer;
     use crate::resources::stemmer::HashMapStemmer;
     use crate::resources::word_clusterer::HashMapWordClusterer;
-    use crate::testutils::{MockedBuiltinEntityParser, MockedCustomEntityParser};
+    use crate::testutils::{
+        MockedBuiltinEntityParser, MockedCustomEntityParser, SharedResourcesBuilder,
+    };
 
     #[test]
     fn test_transform_tokens() {
@@ -592,6 +788,96 @@ mod tests {
         assert_eq!(expected_result, actual_result);
     }
 
+    #[test]
+    fn test_char_ngram_feature() {
+        // Given
+        let tokens = tokenize("flight to a", NluUtilsLanguage::EN);
+        let features = CharNgramFeature::build_features(
+            &hashmap! {
+                "n".to_string() => serde_json::json!(4),
+                "num_buckets".to_string() => serde_json::json!(1),
+            },
+            Arc::new(SharedResourcesBuilder::default().build()),
+        )
+        .unwrap();
+
+        // When
+        let attributes = features[0].compute_attributes(&tokens).unwrap();
+
+        // Then
+        assert_eq!(1, features.len());
+        assert_eq!("char_ngram_4", features[0].name());
+        let expected_attributes = vec![
+            vec![("char_ngram_4_0".to_string(), "1".to_string())],
+            vec![("char_ngram_4_0".to_string(), "1".to_string())],
+            vec![],
+        ];
+        assert_eq!(expected_attributes, attributes);
+    }
+
+    #[test]
+    fn test_is_capitalized_feature() {
+        // Given
+        let tokens = tokenize("Flight af1234 to PARIS", NluUtilsLanguage::EN);
+        let feature = IsCapitalizedFeature {};
+
+        // When
+        let results: Vec<Option<String>> = (0..4)
+            .map(|i| feature.compute(&tokens, i).unwrap())
+            .collect();
+
+        // Then
+        let expected_results = vec![Some("1".to_string()), None, None, Some("1".to_string())];
+        assert_eq!(expected_results, results);
+    }
+
+    #[test]
+    fn test_casing_pattern_feature() {
+        // Given
+        let tokens = tokenize("Flight AF1234 to McDonald 42", NluUtilsLanguage::EN);
+        let feature = CasingPatternFeature {};
+
+        // When
+        let results: Vec<Option<String>> = (0..5)
+            .map(|i| feature.compute(&tokens, i).unwrap())
+            .collect();
+
+        // Then
+        let expected_results = vec![
+            Some("title".to_string()),
+            Some("upper".to_string()),
+            Some("lower".to_string()),
+            Some("mixed".to_string()),
+            None,
+        ];
+        assert_eq!(expected_results, results);
+    }
+
+    #[test]
+    fn test_adjacent_punctuation_feature() {
+        // Given
+        let tokens = tokenize("code AB-12-C3, please", NluUtilsLanguage::EN);
+        let feature = AdjacentPunctuationFeature {};
+
+        // When
+        let results: Vec<Option<String>> = (0..tokens.len())
+            .map(|i| feature.compute(&tokens, i).unwrap())
+            .collect();
+
+        // Then
+        let expected_results = vec![
+            None,
+            Some("right".to_string()),
+            None,
+            Some("both".to_string()),
+            None,
+            Some("both".to_string()),
+            None,
+            None,
+        ];
+        assert_eq!(expected_results, results);
+    }
+
     #[test]
     fn test_shape_feature() {
         // Given
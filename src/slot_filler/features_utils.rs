@@ -1,6 +1,7 @@
 use std::iter::FromIterator;
 use std::str;
 
+use snips_nlu_utils::string::hash_str_to_i32;
 use snips_nlu_utils::token::Token;
 
 pub fn get_word_chunk(
@@ -24,6 +25,25 @@ pub fn get_word_chunk(
     }
 }
 
+/// Returns the hashing buckets of the character n-grams of the word, the word being surrounded
+/// by the `<` and `>` boundary symbols
+pub fn get_char_ngram_buckets(word: &str, ngram_size: usize, num_buckets: usize) -> Vec<usize> {
+    let chars: Vec<char> = format!("<{}>", word).chars().collect();
+    if ngram_size == 0 || ngram_size > chars.len() {
+        return vec![];
+    }
+    let mut buckets: Vec<usize> = chars
+        .windows(ngram_size)
+        .map(|ngram| {
+            let ngram: String = ngram.iter().collect();
+            hash_str_to_i32(&ngram).rem_euclid(num_buckets as i32) as usize
+        })
+        .collect();
+    buckets.sort();
+    buckets.dedup();
+    buckets
+}
+
 pub fn initial_string_from_tokens(tokens: &[Token]) -> String {
     let mut current_index = 0;
     let mut chunks: Vec<String> = Vec::with_capacity(2 * tokens.len() - 1);
@@ -41,8 +61,32 @@ pub fn initial_string_from_tokens(tokens: &[Token]) -> String {
 
 #[cfg(test)]
 mod tests {
+    use itertools::Itertools;
+
     use super::*;
 
+    #[test]
+    fn test_get_char_ngram_buckets() {
+        // Given
+        let word = "ab";
+
+        // When
+        let single_bucket = get_char_ngram_buckets(word, 2, 1);
+        let too_long_ngrams = get_char_ngram_buckets(word, 5, 10);
+        let buckets = get_char_ngram_buckets(word, 2, 1000);
+
+        // Then
+        assert_eq!(vec![0], single_bucket);
+        assert!(too_long_ngrams.is_empty());
+        let expected_buckets = vec!["<a", "ab", "b>"]
+            .into_iter()
+            .map(|ngram| hash_str_to_i32(ngram).rem_euclid(1000) as usize)
+            .sorted()
+            .dedup()
+            .collect::<Vec<_>>();
+        assert_eq!(expected_buckets, buckets);
+    }
+
     #[test]
     fn test_get_word_chunk() {
         // Given
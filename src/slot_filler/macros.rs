@@ -1,3 +1,18 @@
+/// Implements the `name` method of a builtin feature, which is the identifier of its kind
+/// optionally followed by a parameter of the feature
+macro_rules! feature_name {
+    () => {
+        fn name(&self) -> String {
+            self.feature_kind().identifier().to_string()
+        }
+    };
+    ($($parameter:tt)+) => {
+        fn name(&self) -> String {
+            format!("{}_{}", self.feature_kind().identifier(), self.$($parameter)+)
+        }
+    };
+}
+
 #[macro_export]
 macro_rules! get_features {
     ([$(($feature_type:ident,$feature_name:ident)),*]) => {
@@ -24,20 +39,17 @@ macro_rules! get_features {
             }
         )*
 
-        fn get_features(
-            f: &FeatureFactory,
-            shared_resources: Arc<SharedResources>,
-        ) -> Result<Vec<FeatureOffsetter>> {
-            let features = match f.factory_name.as_ref() {
+        impl Default for FeatureRegistry {
+            fn default() -> Self {
+                let mut builders: HashMap<String, Box<FeatureBuilder>> = HashMap::new();
                 $(
-                    stringify!($feature_name) => $feature_type::build_features(&f.args, shared_resources),
+                    builders.insert(
+                        stringify!($feature_name).to_string(),
+                        Box::new($feature_type::build_features),
+                    );
                 )*
-                _ => anyhow::bail!("Feature {} not implemented", f.factory_name),
-            };
-            Ok(features?
-                .into_iter()
-                .map(|feature| FeatureOffsetter { feature, offsets: f.offsets.clone() })
-                .collect())
+                Self { builders }
+            }
         }
     }
 }
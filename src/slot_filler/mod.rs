@@ -1,12 +1,17 @@
 #[macro_use]
 mod macros;
+mod constrained_decoding;
 pub mod crf_slot_filler;
 mod crf_utils;
+pub mod entity_match_slot_filler;
+mod feature_cache;
 mod feature_processor;
 mod features;
 mod features_utils;
 
+use std::collections::HashMap;
 use std::fs::File;
+use std::ops::Range;
 use std::path::Path;
 use std::sync::Arc;
 
@@ -14,16 +19,52 @@ use snips_nlu_utils::token::Token;
 
 use crate::models::ProcessingUnitMetadata;
 use crate::resources::SharedResources;
-use crate::slot_utils::InternalSlot;
+use crate::slot_utils::{InternalSlot, InternalSlotSequence};
 use anyhow::{anyhow, Context, Result};
 
 pub use self::crf_slot_filler::*;
-use self::crf_utils::TaggingScheme;
+pub use self::crf_utils::TaggingScheme;
+pub use self::entity_match_slot_filler::EntityMatchSlotFiller;
+pub use self::feature_cache::with_feature_cache;
+pub(crate) use self::feature_cache::with_new_feature_cache;
+pub use self::feature_processor::{Feature, FeatureRegistry, TokenFeature};
+
+/// Token of an input along with the tag predicted by a slot filler
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaggedToken {
+    pub value: String,
+    pub range: Range<usize>,
+    pub char_range: Range<usize>,
+    pub tag: String,
+    /// Marginal probability of each tag at this token
+    pub marginals: HashMap<String, f64>,
+}
+
+/// Tags predicted for each token of an input
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenTagging {
+    /// Slot filler which produced the tags
+    pub slot_filler: ProcessingUnitMetadata,
+    pub tagging_scheme: TaggingScheme,
+    pub tokens: Vec<TaggedToken>,
+}
 
 pub trait SlotFiller: Send + Sync {
     fn get_tagging_scheme(&self) -> TaggingScheme;
+    /// Returns whether the slot filler has slots to extract
+    fn has_slots(&self) -> bool;
     fn get_slots(&self, text: &str) -> Result<Vec<InternalSlot>>;
+    /// Returns the slots of up to `n` distinct tag sequences, sorted by decreasing probability
+    ///
+    /// This is an approximate n-best: candidate sequences come from a beam search pruned with
+    /// the per-token marginals and are then ranked by their exact probability. The most
+    /// probable sequence is always returned first, but the following ones are not guaranteed
+    /// to be the exact next best sequences.
+    fn get_slots_nbest(&self, text: &str, n: usize) -> Result<Vec<InternalSlotSequence>>;
     fn get_sequence_probability(&self, tokens: &[Token], tags: Vec<String>) -> Result<f64>;
+    /// Returns the decoded slots along with the probability of the corresponding tag sequence
+    fn get_slots_with_probability(&self, text: &str) -> Result<InternalSlotSequence>;
+    fn tag_tokens(&self, text: &str) -> Result<TokenTagging>;
 }
 
 pub fn build_slot_filler<P: AsRef<Path>>(
@@ -43,6 +84,9 @@ pub fn build_slot_filler<P: AsRef<Path>>(
         ProcessingUnitMetadata::CrfSlotFiller => {
             Ok(Box::new(CRFSlotFiller::from_path(path, shared_resources)?) as _)
         }
+        ProcessingUnitMetadata::EntityMatchSlotFiller => {
+            Ok(Box::new(EntityMatchSlotFiller::from_path(path, shared_resources)?) as _)
+        }
         _ => Err(anyhow!("{:?} is not a slot filler", metadata)),
     }
 }
@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+
+use itertools::Itertools;
+use snips_nlu_ontology::{IntentParserResult, Slot};
+
+use crate::models::{IntentSchema, IntentSlotsSchema};
+use crate::utils::{EntityName, IntentName, SlotName};
+use anyhow::{bail, Result};
+
+/// Discrepancies between the slots extracted for an intent and the intent schema
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SlotReport {
+    /// Required slots which were not extracted, in the order of the schema
+    pub missing_slots: Vec<SlotName>,
+    /// Non repeatable slots which were extracted several times
+    pub duplicated_slots: Vec<SlotName>,
+    /// Mutually exclusive slots which were extracted together, for each violated group
+    pub conflicting_slots: Vec<Vec<SlotName>>,
+}
+
+impl SlotReport {
+    /// Returns true when the slots satisfy all the constraints of the schema
+    pub fn is_valid(&self) -> bool {
+        self.missing_slots.is_empty()
+            && self.duplicated_slots.is_empty()
+            && self.conflicting_slots.is_empty()
+    }
+}
+
+/// Parsing result along with the report of its slots against the intent schema
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaParserResult {
+    pub result: IntentParserResult,
+    pub slot_report: SlotReport,
+}
+
+/// Checks that the schema only refers to known intents and slots
+pub fn validate_intent_schema(
+    schema: &IntentSchema,
+    slot_name_mappings: &HashMap<IntentName, HashMap<SlotName, EntityName>>,
+) -> Result<()> {
+    for (intent, intent_schema) in schema.intents.iter() {
+        let slot_name_mapping = match slot_name_mappings.get(intent) {
+            Some(mapping) => mapping,
+            None => bail!("Unknown intent '{}' in intent schema", intent),
+        };
+        let schema_slots = intent_schema
+            .required_slots
+            .iter()
+            .chain(intent_schema.repeatable_slots.iter())
+            .chain(intent_schema.mutually_exclusive_slots.iter().flatten());
+        for slot_name in schema_slots {
+            if !slot_name_mapping.contains_key(slot_name) {
+                bail!(
+                    "Unknown slot '{}' for intent '{}' in intent schema",
+                    slot_name,
+                    intent
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn check_slots(intent_schema: &IntentSlotsSchema, slots: &[Slot]) -> SlotReport {
+    let slots_counts = slots.iter().fold(HashMap::new(), |mut counts, slot| {
+        *counts.entry(&slot.slot_name).or_insert(0) += 1;
+        counts
+    });
+    let missing_slots = intent_schema
+        .required_slots
+        .iter()
+        .filter(|slot_name| !slots_counts.contains_key(slot_name))
+        .cloned()
+        .collect();
+    let duplicated_slots = slots
+        .iter()
+        .map(|slot| &slot.slot_name)
+        .unique()
+        .filter(|slot_name| {
+            slots_counts[slot_name] > 1 && !intent_schema.repeatable_slots.contains(slot_name)
+        })
+        .cloned()
+        .collect();
+    let conflicting_slots = intent_schema
+        .mutually_exclusive_slots
+        .iter()
+        .map(|group| {
+            group
+                .iter()
+                .filter(|slot_name| slots_counts.contains_key(slot_name))
+                .unique()
+                .cloned()
+                .collect::<Vec<_>>()
+        })
+        .filter(|present_slots| present_slots.len() > 1)
+        .collect();
+    SlotReport {
+        missing_slots,
+        duplicated_slots,
+        conflicting_slots,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maplit::hashmap;
+    use snips_nlu_ontology::SlotValue;
+
+    fn get_slot(raw_value: &str, slot_name: &str) -> Slot {
+        Slot {
+            raw_value: raw_value.to_string(),
+            value: SlotValue::Custom(raw_value.into()),
+            alternatives: vec![],
+            range: 0..raw_value.chars().count(),
+            entity: slot_name.to_string(),
+            slot_name: slot_name.to_string(),
+            confidence_score: None,
+        }
+    }
+
+    #[test]
+    fn test_check_slots() {
+        // Given
+        let intent_schema = IntentSlotsSchema {
+            required_slots: vec!["destination".to_string(), "date".to_string()],
+            repeatable_slots: vec!["passenger".to_string()],
+            mutually_exclusive_slots: vec![vec!["date".to_string(), "flexible".to_string()]],
+        };
+        let slots = vec![
+            get_slot("paris", "destination"),
+            get_slot("london", "destination"),
+            get_slot("john", "passenger"),
+            get_slot("jane", "passenger"),
+        ];
+
+        // When
+        let slot_report = check_slots(&intent_schema, &slots);
+
+        // Then
+        let expected_report = SlotReport {
+            missing_slots: vec!["date".to_string()],
+            duplicated_slots: vec!["destination".to_string()],
+            conflicting_slots: vec![],
+        };
+        assert_eq!(expected_report, slot_report);
+        assert!(!slot_report.is_valid());
+    }
+
+    #[test]
+    fn test_check_slots_with_conflicting_slots() {
+        // Given
+        let intent_schema = IntentSlotsSchema {
+            required_slots: vec!["destination".to_string()],
+            repeatable_slots: vec![],
+            mutually_exclusive_slots: vec![vec!["date".to_string(), "flexible".to_string()]],
+        };
+        let slots = vec![
+            get_slot("paris", "destination"),
+            get_slot("flexible", "flexible"),
+            get_slot("tomorrow", "date"),
+        ];
+
+        // When
+        let slot_report = check_slots(&intent_schema, &slots);
+
+        // Then
+        let expected_report = SlotReport {
+            missing_slots: vec![],
+            duplicated_slots: vec![],
+            conflicting_slots: vec![vec!["date".to_string(), "flexible".to_string()]],
+        };
+        assert_eq!(expected_report, slot_report);
+    }
+
+    #[test]
+    fn test_validate_intent_schema() {
+        // Given
+        let slot_name_mappings = hashmap! {
+            "BookFlight".to_string() => hashmap! {
+                "destination".to_string() => "city".to_string(),
+            },
+        };
+        let valid_schema = IntentSchema {
+            intents: hashmap! {
+                "BookFlight".to_string() => IntentSlotsSchema {
+                    required_slots: vec!["destination".to_string()],
+                    ..Default::default()
+                },
+            },
+        };
+        let invalid_schema = IntentSchema {
+            intents: hashmap! {
+                "BookFlight".to_string() => IntentSlotsSchema {
+                    required_slots: vec!["date".to_string()],
+                    ..Default::default()
+                },
+            },
+        };
+
+        // When / Then
+        assert!(validate_intent_schema(&valid_schema, &slot_name_mappings).is_ok());
+        assert!(validate_intent_schema(&invalid_schema, &slot_name_mappings).is_err());
+    }
+}
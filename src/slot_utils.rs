@@ -16,6 +16,14 @@ pub struct InternalSlot {
     pub slot_name: SlotName,
 }
 
+/// Slots extracted from one of the candidate tag sequences, along with the probability of
+/// this sequence
+#[derive(Debug, Clone, PartialEq)]
+pub struct InternalSlotSequence {
+    pub slots: Vec<InternalSlot>,
+    pub probability: f64,
+}
+
 pub fn resolve_builtin_slot(
     internal_slot: InternalSlot,
     builtin_entities: &[BuiltinEntity],
@@ -66,6 +74,13 @@ pub fn resolve_custom_slot(
                 }
             }),
     };
+    // Entities matched despite typos have a score lower than 1.0, which is used as slot
+    // confidence score
+    let match_score = opt_matching_entity
+        .as_ref()
+        .map(|matching_entity| custom_entity_parser.match_score(matching_entity))
+        .transpose()?
+        .filter(|score| *score < 1.0);
     let resolved_slot = opt_matching_entity
         .map(|matching_entity| {
             Some((
@@ -81,7 +96,9 @@ pub fn resolve_custom_slot(
             }
         })
         .map(|(resolved_value, alternatives)| {
-            convert_to_custom_slot(internal_slot, resolved_value, alternatives)
+            let mut slot = convert_to_custom_slot(internal_slot, resolved_value, alternatives);
+            slot.confidence_score = match_score;
+            slot
         });
     Ok(resolved_slot)
 }
@@ -124,6 +141,7 @@ mod tests {
     use super::*;
     use crate::entity_parser::CachingCustomEntityParser;
     use crate::models::nlu_engine::Entity;
+    use crate::resources::normalizer::TextNormalizer;
     use crate::testutils::*;
     use snips_nlu_ontology::*;
     use std::iter::FromIterator;
@@ -322,6 +340,66 @@ mod tests {
         assert_eq!(expected_result, resolved_slot);
     }
 
+    #[test]
+    fn test_resolve_custom_slot_with_fuzzy_match() {
+        // Given
+        struct FuzzyCustomEntityParser;
+
+        impl CustomEntityParser for FuzzyCustomEntityParser {
+            fn extract_entities(
+                &self,
+                _sentence: &str,
+                _filter_entity_kinds: Option<&[String]>,
+                _max_alternative_resolved_values: usize,
+            ) -> Result<Vec<CustomEntity>> {
+                Ok(vec![])
+            }
+
+            fn match_score(&self, _entity: &CustomEntity) -> Result<f32> {
+                Ok(0.8)
+            }
+        }
+
+        let entity = Entity {
+            automatically_extensible: false,
+        };
+        let internal_slot = InternalSlot {
+            value: "beyonse".to_string(),
+            char_range: 5..12,
+            entity: "artist".to_string(),
+            slot_name: "artist".to_string(),
+        };
+        let custom_entities = vec![CustomEntity {
+            value: "beyonse".to_string(),
+            range: 5..12,
+            resolved_value: "Beyoncé".to_string(),
+            alternative_resolved_values: vec![],
+            entity_identifier: "artist".to_string(),
+        }];
+
+        // When
+        let resolved_slot = resolve_custom_slot(
+            internal_slot,
+            &entity,
+            &custom_entities,
+            Arc::new(FuzzyCustomEntityParser),
+            0,
+        )
+        .unwrap();
+
+        // Then
+        let expected_result = Some(Slot {
+            raw_value: "beyonse".to_string(),
+            value: SlotValue::Custom("Beyoncé".into()),
+            alternatives: vec![],
+            range: 5..12,
+            entity: "artist".to_string(),
+            slot_name: "artist".to_string(),
+            confidence_score: Some(0.8),
+        });
+        assert_eq!(expected_result, resolved_slot);
+    }
+
     #[test]
     fn test_resolve_custom_slot_when_no_entities_found_on_whole_input() {
         // Given
@@ -509,7 +587,12 @@ mod tests {
             .join("nlu_engine_game")
             .join("custom_entity_parser");
 
-        let custom_entity_parser = CachingCustomEntityParser::from_path(parser_path, 1000).unwrap();
+        let custom_entity_parser = CachingCustomEntityParser::from_path(
+            parser_path,
+            1000,
+            Arc::new(TextNormalizer::default()),
+        )
+        .unwrap();
 
         // When
         let resolved_slot = resolve_custom_slot(
@@ -1,15 +1,24 @@
 use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
 use std::iter::FromIterator;
+use std::path::Path;
 use std::sync::Arc;
 
 use ndarray::prelude::*;
 use snips_nlu_ontology::{BuiltinEntity, BuiltinEntityKind};
 
-use crate::entity_parser::{BuiltinEntityParser, CustomEntity, CustomEntityParser};
+use crate::entity_parser::{
+    BuiltinEntityParser, CustomEntity, CustomEntityParser, CustomEntityParserUsage,
+};
+use crate::models::Entity;
 use crate::resources::gazetteer::Gazetteer;
+use crate::resources::normalizer::{Normalizer, TextNormalizer};
 use crate::resources::stemmer::Stemmer;
 use crate::resources::word_clusterer::WordClusterer;
+use crate::resources::word_embeddings::WordEmbeddings;
 use crate::resources::SharedResources;
+use crate::slot_filler::FeatureRegistry;
+use crate::utils::EntityName;
 use anyhow::{Context, Result};
 
 pub fn assert_epsilon_eq_array1(a: &Array1<f32>, b: &Array1<f32>, epsilon: f32) {
@@ -29,8 +38,12 @@ pub struct SharedResourcesBuilder {
     custom_entity_parser: Arc<dyn CustomEntityParser>,
     gazetteers: HashMap<String, Arc<dyn Gazetteer>>,
     stemmer: Option<Arc<dyn Stemmer>>,
+    normalizer: Arc<dyn Normalizer>,
+    custom_entities: HashMap<EntityName, Entity>,
     word_clusterers: HashMap<String, Arc<dyn WordClusterer>>,
+    word_embeddings: HashMap<String, Arc<dyn WordEmbeddings>>,
     stop_words: HashSet<String>,
+    feature_registry: FeatureRegistry,
 }
 
 impl Default for SharedResourcesBuilder {
@@ -40,8 +53,12 @@ impl Default for SharedResourcesBuilder {
             custom_entity_parser: Arc::<MockedCustomEntityParser>::default(),
             gazetteers: HashMap::default(),
             stemmer: None,
+            normalizer: Arc::new(TextNormalizer::default()),
+            custom_entities: HashMap::default(),
             word_clusterers: HashMap::default(),
+            word_embeddings: HashMap::default(),
             stop_words: HashSet::default(),
+            feature_registry: FeatureRegistry::default(),
         }
     }
 }
@@ -57,19 +74,48 @@ impl SharedResourcesBuilder {
         self
     }
 
+    pub fn stemmer<S: Stemmer + 'static>(mut self, stemmer: S) -> Self {
+        self.stemmer = Some(Arc::new(stemmer) as _);
+        self
+    }
+
+    pub fn custom_entities(mut self, custom_entities: HashMap<EntityName, Entity>) -> Self {
+        self.custom_entities = custom_entities;
+        self
+    }
+
+    pub fn word_embeddings<E: WordEmbeddings + 'static>(
+        mut self,
+        name: &str,
+        word_embeddings: E,
+    ) -> Self {
+        self.word_embeddings
+            .insert(name.to_string(), Arc::new(word_embeddings) as _);
+        self
+    }
+
     pub fn stop_words(mut self, stop_words: HashSet<String>) -> Self {
         self.stop_words = stop_words;
         self
     }
 
+    pub fn feature_registry(mut self, feature_registry: FeatureRegistry) -> Self {
+        self.feature_registry = feature_registry;
+        self
+    }
+
     pub fn build(self) -> SharedResources {
         SharedResources {
             builtin_entity_parser: self.builtin_entity_parser,
             custom_entity_parser: self.custom_entity_parser,
             gazetteers: self.gazetteers,
             stemmer: self.stemmer,
+            normalizer: self.normalizer,
+            custom_entities: self.custom_entities,
             word_clusterers: self.word_clusterers,
+            word_embeddings: self.word_embeddings,
             stop_words: self.stop_words,
+            feature_registry: Arc::new(self.feature_registry),
         }
     }
 }
@@ -106,6 +152,7 @@ impl FromIterator<(String, Vec<BuiltinEntity>)> for MockedBuiltinEntityParser {
 #[derive(Default)]
 pub struct MockedCustomEntityParser {
     pub mocked_outputs: HashMap<String, Vec<CustomEntity>>,
+    pub parser_usage: Option<CustomEntityParserUsage>,
 }
 
 impl CustomEntityParser for MockedCustomEntityParser {
@@ -121,12 +168,79 @@ impl CustomEntityParser for MockedCustomEntityParser {
             .cloned()
             .unwrap_or_else(|| vec![]))
     }
+
+    fn parser_usage(&self) -> CustomEntityParserUsage {
+        self.parser_usage
+            .clone()
+            .unwrap_or(CustomEntityParserUsage::WithoutStems)
+    }
 }
 
 impl FromIterator<(String, Vec<CustomEntity>)> for MockedCustomEntityParser {
     fn from_iter<T: IntoIterator<Item = (String, Vec<CustomEntity>)>>(iter: T) -> Self {
         Self {
             mocked_outputs: HashMap::from_iter(iter),
+            parser_usage: None,
         }
     }
 }
+
+/// Writes a gazetteer parser of a single entity, with one trained value per token
+pub fn write_gazetteer_parser(gazetteer_parser_dir: &Path, entity: &str, values: &[(&str, &str)]) {
+    let entity_parser_dir = gazetteer_parser_dir.join("parser_1");
+    fs::create_dir_all(&entity_parser_dir).unwrap();
+    serde_json::to_writer(
+        File::create(gazetteer_parser_dir.join("metadata.json")).unwrap(),
+        &serde_json::json!({
+            "parsers_metadata": [{"entity_identifier": entity, "entity_parser": "parser_1"}]
+        }),
+    )
+    .unwrap();
+    serde_json::to_writer(
+        File::create(entity_parser_dir.join("metadata.json")).unwrap(),
+        &serde_json::json!({
+            "version": "0.7.2",
+            "parser_filename": "parser",
+            "threshold": 0.5,
+            "stop_words": [],
+            "edge_cases": []
+        }),
+    )
+    .unwrap();
+
+    let tokens: HashMap<String, u32> = values
+        .iter()
+        .enumerate()
+        .map(|(index, (raw_value, _))| (raw_value.to_string(), index as u32))
+        .collect();
+    let resolved_values: HashMap<u32, String> = values
+        .iter()
+        .enumerate()
+        .map(|(index, (_, resolved_value))| (index as u32, resolved_value.to_string()))
+        .collect();
+    let token_to_resolved_values: HashMap<u32, Vec<u32>> = (0..values.len() as u32)
+        .map(|index| (index, vec![index]))
+        .collect();
+    let resolved_value_to_tokens: HashMap<u32, (u32, Vec<u32>)> = (0..values.len() as u32)
+        .map(|index| (index, (index, vec![index])))
+        .collect();
+    let nb_values = values.len() as u32;
+    let parser = (
+        (tokens, nb_values),
+        (resolved_values, nb_values),
+        token_to_resolved_values,
+        resolved_value_to_tokens,
+        0_usize,
+        Vec::<String>::new(),
+        Vec::<u32>::new(),
+        Vec::<u32>::new(),
+        Vec::<u32>::new(),
+        0.5_f32,
+        Option::<()>::None,
+    );
+    fs::write(
+        entity_parser_dir.join("parser"),
+        rmp_serde::to_vec(&parser).unwrap(),
+    )
+    .unwrap();
+}
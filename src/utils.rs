@@ -4,11 +4,14 @@ use std::io;
 use std::ops::Range;
 use std::path::{Component, Path, PathBuf};
 
-use zip::ZipArchive;
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
 
 use snips_nlu_ontology::BuiltinEntity;
+use snips_nlu_utils::language::Language as NluUtilsLanguage;
 use snips_nlu_utils::range::ranges_overlap;
 use snips_nlu_utils::string::{substring_with_char_range, suffix_from_char_index};
+use snips_nlu_utils::token::tokenize;
 
 use crate::entity_parser::custom_entity_parser::CustomEntity;
 use anyhow::{anyhow, Result};
@@ -63,6 +66,25 @@ where
     deduplicated_items
 }
 
+/// Copies a directory and all its content, the destination directory must not exist
+pub fn copy_directory(source_dir: &Path, dest_dir: &Path) -> Result<()> {
+    fs::create_dir_all(dest_dir)?;
+    for entry in fs::read_dir(source_dir)? {
+        let source_path = entry?.path();
+        let dest_path = dest_dir.join(
+            source_path
+                .file_name()
+                .ok_or_else(|| anyhow!("Invalid file path {:?}", source_path))?,
+        );
+        if source_path.is_dir() {
+            copy_directory(&source_path, &dest_path)?;
+        } else {
+            fs::copy(&source_path, &dest_path)?;
+        }
+    }
+    Ok(())
+}
+
 pub fn extract_nlu_engine_zip_archive<R: io::Read + io::Seek>(
     zip_reader: R,
     dest_path: &Path,
@@ -104,6 +126,58 @@ pub fn extract_nlu_engine_zip_archive<R: io::Read + io::Seek>(
     Ok(dest_path.join(engine_dir_name))
 }
 
+/// Writes an engine directory to a zip archive having the layout expected by
+/// `extract_nlu_engine_zip_archive`, skipping the files and directories whose name is excluded
+pub fn write_nlu_engine_zip_archive<W: io::Write + io::Seek>(
+    engine_dir: &Path,
+    zip_writer: W,
+    excluded_names: &[&str],
+) -> Result<()> {
+    let engine_dir_name = engine_dir
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| anyhow!("Invalid engine directory {:?}", engine_dir))?;
+    let mut archive = ZipWriter::new(zip_writer);
+    write_zip_directory(
+        &mut archive,
+        engine_dir,
+        engine_dir_name.to_string(),
+        excluded_names,
+    )?;
+    archive.finish()?;
+    Ok(())
+}
+
+fn write_zip_directory<W: io::Write + io::Seek>(
+    archive: &mut ZipWriter<W>,
+    dir: &Path,
+    archive_dir_name: String,
+    excluded_names: &[&str],
+) -> Result<()> {
+    archive.add_directory(format!("{}/", archive_dir_name), FileOptions::default())?;
+    let mut paths = fs::read_dir(dir)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>>>()?;
+    paths.sort();
+    for path in paths {
+        let file_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| anyhow!("Invalid file path {:?}", path))?;
+        if excluded_names.contains(&file_name) {
+            continue;
+        }
+        let archive_path = format!("{}/{}", archive_dir_name, file_name);
+        if path.is_dir() {
+            write_zip_directory(archive, &path, archive_path, excluded_names)?;
+        } else {
+            archive.start_file(archive_path, FileOptions::default())?;
+            io::copy(&mut fs::File::open(&path)?, archive)?;
+        }
+    }
+    Ok(())
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct MatchedEntity {
     pub range: Range<usize>,
@@ -179,12 +253,91 @@ pub fn deduplicate_overlapping_entities(entities: Vec<MatchedEntity>) -> Vec<Mat
     deduped
 }
 
+/// Splits an input on coordinating conjunctions
+///
+/// The character ranges of the resulting segments are returned, without the conjunctions
+/// themselves. Empty segments are ignored.
+pub fn split_on_conjunctions(input: &str, language: NluUtilsLanguage) -> Vec<Range<usize>> {
+    let conjunctions = get_conjunctions(language);
+    let mut segments = vec![];
+    let mut current_segment: Option<Range<usize>> = None;
+    for token in tokenize(input, language) {
+        if conjunctions.contains(&&*token.value.to_lowercase()) {
+            segments.extend(current_segment.take());
+        } else {
+            current_segment = Some(match current_segment {
+                Some(segment) => segment.start..token.char_range.end,
+                None => token.char_range.clone(),
+            });
+        }
+    }
+    segments.extend(current_segment);
+    segments
+}
+
+fn get_conjunctions(language: NluUtilsLanguage) -> &'static [&'static str] {
+    match language {
+        NluUtilsLanguage::DE => &["und", "dann"],
+        NluUtilsLanguage::EN => &["and", "then"],
+        NluUtilsLanguage::ES => &["y", "luego"],
+        NluUtilsLanguage::FR => &["et", "puis"],
+        NluUtilsLanguage::IT => &["e", "poi"],
+        NluUtilsLanguage::PT_PT | NluUtilsLanguage::PT_BR => &["e", "depois"],
+        NluUtilsLanguage::JA | NluUtilsLanguage::KO => &[],
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use snips_nlu_utils::range::ranges_overlap;
     use std::ops::Range;
 
+    #[test]
+    fn test_write_nlu_engine_zip_archive() {
+        // Given
+        let engine_parent_dir = tempfile::tempdir().unwrap();
+        let engine_dir = engine_parent_dir.path().join("my_engine");
+        fs::create_dir_all(engine_dir.join("custom_entity_parser")).unwrap();
+        fs::write(engine_dir.join("nlu_engine.json"), "engine").unwrap();
+        fs::write(
+            engine_dir
+                .join("custom_entity_parser")
+                .join("metadata.json"),
+            "metadata",
+        )
+        .unwrap();
+        fs::write(engine_dir.join(".excluded"), "excluded").unwrap();
+        let mut archive = io::Cursor::new(vec![]);
+
+        // When
+        write_nlu_engine_zip_archive(&engine_dir, &mut archive, &[".excluded"]).unwrap();
+
+        // Then
+        let extraction_dir = tempfile::tempdir().unwrap();
+        archive.set_position(0);
+        let extracted_engine_dir =
+            extract_nlu_engine_zip_archive(archive, extraction_dir.path()).unwrap();
+        assert_eq!(
+            extraction_dir.path().join("my_engine"),
+            extracted_engine_dir
+        );
+        assert_eq!(
+            "engine",
+            fs::read_to_string(extracted_engine_dir.join("nlu_engine.json")).unwrap()
+        );
+        assert_eq!(
+            "metadata",
+            fs::read_to_string(
+                extracted_engine_dir
+                    .join("custom_entity_parser")
+                    .join("metadata.json")
+            )
+            .unwrap()
+        );
+        assert!(!extracted_engine_dir.join(".excluded").exists());
+    }
+
     #[test]
     fn test_deduplicate_items_works() {
         // Given
@@ -202,4 +355,17 @@ mod tests {
         let expected_items = vec![0..8, 9..13];
         assert_eq!(expected_items, dedup_items);
     }
+
+    #[test]
+    fn test_split_on_conjunctions() {
+        // Given
+        let input = "turn off the lights and play some jazz then";
+
+        // When
+        let segments = split_on_conjunctions(input, NluUtilsLanguage::EN);
+
+        // Then
+        let expected_segments = vec![0..19, 24..38];
+        assert_eq!(expected_segments, segments);
+    }
 }